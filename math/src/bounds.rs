@@ -0,0 +1,296 @@
+use crate::{Matrix4, Point3, Real, Transform, Vector3};
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb<T> {
+    pub min: Point3<T>,
+    pub max: Point3<T>,
+}
+
+impl<T: Real> Aabb<T> {
+    /// Contains nothing, the neutral element of `union`.
+    pub const EMPTY: Self = Aabb {
+        min: Vector3::new(T::INFINITY, T::INFINITY, T::INFINITY),
+        max: Vector3::new(T::NEG_INFINITY, T::NEG_INFINITY, T::NEG_INFINITY),
+    };
+
+    pub fn from_points(points: impl IntoIterator<Item = Point3<T>>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |bounds, p| {
+            bounds.union(&Aabb { min: p, max: p })
+        })
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Aabb {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn centroid(&self) -> Point3<T> {
+        (self.min + self.max) / (T::ONE + T::ONE)
+    }
+
+    /// Size along every axis.
+    pub fn extent(&self) -> Vector3<T> {
+        self.max - self.min
+    }
+
+    /// The eight corners, x changes fastest.
+    pub fn corners(&self) -> [Point3<T>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vector3::new(a.x, a.y, a.z),
+            Vector3::new(b.x, a.y, a.z),
+            Vector3::new(a.x, b.y, a.z),
+            Vector3::new(b.x, b.y, a.z),
+            Vector3::new(a.x, a.y, b.z),
+            Vector3::new(b.x, a.y, b.z),
+            Vector3::new(a.x, b.y, b.z),
+            Vector3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Box around the transformed corners.
+    pub fn transform(&self, transform: &Transform<T>) -> Self {
+        self.transform_matrix(transform.matrix())
+    }
+
+    /// Box around the corners moved by the affine `matrix`, which need not be invertible.
+    pub fn transform_matrix(&self, matrix: &Matrix4<T>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        Self::from_points(self.corners().map(|corner| matrix.transform_point(corner)))
+    }
+
+    pub fn translate(&self, offset: Vector3<T>) -> Self {
+        Aabb {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Slab test against the part of a ray between `t_min` and `t_max`,
+    /// `inv_direction` holds the reciprocals of the direction coordinates.
+    pub fn hit(&self, origin: Point3<T>, inv_direction: Vector3<T>, t_min: T, t_max: T) -> bool {
+        let mut t_enter = t_min;
+        let mut t_exit = t_max;
+
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) * inv_direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inv_direction[axis];
+            // written so that a NaN from 0 * inf leaves the interval alone
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > t_enter {
+                t_enter = near;
+            }
+            if far < t_exit {
+                t_exit = far;
+            }
+        }
+
+        t_enter <= t_exit
+    }
+}
+
+/// Sphere around a set of points, for culling whole objects before looking at their parts.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere<T> {
+    pub center: Point3<T>,
+    pub radius: T,
+}
+
+impl<T: Real> BoundingSphere<T> {
+    /// Contains nothing, not even its centre, and stays so under any transform.
+    pub const EMPTY: Self = BoundingSphere {
+        center: Vector3::new(T::ZERO, T::ZERO, T::ZERO),
+        radius: T::NEG_INFINITY,
+    };
+
+    /// A small sphere around all of `points`, within a few percent of the smallest one.
+    /// Ritter's: a sphere through the two points furthest apart along an axis grows just
+    /// enough for every point outside it. Whichever of that and the sphere around the
+    /// middle of the box of the points is smaller wins, the radius of each being the
+    /// distance to its furthest point, so rounding never leaves one outside.
+    pub fn from_points(points: &[Point3<T>]) -> Self {
+        if points.is_empty() {
+            return Self::EMPTY;
+        }
+
+        let two = T::ONE + T::ONE;
+        let mut lowest = [points[0]; 3];
+        let mut highest = [points[0]; 3];
+        for &p in points {
+            for axis in 0..3 {
+                if p[axis] < lowest[axis][axis] {
+                    lowest[axis] = p;
+                }
+                if p[axis] > highest[axis][axis] {
+                    highest[axis] = p;
+                }
+            }
+        }
+        let (a, b) = (0..3)
+            .map(|axis| (lowest[axis], highest[axis]))
+            .max_by(|(a, b), (c, d)| (*b - *a).length().total_cmp(&(*d - *c).length()))
+            .unwrap_or((points[0], points[0]));
+
+        let mut center = (a + b) / two;
+        let mut radius = (b - a).length() / two;
+        for &p in points {
+            let distance = (p - center).length();
+            if distance > radius {
+                // the smallest sphere around the old one and `p`
+                let grown = (radius + distance) / two;
+                center += (p - center) * ((grown - radius) / distance);
+                radius = grown;
+            }
+        }
+
+        let around = |center: Point3<T>| BoundingSphere {
+            center,
+            radius: points
+                .iter()
+                .fold(T::ZERO, |radius, &p| radius.max((p - center).length())),
+        };
+        let ritter = around(center);
+        let boxed = around(Aabb::from_points(points.iter().copied()).centroid());
+        if boxed.radius < ritter.radius {
+            boxed
+        } else {
+            ritter
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius < T::ZERO
+    }
+
+    /// Sphere around this one moved by the affine `matrix`. The radius grows by the most
+    /// the matrix stretches any direction, bounded by the square root of the largest row
+    /// sum of MᵀM, so it never shrinks below the transformed points. The bound is exact
+    /// for rotations and uniform scales, non uniform ones and shears get a bit more.
+    pub fn transform_matrix(&self, matrix: &Matrix4<T>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let m = &matrix.values;
+        let column = |j: usize| Vector3::new(m[0][j], m[1][j], m[2][j]);
+        let columns = [column(0), column(1), column(2)];
+        let stretch = columns
+            .iter()
+            .map(|a| columns.iter().fold(T::ZERO, |sum, &b| sum + a.dot(b).abs()))
+            .fold(T::ZERO, T::max)
+            .sqrt();
+
+        BoundingSphere {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * stretch,
+        }
+    }
+
+    pub fn transform(&self, transform: &Transform<T>) -> Self {
+        self.transform_matrix(transform.matrix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The corners of a box and points scattered over a lopsided blob around them.
+    fn cloud() -> Vec<Point3<f32>> {
+        let corners = Aabb {
+            min: Vector3::new(-1., -2., -0.5),
+            max: Vector3::new(3., 1., 0.5),
+        }
+        .corners();
+        let blob = (0..100).map(|i| {
+            let (a, b) = (i as f32 * 0.7, i as f32 * 1.3);
+            Vector3::new(
+                a.cos() * b.sin() * 2. + 1.,
+                a.sin() * b.sin() * 1.5,
+                b.cos() * 4.,
+            )
+        });
+        corners.into_iter().chain(blob).collect()
+    }
+
+    fn assert_contains(sphere: &BoundingSphere<f32>, points: &[Point3<f32>]) {
+        for &p in points {
+            let distance = (p - sphere.center).length();
+            assert!(
+                distance <= sphere.radius,
+                "{p:?} is {distance} from the centre"
+            );
+        }
+    }
+
+    #[test]
+    fn sphere_contains_every_point() {
+        let points = cloud();
+        assert_contains(&BoundingSphere::from_points(&points), &points);
+
+        let single = BoundingSphere::from_points(&points[..1]);
+        assert_eq!(single.radius, 0.);
+        assert!(BoundingSphere::<f32>::from_points(&[]).is_empty());
+    }
+
+    #[test]
+    fn transformed_sphere_contains_every_transformed_point() {
+        let points = cloud();
+        let sphere = BoundingSphere::from_points(&points);
+
+        let mut shear = Matrix4::identity();
+        shear.values[0][1] = 0.8;
+        shear.values[2][0] = -0.3;
+        let matrices = [
+            Matrix4::scaling(Vector3::new(5., 0.2, 1.)),
+            Matrix4::scaling(Vector3::new(-0.5, 3., 0.)),
+            Matrix4::translation(Vector3::new(10., -4., 2.))
+                * Matrix4::rotation_y(0.7)
+                * Matrix4::scaling(Vector3::new(0.1, 4., 2.5))
+                * Matrix4::rotation_x(-1.2),
+            Matrix4::rotation_z(2.) * shear * Matrix4::scaling(Vector3::new(1., 1., 7.)),
+        ];
+        for matrix in &matrices {
+            let moved: Vec<Point3<f32>> =
+                points.iter().map(|&p| matrix.transform_point(p)).collect();
+            assert_contains(&sphere.transform_matrix(matrix), &moved);
+        }
+    }
+
+    #[test]
+    fn empty_sphere_stays_empty() {
+        let empty = BoundingSphere::<f32>::EMPTY;
+        for matrix in [
+            Matrix4::zero(),
+            Matrix4::scaling(Vector3::new(2., 0., 1.)),
+            Matrix4::translation(Vector3::new(1., 2., 3.)) * Matrix4::rotation_y(0.5),
+        ] {
+            assert!(empty.transform_matrix(&matrix).is_empty());
+        }
+    }
+
+    #[test]
+    fn zero_matrix_collapses_the_sphere_to_a_point() {
+        let sphere = BoundingSphere::from_points(&cloud());
+        let collapsed = sphere.transform_matrix(&Matrix4::zero());
+        assert_eq!(collapsed.radius, 0.);
+        assert_eq!(collapsed.center, Vector3::ZERO);
+    }
+}
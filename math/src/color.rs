@@ -0,0 +1,96 @@
+use std::ops;
+
+use crate::Real;
+
+/// Linear RGB colour, 1.0 is full intensity. Values above 1 are kept for HDR.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+}
+
+impl<T> Color<T> {
+    pub const fn new(r: T, g: T, b: T) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl<T: Real> Color<T> {
+    pub const BLACK: Self = Self::new(T::ZERO, T::ZERO, T::ZERO);
+
+    /// 8 bit channels taken linearly, 255 becomes 1.0.
+    pub fn from_rgb8(rgb: [u8; 3]) -> Self {
+        let [r, g, b] = rgb.map(|c| T::from_f64(f64::from(c) / 255.));
+        Self { r, g, b }
+    }
+
+    /// Back to 8 bits, values out of [0, 1] are clamped.
+    pub fn to_rgb8(self) -> [u8; 3] {
+        [self.r, self.g, self.b].map(|c| (c.to_f64() * 255.).round() as u8)
+    }
+
+    /// Rec. 709 weights.
+    pub fn luminance(self) -> T {
+        T::from_f64(0.2126) * self.r + T::from_f64(0.7152) * self.g + T::from_f64(0.0722) * self.b
+    }
+}
+
+impl<T> From<[T; 3]> for Color<T> {
+    fn from([r, g, b]: [T; 3]) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl<T> From<Color<T>> for [T; 3] {
+    fn from(c: Color<T>) -> Self {
+        [c.r, c.g, c.b]
+    }
+}
+
+impl<T: Real> ops::Add for Color<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.r + rhs.r, self.g + rhs.g, self.b + rhs.b)
+    }
+}
+
+impl<T: Real> ops::AddAssign for Color<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T: Real> ops::Sub for Color<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.r - rhs.r, self.g - rhs.g, self.b - rhs.b)
+    }
+}
+
+/// Filtering, a surface reflecting `rhs` of the light `self`.
+impl<T: Real> ops::Mul for Color<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b)
+    }
+}
+
+impl<T: Real> ops::Mul<T> for Color<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self {
+        Self::new(self.r * rhs, self.g * rhs, self.b * rhs)
+    }
+}
+
+impl<T: Real> ops::Div<T> for Color<T> {
+    type Output = Self;
+
+    fn div(self, rhs: T) -> Self {
+        Self::new(self.r / rhs, self.g / rhs, self.b / rhs)
+    }
+}
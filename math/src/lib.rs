@@ -0,0 +1,19 @@
+//! Geometry shared by the rasterizer and the raytracer: vectors, matrices and transforms,
+//! rays, bounding boxes and spheres, colours and the Lambert and Phong reflection terms,
+//! generic over `f32` and `f64`.
+
+mod bounds;
+mod color;
+mod matrix;
+mod ray;
+mod real;
+mod shading;
+mod vector;
+
+pub use bounds::*;
+pub use color::*;
+pub use matrix::*;
+pub use ray::*;
+pub use real::*;
+pub use shading::*;
+pub use vector::*;
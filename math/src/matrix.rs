@@ -0,0 +1,258 @@
+use std::ops;
+
+use crate::{Point3, Real, Vector3};
+
+/// Row major 4x4 matrix working on column vectors, `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4<T> {
+    pub values: [[T; 4]; 4],
+}
+
+impl<T: Real> Matrix4<T> {
+    pub fn zero() -> Self {
+        Self {
+            values: [[T::ZERO; 4]; 4],
+        }
+    }
+
+    pub fn identity() -> Self {
+        let mut matrix = Self::zero();
+        for i in 0..4 {
+            matrix.values[i][i] = T::ONE;
+        }
+        matrix
+    }
+
+    pub fn translation(offset: Vector3<T>) -> Self {
+        let mut matrix = Self::identity();
+        for i in 0..3 {
+            matrix.values[i][3] = offset[i];
+        }
+        matrix
+    }
+
+    pub fn scaling(factors: Vector3<T>) -> Self {
+        let mut matrix = Self::identity();
+        for i in 0..3 {
+            matrix.values[i][i] = factors[i];
+        }
+        matrix
+    }
+
+    /// Counterclockwise rotation around the x axis, looking from +x, angle in radians.
+    pub fn rotation_x(angle: T) -> Self {
+        Self::rotation(angle, 1, 2)
+    }
+
+    pub fn rotation_y(angle: T) -> Self {
+        Self::rotation(angle, 2, 0)
+    }
+
+    pub fn rotation_z(angle: T) -> Self {
+        Self::rotation(angle, 0, 1)
+    }
+
+    /// Rotation in the plane of the axes `a` and `b`, turning `a` towards `b`.
+    fn rotation(angle: T, a: usize, b: usize) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        let mut matrix = Self::identity();
+        matrix.values[a][a] = cos;
+        matrix.values[a][b] = -sin;
+        matrix.values[b][a] = sin;
+        matrix.values[b][b] = cos;
+        matrix
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut result = Self::zero();
+        for i in 0..4 {
+            for j in 0..4 {
+                result.values[i][j] = self.values[j][i];
+            }
+        }
+        result
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, `None` when the matrix collapses space.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.values;
+        let mut inverse = Self::identity().values;
+
+        for column in 0..4 {
+            let pivot =
+                (column..4).max_by(|&x, &y| a[x][column].abs().total_cmp(&a[y][column].abs()))?;
+            if a[pivot][column].abs() < T::from_f64(1e-12) {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = T::ONE / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = a[row][column];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+
+        Some(Self { values: inverse })
+    }
+
+    /// `p` with w = 1, affine matrices only.
+    pub fn transform_point(&self, p: Point3<T>) -> Point3<T> {
+        self.apply(p, T::ONE)
+    }
+
+    /// `v` with w = 0, translations leave it alone.
+    pub fn transform_vector(&self, v: Vector3<T>) -> Vector3<T> {
+        self.apply(v, T::ZERO)
+    }
+
+    fn apply(&self, v: Vector3<T>, w: T) -> Vector3<T> {
+        let m = &self.values;
+        Vector3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3] * w,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3] * w,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * w,
+        )
+    }
+}
+
+impl<T: Real> ops::Mul for Matrix4<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut result = Self::zero();
+        for i in 0..4 {
+            for j in 0..4 {
+                result.values[i][j] = (0..4).map(|k| self.values[i][k] * rhs.values[k][j]).sum();
+            }
+        }
+        result
+    }
+}
+
+/// Affine transform from object space to world space, kept together with its inverse.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform<T> {
+    matrix: Matrix4<T>,
+    inverse: Matrix4<T>,
+}
+
+impl<T: Real> Transform<T> {
+    pub fn identity() -> Self {
+        Self {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    /// Any invertible affine matrix, `None` when it collapses space.
+    pub fn from_matrix(matrix: Matrix4<T>) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn translation(offset: Vector3<T>) -> Self {
+        Self {
+            matrix: Matrix4::translation(offset),
+            inverse: Matrix4::translation(-offset),
+        }
+    }
+
+    /// Scales along the axes, every factor must be non zero.
+    pub fn scaling(factors: Vector3<T>) -> Self {
+        Self {
+            matrix: Matrix4::scaling(factors),
+            inverse: Matrix4::scaling(Vector3::new(
+                T::ONE / factors.x,
+                T::ONE / factors.y,
+                T::ONE / factors.z,
+            )),
+        }
+    }
+
+    /// Counterclockwise rotation around the x axis, looking from +x, angle in radians.
+    pub fn rotation_x(angle: T) -> Self {
+        Self::rotation(Matrix4::rotation_x(angle))
+    }
+
+    pub fn rotation_y(angle: T) -> Self {
+        Self::rotation(Matrix4::rotation_y(angle))
+    }
+
+    pub fn rotation_z(angle: T) -> Self {
+        Self::rotation(Matrix4::rotation_z(angle))
+    }
+
+    /// Rotations are orthogonal, the inverse is the transpose.
+    fn rotation(matrix: Matrix4<T>) -> Self {
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// Applies `self` first and `next` after it.
+    pub fn then(&self, next: &Transform<T>) -> Self {
+        Self {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4<T> {
+        &self.matrix
+    }
+
+    pub fn point(&self, p: Point3<T>) -> Point3<T> {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vector3<T>) -> Vector3<T> {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Normals go through the inverse transpose so they stay perpendicular
+    /// to non uniformly scaled surfaces. The result is not normalized.
+    pub fn normal(&self, n: Vector3<T>) -> Vector3<T> {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    pub fn inverse_point(&self, p: Point3<T>) -> Point3<T> {
+        self.inverse.transform_point(p)
+    }
+
+    pub fn inverse_vector(&self, v: Vector3<T>) -> Vector3<T> {
+        self.inverse.transform_vector(v)
+    }
+
+    /// Upper bound on how much longer any vector gets, the Frobenius norm of the linear part.
+    pub fn stretch_bound(&self) -> T {
+        self.matrix.values[..3]
+            .iter()
+            .flat_map(|row| &row[..3])
+            .map(|&value| value * value)
+            .sum::<T>()
+            .sqrt()
+    }
+}
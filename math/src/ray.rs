@@ -0,0 +1,24 @@
+use crate::{Point3, Real, Vector3};
+
+/// Half line sent at a moment `time`, for shapes that move while the shutter is open.
+/// The direction does not need unit length, `t` is measured in multiples of it.
+#[derive(Clone, Copy, Debug)]
+pub struct Ray<T> {
+    pub origin: Point3<T>,
+    pub direction: Vector3<T>,
+    pub time: T,
+}
+
+impl<T: Real> Ray<T> {
+    pub fn new(origin: Point3<T>, direction: Vector3<T>, time: T) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
+    }
+
+    pub fn at(&self, t: T) -> Point3<T> {
+        self.origin + self.direction * t
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    iter::Sum,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+/// Floating point type the geometry is computed in, `f32` or `f64`.
+pub trait Real:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + Sum
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const INFINITY: Self;
+    const NEG_INFINITY: Self;
+    const EPSILON: Self;
+
+    /// For constants in generic code, rounds when `Self` is `f32`.
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn sin_cos(self) -> (Self, Self);
+    fn powf(self, n: Self) -> Self;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+macro_rules! impl_real {
+    ($float:ident) => {
+        impl Real for $float {
+            const ZERO: Self = 0.;
+            const ONE: Self = 1.;
+            const INFINITY: Self = $float::INFINITY;
+            const NEG_INFINITY: Self = $float::NEG_INFINITY;
+            const EPSILON: Self = $float::EPSILON;
+
+            fn from_f64(value: f64) -> Self {
+                value as $float
+            }
+
+            fn to_f64(self) -> f64 {
+                self.into()
+            }
+
+            fn sqrt(self) -> Self {
+                $float::sqrt(self)
+            }
+
+            fn abs(self) -> Self {
+                $float::abs(self)
+            }
+
+            fn min(self, other: Self) -> Self {
+                $float::min(self, other)
+            }
+
+            fn max(self, other: Self) -> Self {
+                $float::max(self, other)
+            }
+
+            fn sin_cos(self) -> (Self, Self) {
+                $float::sin_cos(self)
+            }
+
+            fn powf(self, n: Self) -> Self {
+                $float::powf(self, n)
+            }
+
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                $float::total_cmp(self, other)
+            }
+        }
+    };
+}
+
+impl_real!(f32);
+impl_real!(f64);
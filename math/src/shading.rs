@@ -0,0 +1,36 @@
+use crate::{Real, Vector3};
+
+/// `direction` mirrored around `normal`, both pointing away from the surface.
+pub fn reflect<T: Real>(direction: Vector3<T>, normal: Vector3<T>) -> Vector3<T> {
+    normal * (T::from_f64(2.) * normal.dot(direction)) - direction
+}
+
+/// Lambert reflection of a light of `intensity` towards `to_light`,
+/// nothing when the light is behind the surface.
+pub fn diffuse<T: Real>(normal: Vector3<T>, to_light: Vector3<T>, intensity: T) -> T {
+    let normal_dot_light = normal.dot(to_light);
+    if normal_dot_light > T::ZERO {
+        intensity * normal_dot_light / (normal.length() * to_light.length())
+    } else {
+        T::ZERO
+    }
+}
+
+/// Phong highlight of a light of `intensity` towards `to_light` seen from `to_viewer`,
+/// tighter for a larger `exponent`.
+pub fn specular<T: Real>(
+    normal: Vector3<T>,
+    to_light: Vector3<T>,
+    to_viewer: Vector3<T>,
+    exponent: T,
+    intensity: T,
+) -> T {
+    let reflection = reflect(to_light, normal);
+    let reflection_dot_viewer = reflection.dot(to_viewer);
+    if reflection_dot_viewer > T::ZERO {
+        intensity
+            * (reflection_dot_viewer / (reflection.length() * to_viewer.length())).powf(exponent)
+    } else {
+        T::ZERO
+    }
+}
@@ -0,0 +1,203 @@
+use std::ops;
+
+use crate::Real;
+
+/// Three coordinates. Points and normals share the type,
+/// the aliases below only tell what a value stands for.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+pub type Point3<T> = Vector3<T>;
+/// Unit length unless said otherwise.
+pub type Normal3<T> = Vector3<T>;
+
+impl<T> Vector3<T> {
+    pub const fn new(x: T, y: T, z: T) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl<T: Real> Vector3<T> {
+    pub const ZERO: Self = Self::new(T::ZERO, T::ZERO, T::ZERO);
+
+    /// All three coordinates set to `value`.
+    pub fn splat(value: T) -> Self {
+        Self::new(value, value, value)
+    }
+
+    pub fn dot(self, rhs: Self) -> T {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn cross(self, rhs: Self) -> Self {
+        Self {
+            x: self.y * rhs.z - self.z * rhs.y,
+            y: self.z * rhs.x - self.x * rhs.z,
+            z: self.x * rhs.y - self.y * rhs.x,
+        }
+    }
+
+    pub fn length_squared(self) -> T {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> T {
+        self.length_squared().sqrt()
+    }
+
+    /// Same direction with unit length. The zero vector gives NaNs.
+    pub fn normalize(self) -> Self {
+        self / self.length()
+    }
+
+    /// Coordinate by coordinate product.
+    pub fn mul_elements(self, rhs: Self) -> Self {
+        Self::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+    }
+
+    /// Coordinate by coordinate minimum.
+    pub fn min(self, rhs: Self) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    /// Coordinate by coordinate maximum.
+    pub fn max(self, rhs: Self) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+
+    /// Largest absolute coordinate.
+    pub fn max_abs(self) -> T {
+        self.x.abs().max(self.y.abs()).max(self.z.abs())
+    }
+
+    /// Index of the largest coordinate, the first one on ties.
+    pub fn max_axis(self) -> usize {
+        if self.x >= self.y && self.x >= self.z {
+            0
+        } else if self.y >= self.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl<T> From<[T; 3]> for Vector3<T> {
+    fn from([x, y, z]: [T; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl<T> From<Vector3<T>> for [T; 3] {
+    fn from(v: Vector3<T>) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+/// Coordinates by axis, 0 is x.
+impl<T> ops::Index<usize> for Vector3<T> {
+    type Output = T;
+
+    fn index(&self, axis: usize) -> &T {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("axis {axis} out of range"),
+        }
+    }
+}
+
+impl<T> ops::IndexMut<usize> for Vector3<T> {
+    fn index_mut(&mut self, axis: usize) -> &mut T {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("axis {axis} out of range"),
+        }
+    }
+}
+
+impl<T: Real> ops::Add for Vector3<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl<T: Real> ops::AddAssign for Vector3<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T: Real> ops::Sub for Vector3<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl<T: Real> ops::SubAssign for Vector3<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T: Real> ops::Neg for Vector3<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl<T: Real> ops::Mul<T> for Vector3<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl<T: Real> ops::MulAssign<T> for Vector3<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
+    }
+}
+
+impl<T: Real> ops::Div<T> for Vector3<T> {
+    type Output = Self;
+
+    fn div(self, rhs: T) -> Self {
+        Self::new(self.x / rhs, self.y / rhs, self.z / rhs)
+    }
+}
+
+impl<T: Real> ops::DivAssign<T> for Vector3<T> {
+    fn div_assign(&mut self, rhs: T) {
+        *self = *self / rhs;
+    }
+}
+
+macro_rules! impl_scalar_mul {
+    ($float:ident) => {
+        impl ops::Mul<Vector3<$float>> for $float {
+            type Output = Vector3<$float>;
+
+            fn mul(self, rhs: Vector3<$float>) -> Vector3<$float> {
+                rhs * self
+            }
+        }
+    };
+}
+
+impl_scalar_mul!(f32);
+impl_scalar_mul!(f64);
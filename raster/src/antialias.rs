@@ -0,0 +1,400 @@
+//! Smoothing the edges of filled triangles: multisampling tests coverage and depth at
+//! several points of every pixel but shades it once per triangle, supersampling draws
+//! the whole image larger and filters it back down.
+
+use image::{Rgb, RgbImage};
+
+use crate::{
+    core::{canvas_extent, canvas_to_image, Point, BACKGROUND_COLOR},
+    edge::rasterize_triangle_multisample,
+    varying::{barycentric, Varying},
+};
+
+/// How the edges of filled triangles are smoothed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AntiAliasing {
+    #[default]
+    None,
+    /// MSAA with 1, 2, 4, 8 or 16 samples per pixel, each with its own depth, see
+    /// `SampleBuffer`. Triangles are rasterized with edge functions whatever the
+    /// rasterizer, wireframes are left alone.
+    Multisample(u32),
+    /// SSAA: everything drawn `factor` times larger along both axes, 1 to 8,
+    /// then scaled back down with `filter`. Time and memory grow with its square.
+    Supersample {
+        factor: u32,
+        filter: DownsampleFilter,
+    },
+}
+
+/// How the pixels of a supersampled image are weighted into the pixel they fall in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DownsampleFilter {
+    /// The pixels under the final pixel, evenly.
+    #[default]
+    Box,
+    /// The pixels up to a final pixel away, less the further they are.
+    Tent,
+    /// A gaussian half a final pixel wide, softest of the three.
+    Gaussian,
+}
+
+impl DownsampleFilter {
+    /// Half the width of the filter, in final pixels.
+    fn radius(self) -> f32 {
+        match self {
+            DownsampleFilter::Box => 0.5,
+            DownsampleFilter::Tent => 1.,
+            DownsampleFilter::Gaussian => 1.5,
+        }
+    }
+
+    /// Weight of a pixel `distance` final pixels away from the centre along one axis.
+    fn weight(self, distance: f32) -> f32 {
+        let distance = distance.abs();
+        match self {
+            // pixels right on the border are shared with the neighbour
+            DownsampleFilter::Box if distance == 0.5 => 0.5,
+            DownsampleFilter::Box if distance < 0.5 => 1.,
+            DownsampleFilter::Box => 0.,
+            DownsampleFilter::Tent => (1. - distance).max(0.),
+            DownsampleFilter::Gaussian => (-2. * distance * distance).exp(),
+        }
+    }
+}
+
+/// Standard sample positions of the supported sample counts, in sixteenths of a pixel
+/// from its centre. Spread so no two share a row or a column.
+fn sample_pattern(samples: u32) -> Option<&'static [(i8, i8)]> {
+    Some(match samples {
+        1 => &[(0, 0)],
+        2 => &[(4, 4), (-4, -4)],
+        4 => &[(-2, -6), (6, -2), (-6, 2), (2, 6)],
+        8 => &[
+            (1, -3),
+            (-1, 3),
+            (5, 1),
+            (-3, -5),
+            (-5, 5),
+            (-7, -1),
+            (3, 7),
+            (7, -7),
+        ],
+        16 => &[
+            (1, 1),
+            (-1, -3),
+            (-3, 2),
+            (4, -1),
+            (-5, -2),
+            (2, 5),
+            (5, 3),
+            (3, -5),
+            (-2, 6),
+            (0, -7),
+            (-4, -6),
+            (-6, 4),
+            (-8, 0),
+            (7, -4),
+            (6, 7),
+            (-7, -8),
+        ],
+        _ => return None,
+    })
+}
+
+/// Whether `Multisample(samples)` is supported.
+pub fn is_sample_count(samples: u32) -> bool {
+    sample_pattern(samples).is_some()
+}
+
+/// Colour and 1/z of every sample of every pixel of an image, laid out like the canvas.
+pub struct SampleBuffer {
+    width: u32,
+    height: u32,
+    /// Sample positions from the pixel centre, in pixels.
+    offsets: Vec<(f32, f32)>,
+    colors: Vec<Rgb<u8>>,
+    /// 0 where nothing was drawn yet.
+    inv_depths: Vec<f32>,
+}
+
+impl SampleBuffer {
+    /// `None` for a sample count `is_sample_count` rejects.
+    pub fn new(width: u32, height: u32, samples: u32) -> Option<Self> {
+        let offsets: Vec<(f32, f32)> = sample_pattern(samples)?
+            .iter()
+            .map(|&(x, y)| (f32::from(x) / 16., f32::from(y) / 16.))
+            .collect();
+        let size = (width * height) as usize * offsets.len();
+
+        Some(Self {
+            width,
+            height,
+            offsets,
+            colors: vec![BACKGROUND_COLOR; size],
+            inv_depths: vec![0.; size],
+        })
+    }
+
+    /// Fills the triangle between the canvas positions `points` like
+    /// `draw::draw_shaded_triangle_subpixel`, testing depth at every sample. Pixels are
+    /// shaded once, at the centroid of their samples that pass, which keeps the attributes
+    /// within the triangle, and the colour goes to those samples.
+    pub fn fill_triangle<V: Varying>(
+        &mut self,
+        points: [(f32, f32); 3],
+        inv_depths: [f32; 3],
+        attributes: [V; 3],
+        shade: impl Fn(Point, f32, V) -> Rgb<u8>,
+    ) {
+        let (width, height) = (self.width, self.height);
+        let offsets = self.offsets.clone();
+        let samples = offsets.len();
+
+        rasterize_triangle_multisample(points, width, height, &offsets, |point, covered| {
+            let Some((image_x, image_y)) = canvas_to_image(&point, width, height) else {
+                return;
+            };
+            let first = (image_y * width + image_x) as usize * samples;
+
+            let mut passed = 0u32;
+            let mut centroid = [0.; 3];
+            for &(sample, weights) in covered {
+                let inv_depth = barycentric(inv_depths, weights);
+                if inv_depth > self.inv_depths[first + sample] {
+                    self.inv_depths[first + sample] = inv_depth;
+                    passed |= 1 << sample;
+                    centroid = centroid.add(weights);
+                }
+            }
+            if passed == 0 {
+                return;
+            }
+
+            let weights = centroid.div(passed.count_ones() as f32);
+            let color = shade(
+                point,
+                barycentric(inv_depths, weights),
+                barycentric(attributes, weights),
+            );
+            for sample in 0..samples {
+                if passed & 1 << sample != 0 {
+                    self.colors[first + sample] = color;
+                }
+            }
+        });
+    }
+
+    /// The image, every pixel the average of its samples.
+    pub fn resolve(&self) -> RgbImage {
+        let samples = self.offsets.len();
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let first = (y * self.width + x) as usize * samples;
+            let mut sum = [0u32; 3];
+            for color in &self.colors[first..first + samples] {
+                for (sum, c) in sum.iter_mut().zip(color.0) {
+                    *sum += u32::from(c);
+                }
+            }
+            let samples = samples as u32;
+            Rgb(sum.map(|c| ((c + samples / 2) / samples) as u8))
+        })
+    }
+}
+
+/// Pixel of an image `high_width` by `high_height`, drawn `factor` times larger than one
+/// `width` by `height`, at the centre of the pixel (`x`, `y`) of the latter.
+/// Both canvases have their origin in their middle, so that is whole and may lie outside.
+pub fn supersampled_pixel(
+    (x, y): (u32, u32),
+    (width, height): (u32, u32),
+    (high_width, high_height): (u32, u32),
+    factor: u32,
+) -> (i64, i64) {
+    let (x_offset, y_offset) = canvas_extent(width, height);
+    let (high_x_offset, high_y_offset) = canvas_extent(high_width, high_height);
+    let (factor, x, y) = (i64::from(factor), i64::from(x), i64::from(y));
+    (
+        (x - i64::from(x_offset)) * factor + i64::from(high_x_offset),
+        (y - i64::from(y_offset)) * factor + i64::from(high_y_offset),
+    )
+}
+
+/// `image`, drawn `factor` times larger, scaled down to `width` by `height` with
+/// `filter`. Pixels of the filter beyond the edges of `image` count as background.
+pub fn downsample(
+    image: &RgbImage,
+    factor: u32,
+    filter: DownsampleFilter,
+    width: u32,
+    height: u32,
+) -> RgbImage {
+    let reach = (filter.radius() * factor as f32).floor() as i64;
+    // the same along both axes, as the centres sit on whole pixels of `image`
+    let weights: Vec<f32> = (-reach..=reach)
+        .map(|offset| filter.weight(offset as f32 / factor as f32))
+        .collect();
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (center_x, center_y) =
+            supersampled_pixel((x, y), (width, height), image.dimensions(), factor);
+
+        let mut sum = [0.; 3];
+        let mut total = 0.;
+        for (weight_y, dy) in weights.iter().zip(-reach..) {
+            for (weight_x, dx) in weights.iter().zip(-reach..) {
+                let weight = weight_x * weight_y;
+                let (sample_x, sample_y) = (center_x + dx, center_y + dy);
+                let inside = (0..i64::from(image.width())).contains(&sample_x)
+                    && (0..i64::from(image.height())).contains(&sample_y);
+                let color = if inside {
+                    *image.get_pixel(sample_x as u32, sample_y as u32)
+                } else {
+                    BACKGROUND_COLOR
+                };
+
+                for (sum, c) in sum.iter_mut().zip(color.0) {
+                    *sum += f32::from(c) * weight;
+                }
+                total += weight;
+            }
+        }
+        Rgb(sum.map(|c| (c / total).round() as u8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+    /// A 20 by 20 canvas, from -5 to 5 on both axes.
+    const SIZE: u32 = 20;
+
+    /// Everything left of x = 0.
+    const LEFT_HALF: [(f32, f32); 3] = [(0., -100.), (0., 100.), (-100., 0.)];
+    const EVERYTHING: [(f32, f32); 3] = [(-100., -100.), (100., -100.), (0., 100.)];
+
+    fn fill(buffer: &mut SampleBuffer, points: [(f32, f32); 3], inv_depth: f32, color: Rgb<u8>) {
+        buffer.fill_triangle(points, [inv_depth; 3], [0.; 3], |_, _, _: f32| color);
+    }
+
+    fn pixel(image: &RgbImage, x: i32, y: i32) -> Rgb<u8> {
+        let (x, y) = canvas_to_image(&Point::new(x, y), image.width(), image.height()).unwrap();
+        *image.get_pixel(x, y)
+    }
+
+    #[test]
+    fn sample_patterns_stay_in_the_pixel() {
+        for samples in 0..=32 {
+            let Some(pattern) = sample_pattern(samples) else {
+                assert!(!is_sample_count(samples));
+                continue;
+            };
+            assert!([1, 2, 4, 8, 16].contains(&samples));
+            assert_eq!(pattern.len(), samples as usize);
+            for (i, &(x, y)) in pattern.iter().enumerate() {
+                assert!((-8..8).contains(&x) && (-8..8).contains(&y));
+                for &(other_x, other_y) in &pattern[i + 1..] {
+                    assert!(
+                        x != other_x && y != other_y,
+                        "{samples} samples share a line"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_averages_the_covered_samples() {
+        let mut buffer = SampleBuffer::new(SIZE, SIZE, 4).unwrap();
+        assert!(buffer.resolve().pixels().all(|&p| p == BACKGROUND_COLOR));
+
+        fill(&mut buffer, LEFT_HALF, 1., BLACK);
+        let image = buffer.resolve();
+        for y in -5..=5 {
+            assert_eq!(pixel(&image, -1, y), BLACK);
+            // two of the four samples are left of the edge
+            assert_eq!(pixel(&image, 0, y), Rgb([128, 128, 128]));
+            assert_eq!(pixel(&image, 1, y), BACKGROUND_COLOR);
+        }
+    }
+
+    #[test]
+    fn every_sample_keeps_its_own_depth() {
+        let mut buffer = SampleBuffer::new(SIZE, SIZE, 4).unwrap();
+        fill(&mut buffer, LEFT_HALF, 1., RED);
+        // behind the red half, in front of the background
+        fill(&mut buffer, EVERYTHING, 0.5, BLUE);
+        let image = buffer.resolve();
+
+        assert_eq!(pixel(&image, -1, 0), RED);
+        assert_eq!(pixel(&image, 0, 0), Rgb([128, 0, 128]));
+        assert_eq!(pixel(&image, 1, 0), BLUE);
+    }
+
+    #[test]
+    fn supersampled_canvases_share_their_origin() {
+        let (width, height) = (SIZE, SIZE + 2);
+        let (high_width, high_height) = (3 * width, 3 * height);
+        let (x_offset, y_offset) = canvas_extent(width, height);
+        let (high_x_offset, high_y_offset) = canvas_extent(high_width, high_height);
+
+        let origin = (x_offset as u32, y_offset as u32);
+        let high = (high_width, high_height);
+        assert_eq!(
+            supersampled_pixel(origin, (width, height), high, 3),
+            (i64::from(high_x_offset), i64::from(high_y_offset))
+        );
+        assert_eq!(
+            supersampled_pixel((origin.0 + 1, origin.1 - 2), (width, height), high, 3),
+            (i64::from(high_x_offset) + 3, i64::from(high_y_offset) - 6)
+        );
+    }
+
+    #[test]
+    fn downsampling_keeps_flat_colours() {
+        let image = RgbImage::from_pixel(3 * SIZE, 3 * SIZE, BLUE);
+        for filter in [
+            DownsampleFilter::Box,
+            DownsampleFilter::Tent,
+            DownsampleFilter::Gaussian,
+        ] {
+            let small = downsample(&image, 3, filter, SIZE, SIZE);
+            // away from the edges, where the background comes in
+            for y in -2..=2 {
+                for x in -2..=2 {
+                    assert_eq!(pixel(&small, x, y), BLUE, "{filter:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wider_filters_spread_further() {
+        let (high_width, high_height) = (3 * SIZE, 3 * SIZE);
+        let (center_x, center_y) =
+            supersampled_pixel((10, 10), (SIZE, SIZE), (high_width, high_height), 3);
+        // black under the final pixel (10, 10) only
+        let image = RgbImage::from_fn(high_width, high_height, |x, y| {
+            if i64::from(x).abs_diff(center_x) <= 1 && i64::from(y).abs_diff(center_y) <= 1 {
+                BLACK
+            } else {
+                BACKGROUND_COLOR
+            }
+        });
+
+        let spread = |filter| {
+            let small = downsample(&image, 3, filter, SIZE, SIZE);
+            (small.get_pixel(10, 10).0[0], small.get_pixel(11, 10).0[0])
+        };
+        assert_eq!(spread(DownsampleFilter::Box), (0, 255));
+        let (tent_center, tent_side) = spread(DownsampleFilter::Tent);
+        let (gaussian_center, gaussian_side) = spread(DownsampleFilter::Gaussian);
+        assert!(0 < tent_center && tent_center < gaussian_center);
+        assert!(gaussian_side < tent_side && tent_side < 255);
+    }
+}
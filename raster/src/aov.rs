@@ -0,0 +1,181 @@
+use image::{DynamicImage, Rgb, Rgb32FImage, RgbImage};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    antialias::supersampled_pixel,
+    clip::ClipRect,
+    core::{canvas_to_image, Point, Vector3},
+    error::{Error, Result},
+    render::CropRegion,
+    varying::{interpolate, triangle_spans},
+};
+
+/// Depth, normal and instance id of the closest triangle under every pixel,
+/// laid out like the canvas.
+pub struct AovBuffers {
+    width: u32,
+    height: u32,
+    /// 1/z of the camera space depth, 0 where nothing was drawn.
+    inv_depth: Vec<f32>,
+    /// Camera space face normal.
+    normal: Vec<Vector3>,
+    /// Index of the instance in the scene plus one, 0 where nothing was drawn.
+    id: Vec<u32>,
+}
+
+impl AovBuffers {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            inv_depth: vec![0.; size],
+            normal: vec![Vector3::ZERO; size],
+            id: vec![0; size],
+        }
+    }
+
+    /// Fills the projected triangle, keeping the pixels closer than what is already there.
+    /// `depths` are the camera space z of the vertices, 1/z is what interpolates
+    /// linearly in screen space.
+    pub fn fill_triangle(
+        &mut self,
+        points: [&Point; 3],
+        depths: [f32; 3],
+        normal: Vector3,
+        id: u32,
+    ) {
+        let inv_depths = depths.map(|depth| 1. / depth);
+        let bounds = ClipRect::canvas(self.width, self.height);
+
+        triangle_spans(points, inv_depths, &bounds, |y, (x_l, z_l), (x_r, z_r)| {
+            for (x, inv_depth) in (x_l..=x_r).zip(interpolate(x_l, z_l, x_r, z_r)) {
+                let Some(index) = self.index(x, y) else {
+                    continue;
+                };
+
+                if inv_depth > self.inv_depth[index] {
+                    self.inv_depth[index] = inv_depth;
+                    self.normal[index] = normal;
+                    self.id[index] = id;
+                }
+            }
+        });
+    }
+
+    /// Buffers `width` by `height` from those of the same image drawn `factor` times larger,
+    /// see `antialias::downsample`. Every pixel takes the one at its centre, as depths,
+    /// normals and ids of different triangles do not blend.
+    pub fn downsample(&self, factor: u32, width: u32, height: u32) -> Self {
+        let mut buffers = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let (source_x, source_y) =
+                    supersampled_pixel((x, y), (width, height), (self.width, self.height), factor);
+                if !(0..i64::from(self.width)).contains(&source_x)
+                    || !(0..i64::from(self.height)).contains(&source_y)
+                {
+                    continue;
+                }
+
+                let source = (source_y * i64::from(self.width) + source_x) as usize;
+                let index = (y * width + x) as usize;
+                buffers.inv_depth[index] = self.inv_depth[source];
+                buffers.normal[index] = self.normal[source];
+                buffers.id[index] = self.id[source];
+            }
+        }
+        buffers
+    }
+
+    /// Only the pixels inside `crop`, which must lie within the buffers.
+    pub fn crop(&self, crop: CropRegion) -> Self {
+        let indices = (crop.y..crop.y + crop.height).flat_map(|y| {
+            (crop.x..crop.x + crop.width).map(move |x| (y * self.width + x) as usize)
+        });
+        Self {
+            width: crop.width,
+            height: crop.height,
+            inv_depth: indices.clone().map(|i| self.inv_depth[i]).collect(),
+            normal: indices.clone().map(|i| self.normal[i]).collect(),
+            id: indices.map(|i| self.id[i]).collect(),
+        }
+    }
+
+    /// Same mapping and bounds as `put_pixel`.
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let (x, y) = canvas_to_image(&Point::new(x, y), self.width, self.height)?;
+        Some((y * self.width + x) as usize)
+    }
+
+    /// Writes the buffers next to the image at `path`: `name.depth.exr`
+    /// (infinite where nothing was drawn), `name.normal.exr`, raw ids in `name.id.exr`
+    /// and a false colour `name.id.png`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let float_image = |value: &dyn Fn(usize) -> [f32; 3]| {
+            Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+                Rgb(value((y * self.width + x) as usize))
+            })
+        };
+
+        let depth = float_image(&|index| {
+            let inv_depth = self.inv_depth[index];
+            let depth = if inv_depth > 0. {
+                1. / inv_depth
+            } else {
+                f32::INFINITY
+            };
+            [depth; 3]
+        });
+
+        let normal = float_image(&|index| {
+            let normal = self.normal[index];
+            [normal.x, normal.y, normal.z]
+        });
+
+        let id = float_image(&|index| [self.id[index] as f32; 3]);
+        let id_colors = RgbImage::from_fn(self.width, self.height, |x, y| {
+            id_color(self.id[(y * self.width + x) as usize])
+        });
+
+        let images = [
+            ("depth", "exr", DynamicImage::from(depth)),
+            ("normal", "exr", normal.into()),
+            ("id", "exr", id.into()),
+            ("id", "png", id_colors.into()),
+        ];
+        for (name, extension, image) in images {
+            let path = aov_path(path, name, extension);
+            image.save(&path).map_err(Error::image(&path))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn aov_path(path: &Path, name: &str, extension: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{name}.{extension}"))
+}
+
+/// Distinct, stable colour per id so neighbouring instances are easy to tell apart.
+/// The raytracer uses the same palette, so one id looks alike in both.
+fn id_color(id: u32) -> Rgb<u8> {
+    if id == 0 {
+        return Rgb([0, 0, 0]);
+    }
+
+    // golden ratio steps around the hue circle
+    let hue = (id as f32 * 0.618_034).fract() * 6.;
+    let x = 1. - (hue % 2. - 1.).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1., x, 0.),
+        1 => (x, 1., 0.),
+        2 => (0., 1., x),
+        3 => (0., x, 1.),
+        4 => (x, 0., 1.),
+        _ => (1., 0., x),
+    };
+
+    Rgb([r, g, b].map(|c: f32| ((0.2 + 0.8 * c) * 255.).round() as u8))
+}
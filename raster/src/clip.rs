@@ -0,0 +1,290 @@
+//! Clipping of lines and triangles to a rectangle of the canvas, so drawing never walks
+//! pixels it would throw away, however far off the canvas projected vertices land.
+
+use crate::{core::canvas_extent, varying::Varying};
+
+/// Axis aligned rectangle of canvas positions, edges included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipRect {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl ClipRect {
+    /// The centres of the canvas pixels of an image `image_width` by `image_height`.
+    pub fn canvas(image_width: u32, image_height: u32) -> Self {
+        let (x, y) = canvas_extent(image_width, image_height);
+        Self {
+            min_x: -x as f32,
+            min_y: -y as f32,
+            max_x: x as f32,
+            max_y: y as f32,
+        }
+    }
+
+    /// The same rectangle `margin` larger on every side.
+    pub fn expand(self, margin: f32) -> Self {
+        Self {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+
+    pub fn contains(&self, p: (f32, f32)) -> bool {
+        (self.min_x..=self.max_x).contains(&p.0) && (self.min_y..=self.max_y).contains(&p.1)
+    }
+}
+
+const INSIDE: u8 = 0;
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const BOTTOM: u8 = 4;
+const TOP: u8 = 8;
+
+/// Part of the line from `a` to `b` inside `rect`, `None` when it misses it.
+/// Cohen–Sutherland: ends outside are moved onto the side they are beyond until both are
+/// inside, or both beyond the same side. The ends may be far off the canvas, where f32 does
+/// not place them to the pixel, so the cuts are worked out in f64.
+pub fn cohen_sutherland(
+    a: (f32, f32),
+    b: (f32, f32),
+    rect: &ClipRect,
+) -> Option<((f32, f32), (f32, f32))> {
+    let (min_x, min_y) = (rect.min_x as f64, rect.min_y as f64);
+    let (max_x, max_y) = (rect.max_x as f64, rect.max_y as f64);
+    // which sides of the rectangle `p` is beyond
+    let outcode = |(x, y): (f64, f64)| {
+        let mut code = INSIDE;
+        if x < min_x {
+            code |= LEFT;
+        } else if x > max_x {
+            code |= RIGHT;
+        }
+        if y < min_y {
+            code |= BOTTOM;
+        } else if y > max_y {
+            code |= TOP;
+        }
+        code
+    };
+
+    let (mut a, mut b) = ((a.0 as f64, a.1 as f64), (b.0 as f64, b.1 as f64));
+    let mut code_a = outcode(a);
+    let mut code_b = outcode(b);
+
+    loop {
+        if code_a | code_b == INSIDE {
+            return Some(((a.0 as f32, a.1 as f32), (b.0 as f32, b.1 as f32)));
+        }
+        if code_a & code_b != INSIDE {
+            return None;
+        }
+
+        let code = code_a.max(code_b);
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        // the line crosses the side the outer end is beyond, so it is not parallel to it
+        let p = if code & TOP != 0 {
+            (a.0 + dx * (max_y - a.1) / dy, max_y)
+        } else if code & BOTTOM != 0 {
+            (a.0 + dx * (min_y - a.1) / dy, min_y)
+        } else if code & RIGHT != 0 {
+            (max_x, a.1 + dy * (max_x - a.0) / dx)
+        } else {
+            (min_x, a.1 + dy * (min_x - a.0) / dx)
+        };
+
+        if code == code_a {
+            a = p;
+            code_a = outcode(a);
+        } else {
+            b = p;
+            code_b = outcode(b);
+        }
+    }
+}
+
+/// A polygon corner: its canvas position and the values interpolated across the polygon.
+pub type Vertex<V> = ((f32, f32), V);
+
+/// Where the edge from `inside` to `outside` crosses `side` of `rect`, `d_` being how far
+/// inside the side they are. Positions far off the canvas are beyond what f32 places to
+/// the pixel, so the cut is worked out in f64 and put right on the side.
+fn cut_edge<V: Varying>(
+    ((xa, ya), va): Vertex<V>,
+    d_inside: f64,
+    ((xb, yb), vb): Vertex<V>,
+    d_outside: f64,
+    side: usize,
+    rect: &ClipRect,
+) -> Vertex<V> {
+    let t = d_inside / (d_inside - d_outside);
+    let lerp = |a: f32, b: f32| (a as f64 + (b as f64 - a as f64) * t) as f32;
+    let (mut x, mut y) = (lerp(xa, xb), lerp(ya, yb));
+    match side {
+        0 => x = rect.min_x,
+        1 => x = rect.max_x,
+        2 => y = rect.min_y,
+        _ => y = rect.max_y,
+    }
+    ((x, y), va.add(vb.sub(va).mul(t as f32)))
+}
+
+/// Part of the convex `polygon` inside `rect`, clipped one side after the other
+/// (Sutherland–Hodgman). Values at new corners are interpolated along the cut edges.
+pub fn clip_polygon<V: Varying>(polygon: &[Vertex<V>], rect: &ClipRect) -> Vec<Vertex<V>> {
+    // how far inside each side a position is
+    let distance = |side, (x, y): (f32, f32)| match side {
+        0 => x as f64 - rect.min_x as f64,
+        1 => rect.max_x as f64 - x as f64,
+        2 => y as f64 - rect.min_y as f64,
+        _ => rect.max_y as f64 - y as f64,
+    };
+
+    let mut output = polygon.to_vec();
+    for side in 0..4 {
+        let input = std::mem::take(&mut output);
+        for (index, &current) in input.iter().enumerate() {
+            let previous = input[(index + input.len() - 1) % input.len()];
+            let (d_current, d_previous) = (distance(side, current.0), distance(side, previous.0));
+
+            if (d_current >= 0.) != (d_previous >= 0.) {
+                // from the inside end, so triangles sharing the edge cut it at the same point
+                output.push(if d_current >= 0. {
+                    cut_edge(current, d_current, previous, d_previous, side, rect)
+                } else {
+                    cut_edge(previous, d_previous, current, d_current, side, rect)
+                });
+            }
+            if d_current >= 0. {
+                output.push(current);
+            }
+        }
+    }
+
+    output
+}
+
+/// Part of the triangle `corners` inside `rect` as a fan of triangles, none when it misses
+/// it and the triangle itself when it lies within.
+pub fn clip_triangle<V: Varying>(corners: [Vertex<V>; 3], rect: &ClipRect) -> Vec<[Vertex<V>; 3]> {
+    if corners.iter().all(|corner| rect.contains(corner.0)) {
+        return vec![corners];
+    }
+
+    let polygon = clip_polygon(&corners, rect);
+    (2..polygon.len())
+        .map(|i| [polygon[0], polygon[i - 1], polygon[i]])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECT: ClipRect = ClipRect {
+        min_x: 0.,
+        min_y: 0.,
+        max_x: 10.,
+        max_y: 10.,
+    };
+
+    /// Corners carrying their own x as the interpolated value.
+    fn corners(points: [(f32, f32); 3]) -> [Vertex<f32>; 3] {
+        points.map(|p| (p, p.0))
+    }
+
+    /// Twice the signed area of the polygon.
+    fn area(polygon: &[(f32, f32)]) -> f32 {
+        (0..polygon.len())
+            .map(|i| {
+                let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum()
+    }
+
+    #[test]
+    fn inside() {
+        let triangle = corners([(1., 1.), (9., 2.), (0., 10.)]);
+        assert_eq!(clip_triangle(triangle, &RECT), vec![triangle]);
+        assert_eq!(
+            cohen_sutherland((0., 0.), (10., 7.5), &RECT),
+            Some(((0., 0.), (10., 7.5)))
+        );
+    }
+
+    #[test]
+    fn outside() {
+        // beyond a single side
+        assert!(clip_triangle(corners([(11., 0.), (20., 5.), (11., 10.)]), &RECT).is_empty());
+        // beyond different sides, all three around the corner
+        assert!(clip_triangle(corners([(9., 12.), (14., 14.), (12., 9.)]), &RECT).is_empty());
+        assert_eq!(cohen_sutherland((-5., -1.), (20., -3.), &RECT), None);
+        assert_eq!(cohen_sutherland((9., 12.), (12., 9.), &RECT), None);
+    }
+
+    #[test]
+    fn corner_crossing() {
+        // cuts the square from 5, 5 to 10, 10 out of the rectangle
+        let pieces = clip_triangle(corners([(5., 5.), (20., 5.), (5., 20.)]), &RECT);
+        let total: f32 = pieces
+            .iter()
+            .map(|piece| area(&piece.map(|(p, _)| p)))
+            .sum();
+        assert_eq!(total, 2. * 25.);
+        for &((x, y), value) in pieces.iter().flatten() {
+            assert!(RECT.contains((x, y)));
+            assert!((value - x).abs() < 1e-5);
+        }
+        assert!(pieces.iter().flatten().any(|&(p, _)| p == (10., 10.)));
+
+        assert_eq!(
+            cohen_sutherland((-5., 12.), (12., -5.), &RECT),
+            Some(((0., 7.), (7., 0.)))
+        );
+        // just touching the corner
+        assert_eq!(
+            cohen_sutherland((5., 15.), (15., 5.), &RECT),
+            Some(((10., 10.), (10., 10.)))
+        );
+    }
+
+    #[test]
+    fn far_off_corners_land_on_the_sides() {
+        let pieces = clip_triangle(corners([(5., 5.), (1e9, 5.), (5., 1e9)]), &RECT);
+        for &((x, y), _) in pieces.iter().flatten() {
+            assert!(RECT.contains((x, y)));
+        }
+        let total: f32 = pieces
+            .iter()
+            .map(|piece| area(&piece.map(|(p, _)| p)))
+            .sum();
+        assert_eq!(total, 2. * 25.);
+    }
+
+    #[test]
+    fn degenerate() {
+        // a triangle without area stays without area
+        let pieces = clip_triangle(corners([(-5., 5.), (5., 5.), (15., 5.)]), &RECT);
+        for piece in &pieces {
+            assert_eq!(area(&piece.map(|(p, _)| p)), 0.);
+        }
+        assert!(clip_triangle(corners([(12., 3.); 3]), &RECT).is_empty());
+        assert_eq!(clip_polygon::<f32>(&[], &RECT), vec![]);
+
+        assert_eq!(
+            cohen_sutherland((4., 6.), (4., 6.), &RECT),
+            Some(((4., 6.), (4., 6.)))
+        );
+        assert_eq!(cohen_sutherland((-4., 6.), (-4., 6.), &RECT), None);
+        // along a side
+        assert_eq!(
+            cohen_sutherland((10., -5.), (10., 15.), &RECT),
+            Some(((10., 0.), (10., 10.)))
+        );
+    }
+}
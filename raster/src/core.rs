@@ -2,14 +2,16 @@ use std::fmt;
 
 use image::Rgb;
 
-use crate::{matrix::Matrix, vector_point::VectorPoint, HomogenousVectorPoint};
-
 pub type Color = Rgb<u8>;
+pub type Vector3 = math::Vector3<f32>;
+pub type Matrix4 = math::Matrix4<f32>;
+pub type Aabb = math::Aabb<f32>;
+pub type BoundingSphere = math::BoundingSphere<f32>;
 
+/// Extra pixels around the canvas so points on its very edge still land in the image.
 pub const THRESHOLD_CANVAS: i32 = 10;
-pub const CANVAS_WIDTH: i32 = 1500;
-pub const CANVAS_HEIGHT: i32 = 1500;
 
+/// Width of the viewport on the projection plane, the horizontal field of view.
 pub const VIEWPORT_SIZE: f32 = 5.0;
 pub const PROJECTION_PLANE_Z: f32 = 4.0;
 
@@ -36,42 +38,75 @@ impl Point {
         std::mem::swap(a, b);
     }
 
-    pub fn viewport_to_canvas(x: f32, y: f32) -> Self {
-        let res = Point {
-            x: (x * (CANVAS_WIDTH as f32) / VIEWPORT_SIZE).round() as i32,
-            y: (y * (CANVAS_HEIGHT as f32) / VIEWPORT_SIZE).round() as i32,
-        };
-        res
+    /// Projection plane point to the canvas `canvas_width` pixels wide, pixels are square.
+    pub fn viewport_to_canvas(x: f32, y: f32, canvas_width: u32) -> Self {
+        let scale = canvas_width as f32 / VIEWPORT_SIZE;
+        Point {
+            x: (x * scale).round() as i32,
+            y: (y * scale).round() as i32,
+        }
     }
 }
 
 pub struct Plane {
-    pub normal: VectorPoint,
+    pub normal: Vector3,
     pub distance: f32,
 }
 
 impl Plane {
-    pub fn new(normal: VectorPoint, distance: f32) -> Self {
+    pub fn new(normal: Vector3, distance: f32) -> Self {
         Self { normal, distance }
     }
 }
 
 pub struct Camera {
-    pub position: VectorPoint,
-    pub orientation: Matrix,
+    pub position: Vector3,
+    pub orientation: Matrix4,
     pub clipping_planes: Vec<Plane>,
 }
 
 impl Camera {
-    pub fn new(position: VectorPoint, orientation: Matrix, clipping_planes: Vec<Plane>) -> Self {
+    pub fn new(position: Vector3, orientation: Matrix4, clipping_planes: Vec<Plane>) -> Self {
         Self {
             position,
             orientation,
             clipping_planes,
         }
     }
+
+    /// Clipping against the near plane at z = 1 and the sides of the 90 degree frustum.
+    pub fn with_frustum(position: Vector3, orientation: Matrix4) -> Self {
+        let s2 = 1.0 / f32::sqrt(2.);
+
+        let clipping_planes = vec![
+            Plane::new(Vector3::new(0., 0., 1.), -1.), //Near
+            Plane::new(Vector3::new(s2, 0., s2), 0.),  // Left
+            Plane::new(Vector3::new(-s2, 0., s2), 0.), // Right
+            Plane::new(Vector3::new(0., -s2, s2), 0.), // Top
+            Plane::new(Vector3::new(0., s2, s2), 0.),  // Bottom
+        ];
+
+        Self::new(position, orientation, clipping_planes)
+    }
 }
 
-pub fn dot(v1: &HomogenousVectorPoint, v2: &HomogenousVectorPoint) -> f32 {
-    v1.values[0] * v2.values[0] + v1.values[1] * v2.values[1] + v1.values[2] * v2.values[2]
+/// Largest x and y of the canvas of an image `image_width` by `image_height`,
+/// which spans from minus these to these.
+pub fn canvas_extent(image_width: u32, image_height: u32) -> (i32, i32) {
+    (
+        (image_width as i32 - THRESHOLD_CANVAS) / 2,
+        (image_height as i32 - THRESHOLD_CANVAS) / 2,
+    )
+}
+
+/// Pixel of an image `image_width` by `image_height` under the canvas point `coord`.
+/// The canvas origin is in the middle of the image, `None` outside of the canvas.
+pub fn canvas_to_image(coord: &Point, image_width: u32, image_height: u32) -> Option<(u32, u32)> {
+    let (x_offset, y_offset) = canvas_extent(image_width, image_height);
+
+    if coord.x < -x_offset || coord.x > x_offset || coord.y < -y_offset || coord.y > y_offset {
+        return None;
+    }
+
+    Some(((coord.x + x_offset) as u32, (coord.y + y_offset) as u32))
 }
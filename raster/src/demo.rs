@@ -0,0 +1,51 @@
+//! The scene the `raster` binary draws: three cubes, one of them behind the camera,
+//! lit by an ambient, a directional and a point light.
+
+use crate::{
+    core::{Camera, Matrix4, Vector3},
+    light::{Light, LightType},
+    model::{Model, Transform},
+    render::Scene,
+};
+
+/// At (-3, 1, 2), turned 30 degrees towards the cubes and clipping against
+/// the 90 degree viewing frustum.
+pub fn camera() -> Camera {
+    Camera::with_frustum(
+        Vector3::new(-3., 1., 2.),
+        Matrix4::rotation_y(30f32.to_radians()),
+    )
+}
+
+/// Shiny cubes scaled, turned and moved by their transforms.
+pub fn scene() -> Scene {
+    let cube = |transform| Model {
+        specular: 50,
+        ..Model::cube(transform)
+    };
+
+    Scene {
+        instances: vec![
+            cube(Transform::new(0.75, 0, Vector3::new(-1.5, 0., 5.))),
+            cube(Transform::new(1., 195, Vector3::new(1.25, 2.5, 7.5))),
+            cube(Transform::new(1., 195, Vector3::new(0., 0., -10.))),
+        ],
+        lights: vec![
+            Light {
+                intensity: 0.2,
+                light_type: LightType::Ambient,
+                direction: None,
+            },
+            Light {
+                intensity: 0.2,
+                light_type: LightType::Directional,
+                direction: Some(Vector3::new(-1., 0., 1.)),
+            },
+            Light {
+                intensity: 0.6,
+                light_type: LightType::Point,
+                direction: Some(Vector3::new(-3., 2., -10.)),
+            },
+        ],
+    }
+}
@@ -0,0 +1,613 @@
+use image::{Pixel, Rgb, RgbImage};
+
+use crate::{
+    clip::{cohen_sutherland, ClipRect},
+    core::{canvas_extent, canvas_to_image, Point},
+    edge::rasterize_triangle,
+    varying::{barycentric, interpolate, triangle_spans, Varying},
+};
+
+/// Sets the canvas point `coord`, points outside of the canvas are skipped.
+pub fn put_pixel(canvas: &mut RgbImage, color: &mut Rgb<u8>, coord: Point) {
+    if let Some((x, y)) = canvas_to_image(&coord, canvas.width(), canvas.height()) {
+        canvas.put_pixel(x, y, *color);
+    }
+}
+
+/// Line between two canvas points, one pixel per step along its longer axis, both ends
+/// included so the outlines of triangles close at their corners.
+/// Only the steps that can land on the canvas are walked.
+pub fn draw_line(
+    canvas: &mut RgbImage,
+    point_a: &mut Point,
+    point_b: &mut Point,
+    color: &mut Rgb<u8>,
+) {
+    let dx = point_b.x - point_a.x;
+    let dy = point_b.y - point_a.y;
+    let mut p0 = point_a.clone();
+    let mut p1 = point_b.clone();
+    // pixels whose centre is within half a pixel of the canvas may still round onto it
+    let bounds = ClipRect::canvas(canvas.width(), canvas.height()).expand(0.5);
+
+    if dx.abs() > dy.abs() {
+        //line is horizontalish
+        if dx < 0 {
+            Point::swap(&mut p0, &mut p1);
+        }
+
+        let Some((a, b)) = cohen_sutherland(position(&p0), position(&p1), &bounds) else {
+            return;
+        };
+        let (x_from, y_from, x_to) = clip_steps((p0.x, p0.y), (p1.x, p1.y), (a.0, b.0));
+        let ys = interpolate(x_from, y_from, p1.x, p1.y as f32);
+
+        for (x, y) in (x_from..x_to).zip(ys) {
+            put_pixel(canvas, color, Point::new(x, y.round() as i32));
+        }
+    } else {
+        // Line is vertical-ish
+        if dy < 0 {
+            Point::swap(&mut p0, &mut p1);
+        }
+
+        let Some((a, b)) = cohen_sutherland(position(&p0), position(&p1), &bounds) else {
+            return;
+        };
+        let (y_from, x_from, y_to) = clip_steps((p0.y, p0.x), (p1.y, p1.x), (a.1, b.1));
+        let xs = interpolate(y_from, x_from, p1.y, p1.x as f32);
+
+        for (y, x) in (y_from..y_to).zip(xs) {
+            put_pixel(canvas, color, Point::new(x.round() as i32, y));
+        }
+    }
+}
+
+fn position(point: &Point) -> (f32, f32) {
+    (point.x as f32, point.y as f32)
+}
+
+/// Steps of `draw_line` from `(i0, d0)` to `(i1, d1)`, `i` being the longer axis and
+/// `i0 <= i1`, between the clipped ends `from` and `to` along it: the first `i` and `d`
+/// there, and the `i` to stop before.
+fn clip_steps(
+    (i0, d0): (i32, i32),
+    (i1, d1): (i32, i32),
+    (from, to): (f32, f32),
+) -> (i32, f32, i32) {
+    let first = (from.ceil() as i32).max(i0);
+    let stop = (to.floor() as i32 + 1).min(i1.saturating_add(1));
+    if first == i0 {
+        return (first, d0 as f32, stop);
+    }
+    let d = d0 as f64 + (d1 - d0) as f64 * (first - i0) as f64 / (i1 - i0) as f64;
+    (first, d as f32, stop)
+}
+
+/// Line between two canvas points with Bresenham's integer steps, both ends included.
+/// Ends off the canvas are first moved onto its edge with `cohen_sutherland`.
+pub fn draw_line_bresenham(canvas: &mut RgbImage, a: &Point, b: &Point, mut color: Rgb<u8>) {
+    let bounds = ClipRect::canvas(canvas.width(), canvas.height()).expand(0.5);
+    let Some((a, b)) = cohen_sutherland(position(a), position(b), &bounds) else {
+        return;
+    };
+    let [a, b] = [a, b].map(|(x, y)| Point::new(x.round() as i32, y.round() as i32));
+
+    let (dx, dy) = ((b.x - a.x).abs(), -(b.y - a.y).abs());
+    let (sx, sy) = ((b.x - a.x).signum(), (b.y - a.y).signum());
+    let (mut x, mut y) = (a.x, a.y);
+    let mut error = dx + dy;
+
+    loop {
+        put_pixel(canvas, &mut color, Point::new(x, y));
+        if x == b.x && y == b.y {
+            break;
+        }
+
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Mixes `color` over the canvas point `coord` by `alpha` between 0 and 1,
+/// points outside of the canvas are skipped.
+pub fn blend_pixel(canvas: &mut RgbImage, color: Rgb<u8>, coord: Point, alpha: f32) {
+    if alpha <= 0. {
+        return;
+    }
+    if let Some((x, y)) = canvas_to_image(&coord, canvas.width(), canvas.height()) {
+        let alpha = alpha.min(1.);
+        let pixel = canvas.get_pixel_mut(x, y);
+        for (channel, c) in pixel.0.iter_mut().zip(color.0) {
+            *channel = (f32::from(*channel) * (1. - alpha) + f32::from(c) * alpha).round() as u8;
+        }
+    }
+}
+
+/// Xiaolin Wu's anti-aliased one pixel line between two canvas positions: every step
+/// along the longer axis covers the two pixels around the line in proportion to
+/// how close it passes, and the ends fade by how much of their pixel they cover.
+pub fn draw_line_wu(canvas: &mut RgbImage, a: (f32, f32), b: (f32, f32), color: Rgb<u8>) {
+    // the pixels around the line reach up to two away from it
+    let bounds = ClipRect::canvas(canvas.width(), canvas.height()).expand(2.);
+    let Some((a, b)) = cohen_sutherland(a, b, &bounds) else {
+        return;
+    };
+
+    let steep = (b.1 - a.1).abs() > (b.0 - a.0).abs();
+    // walk along x, swapping the axes back when plotting
+    let (mut a, mut b) = if steep {
+        ((a.1, a.0), (b.1, b.0))
+    } else {
+        (a, b)
+    };
+    if a.0 > b.0 {
+        std::mem::swap(&mut a, &mut b);
+    }
+
+    let mut plot = |x: i32, y: i32, alpha: f32| {
+        let point = if steep {
+            Point::new(y, x)
+        } else {
+            Point::new(x, y)
+        };
+        blend_pixel(canvas, color, point, alpha);
+    };
+    let fract = |v: f32| v - v.floor();
+
+    let dx = b.0 - a.0;
+    let gradient = if dx == 0. { 1. } else { (b.1 - a.1) / dx };
+
+    // the ends, weighted by how much of their pixel column the line covers
+    let mut end = |(x, y): (f32, f32), gap: f32| {
+        let x_end = x.round();
+        let y_end = y + gradient * (x_end - x);
+        plot(
+            x_end as i32,
+            y_end.floor() as i32,
+            (1. - fract(y_end)) * gap,
+        );
+        plot(x_end as i32, y_end.floor() as i32 + 1, fract(y_end) * gap);
+        (x_end as i32, y_end)
+    };
+    let (x_start, y_start) = end(a, 1. - fract(a.0 + 0.5));
+    let (x_stop, _) = end(b, fract(b.0 + 0.5));
+
+    let mut y = y_start + gradient;
+    for x in x_start + 1..x_stop {
+        plot(x, y.floor() as i32, 1. - fract(y));
+        plot(x, y.floor() as i32 + 1, fract(y));
+        y += gradient;
+    }
+}
+
+/// Shape of the ends of a stroke.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineCap {
+    /// Cut square at the end points.
+    #[default]
+    Butt,
+    /// Cut square half the thickness past the end points.
+    Square,
+    /// Rounded off around the end points.
+    Round,
+}
+
+/// Shortest length in pixels after which `LineStyle::dashes` may repeat, as every dash
+/// takes a step along the line.
+pub const MIN_DASH_PERIOD: f32 = 1. / 16.;
+
+/// How `draw_stroke` draws lines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineStyle {
+    /// Width of the line in pixels.
+    pub thickness: f32,
+    pub cap: LineCap,
+    /// Lengths in pixels of the dashes and of the gaps between them, alternating and
+    /// starting with a dash, repeated along the line. Empty for a solid line, otherwise
+    /// they add up to at least `MIN_DASH_PERIOD`.
+    pub dashes: Vec<f32>,
+    pub anti_aliased: bool,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            thickness: 1.,
+            cap: LineCap::Butt,
+            dashes: vec![],
+            anti_aliased: true,
+        }
+    }
+}
+
+/// Line between two canvas positions in `style`, every dash with its own caps.
+/// Anti-aliased lines one pixel thick or thinner are drawn with `draw_line_wu`.
+/// Dashes repeating sooner than `MIN_DASH_PERIOD` are drawn as a solid line.
+pub fn draw_stroke(
+    canvas: &mut RgbImage,
+    a: (f32, f32),
+    b: (f32, f32),
+    color: Rgb<u8>,
+    style: &LineStyle,
+) {
+    // only the part whose pixels, caps included, can reach the canvas
+    let bounds = ClipRect::canvas(canvas.width(), canvas.height()).expand(style.thickness + 2.);
+    let Some((from, to)) = cohen_sutherland(a, b, &bounds) else {
+        return;
+    };
+    // how far along the line the visible part starts, where the dashes pick up from
+    let skipped = (from.0 as f64 - a.0 as f64).hypot(from.1 as f64 - a.1 as f64);
+    let (a, b) = (from, to);
+
+    let length = (b.0 - a.0).hypot(b.1 - a.1);
+    let at = |s: f32| {
+        let t = if length == 0. { 0. } else { s / length };
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+    };
+    let mut segment = |from: f32, to: f32| {
+        if style.anti_aliased && style.thickness <= 1. {
+            draw_line_wu(canvas, at(from), at(to), color);
+        } else {
+            draw_stroke_segment(canvas, at(from), at(to), color, style);
+        }
+    };
+
+    let period: f32 = style.dashes.iter().map(|dash| dash.max(0.)).sum();
+    if period < MIN_DASH_PERIOD {
+        segment(0., length);
+        return;
+    }
+
+    // dashes and gaps swap places every other pass through an odd number of lengths
+    let period = if style.dashes.len().is_multiple_of(2) {
+        period
+    } else {
+        2. * period
+    };
+    // in f64 so short dashes still move along far from the start of the line
+    let mut s = -(skipped % period as f64);
+    for (index, dash) in style.dashes.iter().cycle().enumerate() {
+        if s >= length as f64 {
+            break;
+        }
+        let next = s + dash.max(0.) as f64;
+        if index % 2 == 0 && next >= 0. {
+            segment(s.max(0.) as f32, (next as f32).min(length));
+        }
+        s = next;
+    }
+}
+
+/// One solid piece of `draw_stroke`: every pixel near the segment is covered by how much
+/// of it lies within half the thickness, or fully from half its area without anti-aliasing.
+fn draw_stroke_segment(
+    canvas: &mut RgbImage,
+    a: (f32, f32),
+    b: (f32, f32),
+    color: Rgb<u8>,
+    style: &LineStyle,
+) {
+    let half = style.thickness / 2.;
+    let length = (b.0 - a.0).hypot(b.1 - a.1);
+    let direction = if length == 0. {
+        (1., 0.)
+    } else {
+        ((b.0 - a.0) / length, (b.1 - a.1) / length)
+    };
+
+    let (x_limit, y_limit) = canvas_extent(canvas.width(), canvas.height());
+    let reach = half + 1.;
+    let min_x = ((a.0.min(b.0) - reach).floor() as i32).max(-x_limit);
+    let max_x = ((a.0.max(b.0) + reach).ceil() as i32).min(x_limit);
+    let min_y = ((a.1.min(b.1) - reach).floor() as i32).max(-y_limit);
+    let max_y = ((a.1.max(b.1) + reach).ceil() as i32).min(y_limit);
+
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let (px, py) = (x as f32 - a.0, y as f32 - a.1);
+            // along the segment from `a`, and away from its line
+            let along = px * direction.0 + py * direction.1;
+            let across = (px * direction.1 - py * direction.0).abs();
+
+            let coverage = match style.cap {
+                LineCap::Butt | LineCap::Square => {
+                    let extra = if style.cap == LineCap::Square {
+                        half
+                    } else {
+                        0.
+                    };
+                    let side = (half + 0.5 - across).clamp(0., 1.);
+                    let start = (along + extra + 0.5).clamp(0., 1.);
+                    let end = (length + extra + 0.5 - along).clamp(0., 1.);
+                    side * start * end
+                }
+                LineCap::Round => {
+                    let closest = along.clamp(0., length);
+                    let distance = (along - closest).hypot(across);
+                    (half + 0.5 - distance).clamp(0., 1.)
+                }
+            };
+
+            let alpha = if style.anti_aliased {
+                coverage
+            } else if coverage >= 0.5 {
+                1.
+            } else {
+                0.
+            };
+            blend_pixel(canvas, color, Point::new(x, y), alpha);
+        }
+    }
+}
+
+/// Outline of the triangle.
+pub fn draw_wireframe_triangle(
+    p0: &mut Point,
+    p1: &mut Point,
+    p2: &mut Point,
+    color: &mut Rgb<u8>,
+    canvas: &mut RgbImage,
+) {
+    draw_line(canvas, p0, p1, color);
+    draw_line(canvas, p1, p2, color);
+    draw_line(canvas, p0, p2, color);
+}
+
+/// Triangle filled with `color`, shaded from dark at `p0` to almost full intensity at `p2`.
+/// Sorts the points by y in place.
+pub fn draw_filled_triangle(
+    p0: &mut Point,
+    p1: &mut Point,
+    p2: &mut Point,
+    color: Rgb<u8>,
+    canvas: &mut RgbImage,
+) {
+    if p1.y < p0.y {
+        Point::swap(p1, p0);
+    }
+
+    if p2.y < p0.y {
+        Point::swap(p2, p0);
+    }
+
+    if p2.y < p1.y {
+        Point::swap(p2, p1);
+    }
+
+    let intensities = [0., 0.4, 0.9];
+    let bounds = ClipRect::canvas(canvas.width(), canvas.height());
+    triangle_spans(
+        [p0, p1, p2],
+        intensities,
+        &bounds,
+        |y, (x_l, h_l), (x_r, h_r)| {
+            for (x, h) in (x_l..x_r).zip(interpolate(x_l, h_l, x_r, h_r)) {
+                let mut color = color;
+                color.apply(|x_in| ((x_in as f32) * h).round() as u8);
+                put_pixel(canvas, &mut color, Point::new(x, y))
+            }
+        },
+    );
+}
+
+/// Fills the triangle between the canvas points `points`, keeping only the pixels closer
+/// than what `depth` holds for them. `depth` is 1/z laid out like the canvas, 0 where
+/// nothing was drawn yet, and `inv_depths` are 1/z of the vertices.
+/// The `attributes` of the vertices are interpolated across the triangle along with 1/z,
+/// `shade` turns them into the colour of a pixel given its canvas point and 1/z.
+pub fn draw_shaded_triangle<V: Varying>(
+    canvas: &mut RgbImage,
+    depth: &mut [f32],
+    points: [&Point; 3],
+    inv_depths: [f32; 3],
+    attributes: [V; 3],
+    shade: impl Fn(Point, f32, V) -> Rgb<u8>,
+) {
+    let (width, height) = canvas.dimensions();
+    let values = [0, 1, 2].map(|i| (inv_depths[i], attributes[i]));
+    let bounds = ClipRect::canvas(width, height);
+
+    triangle_spans(points, values, &bounds, |y, (x_l, left), (x_r, right)| {
+        for (x, (inv_depth, attributes)) in (x_l..=x_r).zip(interpolate(x_l, left, x_r, right)) {
+            let point = Point::new(x, y);
+            let Some((image_x, image_y)) = canvas_to_image(&point, width, height) else {
+                continue;
+            };
+            let index = (image_y * width + image_x) as usize;
+
+            if inv_depth > depth[index] {
+                depth[index] = inv_depth;
+                canvas.put_pixel(image_x, image_y, shade(point, inv_depth, attributes));
+            }
+        }
+    });
+}
+
+/// Same as `draw_shaded_triangle` with the edge functions of `edge::rasterize_triangle`,
+/// from the unrounded canvas positions `points`: triangles sharing an edge neither leave
+/// gaps between them nor draw its pixels twice.
+pub fn draw_shaded_triangle_subpixel<V: Varying>(
+    canvas: &mut RgbImage,
+    depth: &mut [f32],
+    points: [(f32, f32); 3],
+    inv_depths: [f32; 3],
+    attributes: [V; 3],
+    shade: impl Fn(Point, f32, V) -> Rgb<u8>,
+) {
+    let (width, height) = canvas.dimensions();
+    rasterize_triangle(points, width, height, |point, weights| {
+        let Some((image_x, image_y)) = canvas_to_image(&point, width, height) else {
+            return;
+        };
+        let index = (image_y * width + image_x) as usize;
+
+        let inv_depth = barycentric(inv_depths, weights);
+        if inv_depth > depth[index] {
+            depth[index] = inv_depth;
+            let attributes = barycentric(attributes, weights);
+            canvas.put_pixel(image_x, image_y, shade(point, inv_depth, attributes));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+    /// A black canvas reaching from -10 to 10 on both axes.
+    fn blank() -> RgbImage {
+        RgbImage::new(30, 30)
+    }
+
+    fn value(canvas: &RgbImage, x: i32, y: i32) -> u8 {
+        let (x, y) = canvas_to_image(&Point::new(x, y), canvas.width(), canvas.height()).unwrap();
+        canvas.get_pixel(x, y).0[0]
+    }
+
+    /// The canvas points that are not black.
+    fn lit(canvas: &RgbImage) -> Vec<(i32, i32)> {
+        let mut points = vec![];
+        for y in -10..=10 {
+            for x in -10..=10 {
+                if value(canvas, x, y) > 0 {
+                    points.push((x, y));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn bresenham_takes_one_pixel_per_step() {
+        for (a, b) in [
+            (Point::new(-5, 2), Point::new(6, 2)),
+            (Point::new(-4, -4), Point::new(4, 4)),
+            (Point::new(1, -7), Point::new(-2, 8)),
+            (Point::new(3, 3), Point::new(3, 3)),
+        ] {
+            let mut canvas = blank();
+            draw_line_bresenham(&mut canvas, &a, &b, WHITE);
+            let points = lit(&canvas);
+
+            let steps = (b.x - a.x).abs().max((b.y - a.y).abs()) + 1;
+            assert_eq!(points.len(), steps as usize, "{a:?} to {b:?}");
+            assert!(points.contains(&(a.x, a.y)) && points.contains(&(b.x, b.y)));
+            // no gaps along the longer axis
+            for pair in points.windows(2) {
+                assert!((pair[0].0 - pair[1].0).abs() <= 1 || (pair[0].1 - pair[1].1).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn bresenham_clips_to_the_canvas() {
+        let mut canvas = blank();
+        draw_line_bresenham(
+            &mut canvas,
+            &Point::new(-500, 0),
+            &Point::new(500, 0),
+            WHITE,
+        );
+        assert_eq!(lit(&canvas), (-10..=10).map(|x| (x, 0)).collect::<Vec<_>>());
+
+        let mut canvas = blank();
+        draw_line_bresenham(
+            &mut canvas,
+            &Point::new(-500, 20),
+            &Point::new(500, 20),
+            WHITE,
+        );
+        assert!(lit(&canvas).is_empty());
+    }
+
+    #[test]
+    fn wu_splits_each_column_between_two_rows() {
+        let mut canvas = blank();
+        draw_line_wu(&mut canvas, (-6., 2.), (6., 2.), WHITE);
+        for x in -5..=5 {
+            assert_eq!(value(&canvas, x, 2), 255);
+            assert_eq!(value(&canvas, x, 3), 0);
+        }
+
+        let mut canvas = blank();
+        draw_line_wu(&mut canvas, (-6., 2.5), (6., 2.5), WHITE);
+        for x in -5..=5 {
+            assert_eq!(value(&canvas, x, 2), 128);
+            assert_eq!(value(&canvas, x, 3), 128);
+        }
+
+        // a slanted line covers about one pixel in every column
+        let mut canvas = blank();
+        draw_line_wu(&mut canvas, (-8., -3.), (8., 4.), WHITE);
+        for x in -7..=7 {
+            let column: u32 = (-10..=10).map(|y| u32::from(value(&canvas, x, y))).sum();
+            assert!(column.abs_diff(255) <= 2, "column {x} adds up to {column}");
+        }
+    }
+
+    #[test]
+    fn dashes_alternate_along_the_line() {
+        let style = LineStyle {
+            thickness: 3.,
+            dashes: vec![4., 4.],
+            anti_aliased: false,
+            ..LineStyle::default()
+        };
+        let mut canvas = blank();
+        draw_stroke(&mut canvas, (-10., 0.), (10., 0.), WHITE, &style);
+
+        let dashes: Vec<i32> = (-10..=-6).chain(-2..=2).chain(6..=10).collect();
+        for y in -1..=1 {
+            let row: Vec<i32> = (-10..=10).filter(|&x| value(&canvas, x, y) > 0).collect();
+            assert_eq!(row, dashes, "row {y}");
+        }
+        assert!(lit(&canvas).iter().all(|&(_, y)| (-1..=1).contains(&y)));
+    }
+
+    #[test]
+    fn dashes_keep_their_phase_off_the_canvas() {
+        let style = LineStyle {
+            thickness: 2.,
+            dashes: vec![3., 2., 1., 2.],
+            ..LineStyle::default()
+        };
+        // eleven periods apart, the visible part is the same
+        let mut near = blank();
+        draw_stroke(&mut near, (-22., 1.), (10., 1.), WHITE, &style);
+        let mut far = blank();
+        draw_stroke(&mut far, (-110., 1.), (10., 1.), WHITE, &style);
+
+        assert!(!lit(&near).is_empty());
+        assert_eq!(near, far);
+    }
+
+    #[test]
+    fn short_dash_patterns_draw_solid_lines() {
+        let dashed = LineStyle {
+            dashes: vec![MIN_DASH_PERIOD / 4., MIN_DASH_PERIOD / 4.],
+            ..LineStyle::default()
+        };
+        let mut solid = blank();
+        draw_stroke(
+            &mut solid,
+            (-7., -2.),
+            (8., 5.),
+            WHITE,
+            &LineStyle::default(),
+        );
+        let mut canvas = blank();
+        draw_stroke(&mut canvas, (-7., -2.), (8., 5.), WHITE, &dashed);
+
+        assert_eq!(canvas, solid);
+    }
+}
@@ -0,0 +1,306 @@
+//! Bounding box rasterization with edge functions. Vertices snap to a fixed point grid of
+//! `1 / 2^SUBPIXEL_BITS` pixels and pixels on an edge shared by two triangles belong to
+//! exactly one of them by the top-left rule, so meshes come out without gaps or overlaps.
+
+use crate::{
+    clip::{clip_triangle, ClipRect},
+    core::{canvas_extent, Point},
+    varying::barycentric,
+};
+
+/// Bits of sub-pixel precision of the vertex positions.
+pub const SUBPIXEL_BITS: u32 = 8;
+const ONE: i64 = 1 << SUBPIXEL_BITS;
+
+/// Pixels around the canvas within which triangles are rasterized whole, the bounding box
+/// skipping what is off the canvas. Triangles reaching further are clipped to it first,
+/// which keeps the fixed point edge functions far from overflowing.
+pub const GUARD_BAND: f32 = 4096.;
+
+/// Twice the signed area of the triangle `a`, `b`, `p` in fixed point,
+/// positive when `p` is on the inner side of `a` to `b` for a triangle of positive area.
+fn edge(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+/// Whether pixels right on the edge from `a` to `b` are inside: canvas y grows downwards,
+/// so for triangles of positive area top edges run towards +x and left edges towards -y.
+fn is_top_left(a: (i64, i64), b: (i64, i64)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    dy < 0 || (dy == 0 && dx > 0)
+}
+
+/// Calls `fragment` for every canvas point whose centre is inside the triangle between
+/// the canvas positions `points`, with its barycentric coordinates: the weights of the
+/// three vertices, in the order of `points` and adding up to 1. Either winding works.
+/// Only points of the canvas of an image `image_width` by `image_height` are visited.
+pub fn rasterize_triangle(
+    points: [(f32, f32); 3],
+    image_width: u32,
+    image_height: u32,
+    mut fragment: impl FnMut(Point, [f32; 3]),
+) {
+    rasterize_triangle_multisample(
+        points,
+        image_width,
+        image_height,
+        &[(0., 0.)],
+        |point, covered| fragment(point, covered[0].1),
+    );
+}
+
+/// Same as `rasterize_triangle` testing the positions `offsets` around every pixel centre,
+/// at most half a pixel away, instead of the centre alone. `fragment` gets every canvas
+/// point with at least one of them inside, along with the index in `offsets` and the
+/// barycentric coordinates of each one inside.
+pub fn rasterize_triangle_multisample(
+    points: [(f32, f32); 3],
+    image_width: u32,
+    image_height: u32,
+    offsets: &[(f32, f32)],
+    mut fragment: impl FnMut(Point, &[(usize, [f32; 3])]),
+) {
+    let bounds = ClipRect::canvas(image_width, image_height).expand(GUARD_BAND);
+    // the corners of the pieces carry their weights of the original vertices
+    let corners = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    let corners = [0, 1, 2].map(|i| (points[i], corners[i]));
+    let offsets = offsets.iter().map(|&(x, y)| (to_fixed(x), to_fixed(y)));
+    let offsets: Vec<(i64, i64)> = offsets.collect();
+
+    let mut covered = vec![];
+    for piece in clip_triangle(corners, &bounds) {
+        let weights = piece.map(|(_, weights)| weights);
+        rasterize_piece(
+            piece.map(|(point, _)| point),
+            image_width,
+            image_height,
+            &offsets,
+            |point, piece_covered| {
+                covered.clear();
+                covered.extend(
+                    piece_covered.iter().map(|&(sample, piece_weights)| {
+                        (sample, barycentric(weights, piece_weights))
+                    }),
+                );
+                fragment(point, &covered);
+            },
+        );
+    }
+}
+
+fn to_fixed(v: f32) -> i64 {
+    (v * ONE as f32).round() as i64
+}
+
+/// `rasterize_triangle_multisample` of a triangle within the guard band,
+/// `offsets` already in fixed point.
+fn rasterize_piece(
+    points: [(f32, f32); 3],
+    image_width: u32,
+    image_height: u32,
+    offsets: &[(i64, i64)],
+    mut fragment: impl FnMut(Point, &[(usize, [f32; 3])]),
+) {
+    let mut v = points.map(|(x, y)| (to_fixed(x), to_fixed(y)));
+    let mut order = [0, 1, 2];
+
+    let mut area = edge(v[0], v[1], v[2]);
+    if area == 0 {
+        return;
+    }
+    if area < 0 {
+        v.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+
+    // the canvas, as `canvas_to_image` lays it out
+    let (x_limit, y_limit) = canvas_extent(image_width, image_height);
+    let (x_limit, y_limit) = (x_limit as i64, y_limit as i64);
+    // how far the samples reach from the pixel centres
+    let reach_x = offsets.iter().map(|o| o.0.abs()).max().unwrap_or_default();
+    let reach_y = offsets.iter().map(|o| o.1.abs()).max().unwrap_or_default();
+
+    // pixel centres sit on whole canvas coordinates
+    let min_x = (v.iter().map(|p| p.0).min().unwrap_or_default() - reach_x + ONE - 1)
+        .div_euclid(ONE)
+        .max(-x_limit);
+    let max_x = (v.iter().map(|p| p.0).max().unwrap_or_default() + reach_x)
+        .div_euclid(ONE)
+        .min(x_limit);
+    let min_y = (v.iter().map(|p| p.1).min().unwrap_or_default() - reach_y + ONE - 1)
+        .div_euclid(ONE)
+        .max(-y_limit);
+    let max_y = (v.iter().map(|p| p.1).max().unwrap_or_default() + reach_y)
+        .div_euclid(ONE)
+        .min(y_limit);
+    if min_x > max_x || min_y > max_y {
+        return;
+    }
+
+    // the weight of every vertex comes from the edge facing it
+    let edges = [(v[1], v[2]), (v[2], v[0]), (v[0], v[1])];
+    // pixels on edges that are not top-left need to be strictly inside
+    let bias = edges.map(|(a, b)| if is_top_left(a, b) { 0 } else { -1 });
+    // moving one pixel along x or y changes every edge function by a constant
+    let step_x = edges.map(|(a, b)| -(b.1 - a.1) * ONE);
+    let step_y = edges.map(|(a, b)| (b.0 - a.0) * ONE);
+    // and so does moving from the pixel centre to a sample
+    let sample_steps: Vec<[i64; 3]> = offsets
+        .iter()
+        .map(|&(x, y)| edges.map(|(a, b)| -(b.1 - a.1) * x + (b.0 - a.0) * y))
+        .collect();
+
+    let start = (min_x * ONE, min_y * ONE);
+    let mut row = edges.map(|(a, b)| edge(a, b, start));
+    let mut covered = Vec::with_capacity(offsets.len());
+
+    for y in min_y..=max_y {
+        let mut w = row;
+        for x in min_x..=max_x {
+            covered.clear();
+            for (sample, steps) in sample_steps.iter().enumerate() {
+                let w = [0, 1, 2].map(|i| w[i] + steps[i]);
+                if (0..3).all(|i| w[i] + bias[i] >= 0) {
+                    let weights = w.map(|w| w as f32 / area as f32);
+                    let mut barycentric = [0.; 3];
+                    for (i, &vertex) in order.iter().enumerate() {
+                        barycentric[vertex] = weights[i];
+                    }
+                    covered.push((sample, barycentric));
+                }
+            }
+            if !covered.is_empty() {
+                fragment(Point::new(x as i32, y as i32), &covered);
+            }
+
+            for i in 0..3 {
+                w[i] += step_x[i];
+            }
+        }
+
+        for i in 0..3 {
+            row[i] += step_y[i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Canvas from -32 to 32 on both axes.
+    const SIZE: u32 = 74;
+
+    /// How many of `triangles` cover every canvas point.
+    fn coverage(triangles: &[[(f32, f32); 3]]) -> HashMap<(i32, i32), u32> {
+        let mut count = HashMap::new();
+        for &points in triangles {
+            rasterize_triangle(points, SIZE, SIZE, |point, _| {
+                *count.entry((point.x, point.y)).or_default() += 1;
+            });
+        }
+        count
+    }
+
+    /// Triangles from `center` to every side of the convex `polygon`, every other one
+    /// wound the opposite way.
+    fn fan(center: (f32, f32), polygon: &[(f32, f32)]) -> Vec<[(f32, f32); 3]> {
+        (0..polygon.len())
+            .map(|i| {
+                let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                if i % 2 == 0 {
+                    [center, a, b]
+                } else {
+                    [center, b, a]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shared_edges_through_pixel_centres_cover_every_pixel_once() {
+        // the diagonal and the sides of the square run through pixel centres
+        let (a, b, c, d) = ((-10., -10.), (10., -10.), (10., 10.), (-10., 10.));
+        let count = coverage(&[[a, b, c], [a, d, c]]);
+
+        assert!(count.values().all(|&n| n == 1));
+        // two of the sides are in, the other two are not
+        assert_eq!(count.len(), 20 * 20);
+    }
+
+    #[test]
+    fn shared_edges_of_a_fan_cover_every_pixel_once() {
+        let polygon: Vec<(f32, f32)> = (0..7)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::TAU / 7. + 0.3;
+                (0.37 + 20.3 * angle.cos(), -0.21 + 20.3 * angle.sin())
+            })
+            .collect();
+        let count = coverage(&fan((1.13, 2.71), &polygon));
+
+        assert!(count.values().all(|&n| n == 1));
+        // how far inside the polygon a position is, in pixels
+        let inside = |(x, y): (f32, f32)| {
+            (0..polygon.len())
+                .map(|i| {
+                    let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                    edge_distance(a, b, (x, y))
+                })
+                .fold(f32::INFINITY, f32::min)
+        };
+        for y in -32..=32 {
+            for x in -32..=32 {
+                let distance = inside((x as f32, y as f32));
+                if distance > 0.01 {
+                    assert!(count.contains_key(&(x, y)), "({x}, {y}) is skipped");
+                } else if distance < -0.01 {
+                    assert!(!count.contains_key(&(x, y)), "({x}, {y}) is outside");
+                }
+            }
+        }
+    }
+
+    /// Signed distance of `p` to the line from `a` to `b`, positive on the inner side
+    /// of the sides of a polygon going around with growing angles.
+    fn edge_distance(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        (dx * (p.1 - a.1) - dy * (p.0 - a.0)) / dx.hypot(dy)
+    }
+
+    #[test]
+    fn shared_edges_cover_every_sample_once() {
+        let offsets = [(-0.25, -0.25), (0.25, -0.25), (-0.25, 0.25), (0.25, 0.25)];
+        let (a, b, c, d) = ((-9.5, -9.5), (10.25, -9.), (9.75, 10.5), (-10., 9.25));
+        let mut count = HashMap::new();
+        for points in [[a, b, c], [a, c, d]] {
+            rasterize_triangle_multisample(points, SIZE, SIZE, &offsets, |point, covered| {
+                for &(sample, _) in covered {
+                    *count.entry((point.x, point.y, sample)).or_insert(0) += 1;
+                }
+            });
+        }
+
+        assert!(count.values().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn barycentric_coordinates_add_up_to_one() {
+        rasterize_triangle([(-20., -3.), (15., -18.), (4., 25.)], SIZE, SIZE, |_, b| {
+            assert!((b.iter().sum::<f32>() - 1.).abs() < 1e-5);
+            assert!(b.iter().all(|&w| (0. ..=1.).contains(&w)));
+        });
+    }
+
+    #[test]
+    fn degenerate_triangles_cover_nothing() {
+        let count = coverage(&[
+            [(-10., -10.), (0., 0.), (10., 10.)],
+            [(3., 4.), (3., 4.), (3., 4.)],
+            [(-100., 0.), (0., 0.), (5000., 0.)],
+        ]);
+
+        assert!(count.is_empty());
+    }
+}
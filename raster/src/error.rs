@@ -0,0 +1,106 @@
+use image::ImageError;
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Everything that can go wrong loading or rendering a scene.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the file at `path` failed.
+    Io { path: PathBuf, source: io::Error },
+    /// The image at `path` could not be decoded, or encoded in the format of its extension.
+    Image { path: PathBuf, source: ImageError },
+    /// Triangle `triangle` of a model with `vertices` vertices refers to vertex `index`.
+    InvalidMeshIndex {
+        triangle: usize,
+        index: usize,
+        vertices: usize,
+    },
+    /// Triangle `triangle` of a model has no area, so no normal either.
+    DegenerateTriangle { triangle: usize },
+    /// A model has `count` vertex `attribute`s, neither none nor one per vertex.
+    AttributeCount {
+        attribute: &'static str,
+        count: usize,
+        vertices: usize,
+    },
+    /// A scene file that does not parse, `line` counts from 1.
+    BadScene {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// Light `index` of a scene can not be rendered.
+    InvalidLight { index: usize, message: &'static str },
+    /// An image without a single pixel, which can not be sampled, read from `path` if any.
+    EmptyImage { path: Option<PathBuf> },
+    /// `RenderSettings` that can not be rendered.
+    InvalidSettings { message: &'static str },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// For `map_err` on I/O with the file at `path`.
+    pub fn io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// For `map_err` on loading or saving the image at `path`.
+    pub fn image(path: &Path) -> impl FnOnce(ImageError) -> Self + '_ {
+        move |source| Error::Image {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::Image { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::InvalidMeshIndex {
+                triangle,
+                index,
+                vertices,
+            } => write!(
+                f,
+                "triangle {triangle} refers to vertex {index} of a model with {vertices} vertices"
+            ),
+            Error::DegenerateTriangle { triangle } => {
+                write!(f, "triangle {triangle} has no area")
+            }
+            Error::AttributeCount {
+                attribute,
+                count,
+                vertices,
+            } => write!(f, "model has {count} {attribute} for {vertices} vertices"),
+            Error::BadScene {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+            Error::InvalidLight { index, message } => write!(f, "light {index}: {message}"),
+            Error::EmptyImage { path: Some(path) } => {
+                write!(f, "{}: image has no pixels", path.display())
+            }
+            Error::EmptyImage { path: None } => write!(f, "image has no pixels"),
+            Error::InvalidSettings { message } => write!(f, "render settings: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
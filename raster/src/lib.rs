@@ -0,0 +1,43 @@
+//! Rasterizer: model instances are transformed into camera space, clipped against the
+//! viewing frustum and projected onto a canvas whose origin is in the middle, then drawn
+//! as wireframes or filled, textured and lit with flat, Gouraud or Phong shading.
+//!
+//! ```no_run
+//! use raster::{demo, render, RenderSettings};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let (canvas, aovs) = render(&demo::scene(), &demo::camera(), &RenderSettings::default())?;
+//! canvas.save("wireframe.png")?;
+//! aovs.save("wireframe.png".as_ref())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`scene_file`] reads scenes from text files, the drawing primitives in [`draw`]
+//! work on any canvas on their own. Loading and rendering report what went wrong
+//! as an [`Error`].
+
+pub mod antialias;
+pub mod aov;
+pub mod clip;
+pub mod core;
+pub mod demo;
+pub mod draw;
+pub mod edge;
+mod error;
+pub mod light;
+pub mod model;
+mod render;
+pub mod scene_file;
+pub mod texture;
+pub mod varying;
+
+pub use antialias::{AntiAliasing, DownsampleFilter};
+pub use aov::AovBuffers;
+pub use core::{Camera, Color, Matrix4, Plane, Point, Vector3};
+pub use draw::{LineCap, LineStyle};
+pub use error::{Error, Result};
+pub use light::{Light, LightType};
+pub use model::{Model, ModelName, Transform, Triangle};
+pub use render::{render, CropRegion, Rasterizer, RenderSettings, Scene, ShadingMode};
+pub use texture::{Filter, Texture, WrapMode};
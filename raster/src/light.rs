@@ -0,0 +1,72 @@
+use crate::{core::Vector3, Matrix4};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightType {
+    /// Reaches every point evenly.
+    Ambient,
+    /// Sits at `direction`, which is a position for this type.
+    Point,
+    /// Comes from infinitely far away along `direction`.
+    Directional,
+}
+
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub intensity: f32,
+    pub light_type: LightType,
+    /// Position of point lights, direction towards directional lights, `None` for ambient ones.
+    pub direction: Option<Vector3>,
+}
+
+impl Light {
+    /// The same light with its position or direction taken through `matrix`.
+    pub fn transform(&self, matrix: &Matrix4) -> Self {
+        let direction = self.direction.map(|direction| match self.light_type {
+            LightType::Point => matrix.transform_point(direction),
+            _ => matrix.transform_vector(direction),
+        });
+
+        Self {
+            direction,
+            ..self.clone()
+        }
+    }
+}
+
+/// Light reflected towards the origin by the surface at `position` facing `normal`,
+/// all in the space of `lights`, without shadows.
+/// `specular` is the Phong exponent, -1 for matte surfaces.
+pub fn compute_illumination(
+    lights: &[Light],
+    position: Vector3,
+    normal: Vector3,
+    specular: i32,
+) -> f32 {
+    let mut illumination = 0.;
+
+    for light in lights {
+        let to_light = match (light.light_type, light.direction) {
+            (LightType::Ambient, _) => {
+                illumination += light.intensity;
+                continue;
+            }
+            (LightType::Point, Some(light_position)) => light_position - position,
+            (LightType::Directional, Some(direction)) => direction,
+            // rejected by `Scene::validate`
+            (_, None) => continue,
+        };
+
+        illumination += math::diffuse(normal, to_light, light.intensity);
+        if specular != -1 {
+            illumination += math::specular(
+                normal,
+                to_light,
+                -position,
+                specular as f32,
+                light.intensity,
+            );
+        }
+    }
+
+    illumination
+}
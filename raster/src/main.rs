@@ -1,432 +1,274 @@
-use image::{Pixel, Rgb, RgbImage};
-use std::path::Path;
-
-use core::*;
-use matrix::*;
-use model::*;
-use vector_point::*;
-
-mod core;
-mod matrix;
-mod model;
-mod vector_point;
-
-fn put_pixel(canvas: &mut RgbImage, color: &mut Rgb<u8>, coord: Point) {
-    let y_offset = CANVAS_HEIGHT / 2;
-    let x_offset = CANVAS_WIDTH / 2;
-
-    if coord.x < -x_offset || coord.x > x_offset || coord.y < -y_offset || coord.y > y_offset {
-        // println!("Point - {:?} is out of bound ", coord);
-        return;
-    }
-
-    canvas.put_pixel(
-        (coord.x + x_offset) as u32,
-        (coord.y + y_offset) as u32,
-        *color,
-    );
-}
-
-/// Creates a Vec of dependant values d, d = f(i).
-/// i - independant variables.
-/// works with i32
-fn interpolate(i0: i32, d0: i32, i1: i32, d1: i32) -> Vec<i32> {
-    if i0 == i1 {
-        return vec![d0];
-    }
-
-    let mut values: Vec<i32> = vec![];
-    let a = ((d1 - d0) as f32) / ((i1 - i0) as f32);
-    let mut d = d0 as f32;
-
-    for _i in i0..=i1 {
-        values.push(d.round() as i32);
-
-        d += a;
-    }
-
-    values
-}
-
-fn interpolate_f32(i0: i32, d0: f32, i1: i32, d1: f32) -> Vec<f32> {
-    if i0 == i1 {
-        return vec![d0];
-    }
-
-    let mut values: Vec<f32> = vec![];
-    let a = (d1 - d0) / ((i1 - i0) as f32);
-    let mut d = d0;
-
-    for _i in i0..=i1 {
-        values.push(d);
-
-        d += a;
-    }
-
-    values
+use image::ImageFormat;
+use std::{env, path::PathBuf, process::ExitCode, str::FromStr};
+
+use raster::{
+    demo, draw::MIN_DASH_PERIOD, render, scene_file, AntiAliasing, CropRegion, DownsampleFilter,
+    Error, LineCap, LineStyle, Rasterizer, RenderSettings, ShadingMode,
+};
+
+const USAGE: &str = "\
+Usage: raster [OPTIONS]
+
+Draws a scene file, or the built-in demo scene without one.
+
+Options:
+  -s, --scene FILE          scene to draw, in the format of the `scene_file` module
+  -o, --output FILE         image to write [default: ./imgs/1_draw_line.png]
+  -f, --format FORMAT       png, jpeg, bmp, tga, tiff, ... [default: from the output extension]
+  -r, --resolution WxH      canvas size in pixels, the image has a 10 pixel margin on top
+                            [default: 1500x1500]
+      --crop X,Y,W,H        keep only this rectangle of the image
+      --shading MODE        wireframe, flat, gouraud or phong [default: wireframe]
+      --rasterizer NAME     scanline, or edge for watertight sub-pixel edge functions
+                            [default: scanline]
+      --line-width PIXELS   draw anti-aliased wireframe lines this thick
+      --line-cap CAP        butt, square or round ends of the wireframe lines [default: butt]
+      --dash LENGTHS        comma separated dash and gap lengths of the wireframe lines
+      --aliased             keep the wireframe lines of the options above hard edged
+      --msaa SAMPLES        smooth the edges of filled triangles with 2, 4, 8 or 16 depth
+                            and coverage samples per pixel, shading each pixel once
+      --ssaa FACTOR         smooth everything by drawing it FACTOR times larger, 2 to 8,
+                            and scaling it back down
+      --ssaa-filter FILTER  box, tent or gaussian filter of --ssaa [default: box]
+      --aovs                also write depth, normal and id AOVs next to the image
+  -h, --help                print this help
+";
+
+/// Everything the command line decides.
+struct Options {
+    scene: Option<PathBuf>,
+    output: PathBuf,
+    format: Option<ImageFormat>,
+    settings: RenderSettings,
+    aovs: bool,
 }
 
-fn draw_line(canvas: &mut RgbImage, point_a: &mut Point, point_b: &mut Point, color: &mut Rgb<u8>) {
-    let dx = point_b.x - point_a.x;
-    let dy = point_b.y - point_a.y;
-    let mut p0 = point_a.clone();
-    let mut p1 = point_b.clone();
-
-    if dx.abs() > dy.abs() {
-        //line is horizontalish
-        if dx < 0 {
-            let temp = p0;
-            p0 = p1;
-            p1 = temp;
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
         }
-
-        let ys = interpolate(p0.x, p0.y, p1.x, p1.y);
-
-        for x in p0.x..p1.x {
-            let point = Point::new(x, ys[(x - p0.x) as usize]);
-            put_pixel(canvas, color, point);
-        }
-    } else {
-        // Line is vertical-ish
-        if dy < 0 {
-            let temp = p0;
-            p0 = p1;
-            p1 = temp;
+        Err(message) => {
+            eprintln!("error: {message}\n\nTry `raster --help`.");
+            return ExitCode::from(2);
         }
+    };
 
-        let xs = interpolate(p0.y, p0.x, p1.y, p1.x);
-
-        for y in p0.y..p1.y {
-            let point = Point::new(xs[(y - p0.y) as usize], y);
-            put_pixel(canvas, color, point);
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
         }
     }
 }
 
-fn draw_wireframe_triangle(
-    p0: &mut Point,
-    p1: &mut Point,
-    p2: &mut Point,
-    color: &mut Rgb<u8>,
-    canvas: &mut RgbImage,
-) {
-    draw_line(canvas, p0, p1, color);
-    draw_line(canvas, p1, p2, color);
-    draw_line(canvas, p0, p2, color);
+fn run(options: &Options) -> Result<(), String> {
+    let (scene, camera) = match &options.scene {
+        Some(path) => scene_file::load(path).map_err(|error| error.to_string())?,
+        None => (demo::scene(), demo::camera()),
+    };
+
+    let (canvas, aovs) =
+        render(&scene, &camera, &options.settings).map_err(|error| error.to_string())?;
+
+    let path = &options.output;
+    let saved = match options.format {
+        Some(format) => canvas.save_with_format(path, format),
+        None => canvas.save(path),
+    };
+    saved
+        .map_err(Error::image(path))
+        .and_then(|()| {
+            if options.aovs {
+                aovs.save(path)
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|error| error.to_string())
 }
 
-fn draw_filled_triangle(
-    p0: &mut Point,
-    p1: &mut Point,
-    p2: &mut Point,
-    color: Rgb<u8>,
-    canvas: &mut RgbImage,
-) {
-    if p1.y < p0.y {
-        Point::swap(p1, p0);
-    }
-
-    if p2.y < p0.y {
-        Point::swap(p2, p0);
-    }
-
-    if p2.y < p1.y {
-        Point::swap(p2, p1);
-    }
-
-    let mut x01 = interpolate(p0.y, p0.x, p1.y, p1.x);
-    let mut h01 = interpolate_f32(p0.y, 0., p1.y, 0.4);
-
-    let mut x12 = interpolate(p1.y, p1.x, p2.y, p2.x);
-    let mut h12 = interpolate_f32(p1.y, 0.4, p2.y, 0.9);
-
-    let x02 = interpolate(p0.y, p0.x, p2.y, p2.x);
-    let h02 = interpolate_f32(p0.y, 0., p2.y, 0.9);
-
-    x01.pop().unwrap();
-    x01.append(&mut x12);
-
-    h01.pop().unwrap();
-    h01.append(&mut h12);
-
-    let m = ((x02.len() as f32) / 2.).floor() as usize;
-
-    let x_left;
-    let x_right;
-
-    let h_left;
-    let h_right;
-
-    if x02[m] < x01[m] {
-        x_left = x02;
-        h_left = h02;
-
-        x_right = x01;
-        h_right = h01;
-    } else {
-        x_left = x01;
-        h_left = h01;
-
-        x_right = x02;
-        h_right = h02;
-    }
-
-    for y in p0.y..=p2.y {
-        let x_l = x_left[(y - p0.y) as usize];
-        let x_r = x_right[(y - p0.y) as usize];
-
-        let h_segment = interpolate_f32(
-            x_l,
-            h_left[(y - p0.y) as usize],
-            x_r,
-            h_right[(y - p0.y) as usize],
-        );
-
-        for x in x_l..x_r {
-            let mut color = color;
-            color.apply(|x_in| ((x_in as f32) * h_segment[(x - x_l) as usize]).round() as u8);
-            put_pixel(canvas, &mut color, Point::new(x, y))
+/// `None` when help was asked for.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        scene: None,
+        output: PathBuf::from("./imgs/1_draw_line.png"),
+        format: None,
+        settings: RenderSettings::default(),
+        aovs: false,
+    };
+    let settings = &mut options.settings;
+    let mut ssaa_filter = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // `--flag=value` as well as `--flag value`
+        let (flag, mut inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .take()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{flag} needs a value"))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-s" | "--scene" => options.scene = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => options.output = PathBuf::from(value()?),
+            "-f" | "--format" => {
+                let name = value()?;
+                options.format = Some(
+                    ImageFormat::from_extension(&name)
+                        .filter(|format| format.can_write())
+                        .ok_or_else(|| format!("can not write `{name}` images"))?,
+                );
+            }
+            "-r" | "--resolution" => {
+                (settings.width, settings.height) = parse_resolution(&value()?)?;
+            }
+            "--crop" => {
+                let [x, y, width, height] = parse_list(&flag, &value()?)?;
+                settings.crop = Some(CropRegion {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+            "--shading" => {
+                settings.shading = match value()?.as_str() {
+                    "wireframe" => ShadingMode::Wireframe,
+                    "flat" => ShadingMode::Flat,
+                    "gouraud" => ShadingMode::Gouraud,
+                    "phong" => ShadingMode::Phong,
+                    other => return Err(format!("unknown shading mode `{other}`")),
+                };
+            }
+            "--rasterizer" => {
+                settings.rasterizer = match value()?.as_str() {
+                    "scanline" => Rasterizer::Scanline,
+                    "edge" => Rasterizer::EdgeFunction,
+                    other => return Err(format!("unknown rasterizer `{other}`")),
+                };
+            }
+            "--line-width" => {
+                let width: f32 = parse_number(&flag, &value()?)?;
+                if !(width > 0. && width.is_finite()) {
+                    return Err(format!("{flag} must be a positive number of pixels"));
+                }
+                settings
+                    .line
+                    .get_or_insert_with(LineStyle::default)
+                    .thickness = width;
+            }
+            "--line-cap" => {
+                settings.line.get_or_insert_with(LineStyle::default).cap = match value()?.as_str() {
+                    "butt" => LineCap::Butt,
+                    "square" => LineCap::Square,
+                    "round" => LineCap::Round,
+                    other => return Err(format!("unknown line cap `{other}`")),
+                };
+            }
+            "--dash" => {
+                let text = value()?;
+                let dashes = text
+                    .split(',')
+                    .map(|length| parse_number(&flag, length.trim()))
+                    .collect::<Result<Vec<f32>, _>>()?;
+                if dashes
+                    .iter()
+                    .any(|&length| !(length >= 0. && length.is_finite()))
+                    || dashes.iter().sum::<f32>() < MIN_DASH_PERIOD
+                {
+                    return Err(format!(
+                        "{flag} expects lengths in pixels adding up to 1/16 or more, found `{text}`"
+                    ));
+                }
+                settings.line.get_or_insert_with(LineStyle::default).dashes = dashes;
+            }
+            "--aliased" => {
+                settings
+                    .line
+                    .get_or_insert_with(LineStyle::default)
+                    .anti_aliased = false;
+            }
+            "--msaa" => {
+                let samples = parse_number(&flag, &value()?)?;
+                if !raster::antialias::is_sample_count(samples) || samples == 1 {
+                    return Err(format!("{flag} takes 2, 4, 8 or 16 samples"));
+                }
+                settings.anti_aliasing = AntiAliasing::Multisample(samples);
+            }
+            "--ssaa" => {
+                let factor = parse_number(&flag, &value()?)?;
+                if !(2..=8).contains(&factor) {
+                    return Err(format!("{flag} takes a factor from 2 to 8"));
+                }
+                settings.anti_aliasing = AntiAliasing::Supersample {
+                    factor,
+                    filter: DownsampleFilter::default(),
+                };
+            }
+            "--ssaa-filter" => {
+                ssaa_filter = Some(match value()?.as_str() {
+                    "box" => DownsampleFilter::Box,
+                    "tent" => DownsampleFilter::Tent,
+                    "gaussian" => DownsampleFilter::Gaussian,
+                    other => return Err(format!("unknown downsample filter `{other}`")),
+                });
+            }
+            "--aovs" => options.aovs = true,
+            _ if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            _ => return Err(format!("unexpected argument `{flag}`")),
         }
-    }
-}
-
-fn project_vertex(v: HomogenousVectorPoint) -> Point {
-    Point::viewport_to_canvas(
-        v.values[0] * PROJECTION_PLANE_Z / v.values[2],
-        v.values[1] * PROJECTION_PLANE_Z / v.values[2],
-    )
-}
-
-fn render_triangle(canvas: &mut RgbImage, triangle: &mut Triangle, projected: &mut Vec<Point>) {
-    // println!("{:?}", projected);
-    let mut p0 = projected[triangle.vertex.0].clone();
-    let mut p1 = projected[triangle.vertex.1].clone();
-    let mut p2 = projected[triangle.vertex.2].clone();
-    draw_wireframe_triangle(&mut p0, &mut p1, &mut p2, &mut triangle.color, canvas)
-}
-
-fn clip_triangle(
-    triangle: &Triangle,
-    plane: &Plane,
-    triangles: &mut Vec<Triangle>,
-    vertices: &Vec<VectorPoint>,
-) {
-    let v0 = vertices[triangle.vertex.0];
-    let v1 = vertices[triangle.vertex.1];
-    let v2 = vertices[triangle.vertex.2];
-
-    let in0 = dot(
-        &HomogenousVectorPoint::new(&plane.normal),
-        &HomogenousVectorPoint::new(&v0),
-    ) + plane.distance
-        > 0.;
-    let in1 = dot(
-        &HomogenousVectorPoint::new(&plane.normal),
-        &HomogenousVectorPoint::new(&v1),
-    ) + plane.distance
-        > 0.;
-    let in2 = dot(
-        &HomogenousVectorPoint::new(&plane.normal),
-        &HomogenousVectorPoint::new(&v2),
-    ) + plane.distance
-        > 0.;
-
-    let mut in_count = 0;
-
-    if in0 {
-        in_count += 1;
-    }
-
-    if in1 {
-        in_count += 1;
-    }
-
-    if in2 {
-        in_count += 1;
-    }
-
-    match in_count {
-        0 => return,
-        3 => triangles.push(triangle.clone()),
-        _ => return,
-    }
-}
 
-fn transform_and_clip(
-    clipping_planes: &Vec<Plane>,
-    model: &Model,
-    scale: f32,
-    transform: Matrix,
-) -> Option<Model> {
-    let center = transform.clone() * HomogenousVectorPoint::new(&model.bounds_center);
-    println!("{:?}", center);
-    let radius = model.bounds_radius * scale;
-
-    for p in clipping_planes {
-        let distance = dot(&HomogenousVectorPoint::new(&p.normal), &center) + p.distance;
-        if distance < -radius {
-            return None;
+        if let Some(value) = inline {
+            return Err(format!("{flag} does not take a value, found `{value}`"));
         }
     }
 
-    let mut vertices: Vec<VectorPoint> = vec![];
-
-    for v in &model.vertices {
-        vertices.push(VectorPoint::from(
-            transform.clone() * HomogenousVectorPoint::new(v),
-        ));
-    }
-
-    let mut triangles = model.triangles.clone();
-
-    for p in clipping_planes {
-        let mut new_triangles = vec![];
-
-        for t in &triangles {
-            clip_triangle(t, p, &mut new_triangles, &vertices);
+    if let Some(filter) = ssaa_filter {
+        match &mut settings.anti_aliasing {
+            AntiAliasing::Supersample { filter: chosen, .. } => *chosen = filter,
+            _ => return Err("--ssaa-filter needs --ssaa".to_string()),
         }
-
-        triangles = new_triangles;
     }
 
-    return Some(Model::new(
-        ModelName::CUBE,
-        vertices,
-        triangles,
-        model.transform.clone(),
-        center.into(),
-        model.bounds_radius,
-    ));
-}
-
-fn render_scene(canvas: &mut RgbImage, camera: Camera, instances: Vec<Model>) {
-    let camera_matrix = Matrix::transpose(&camera.orientation)
-        * Matrix::new_translation_matrix(-1. * camera.position);
-    for i in instances {
-        let transform = camera_matrix.clone() * i.transform_matrix.clone();
-        let clipped = transform_and_clip(
-            &camera.clipping_planes,
-            &i,
-            i.transform.scale,
-            transform.clone(),
-        );
+    settings.validate().map_err(|error| error.to_string())?;
 
-
-        if clipped.is_some() {
-        println!("clipped - {:?}  \n", clipped);
-            render_instance(canvas, clipped.unwrap(), transform);
-        }
-    }
+    Ok(Some(options))
 }
 
-fn render_instance(canvas: &mut RgbImage, instance: Model, transform: Matrix) {
-    let mut projected = vec![];
-
-    for v in instance.vertices {
-        let v_homogenous = HomogenousVectorPoint::new(&v);
-        projected.push(project_vertex(transform.clone() * v_homogenous));
-    }
-
-    for mut t in instance.triangles {
-        render_triangle(canvas, &mut t, &mut projected)
-    }
+fn parse_number<T: FromStr>(flag: &str, text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("{flag} expects a number, found `{text}`"))
 }
 
-fn main() {
-    let path = Path::new("./imgs/1_draw_line.png");
-
-    let BLUE = Rgb([0, 0, 255]);
-    let RED = Rgb([255, 0, 0]);
-    let GREEN = Rgb([0, 255, 0]);
-    let CYAN = Rgb([0, 255, 255]);
-    let PURPLE = Rgb([128, 0, 128]);
-    let YELLOW = Rgb([255, 255, 0]);
-
-    let mut canvas = RgbImage::new(
-        u32::try_from(CANVAS_WIDTH + THRESHOLD_CANVAS).unwrap(),
-        u32::try_from(CANVAS_HEIGHT + THRESHOLD_CANVAS).unwrap(),
-    );
+/// `N` comma separated numbers, like `0,0,64,64`.
+fn parse_list<const N: usize>(flag: &str, text: &str) -> Result<[u32; N], String> {
+    let numbers: Vec<u32> = text
+        .split(',')
+        .map(|number| parse_number(flag, number.trim()))
+        .collect::<Result<_, _>>()?;
+    numbers
+        .try_into()
+        .map_err(|_| format!("{flag} expects {N} comma separated numbers, found `{text}`"))
+}
 
-    for (_x, _y, pix) in canvas.enumerate_pixels_mut() {
-        pix.0 = BACKGROUND_COLOR.0;
+/// `WIDTHxHEIGHT`, both at least 1.
+fn parse_resolution(text: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected a resolution like 1920x1080, found `{text}`");
+    let (width, height) = text.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
     }
-
-    // Define vertices
-    let v0 = VectorPoint::new(1., 1., 1.);
-    let v1 = VectorPoint::new(-1., 1., 1.);
-    let v2 = VectorPoint::new(-1., -1., 1.);
-    let v3 = VectorPoint::new(1., -1., 1.);
-    let v4 = VectorPoint::new(1., 1., -1.);
-    let v5 = VectorPoint::new(-1., 1., -1.);
-    let v6 = VectorPoint::new(-1., -1., -1.);
-    let v7 = VectorPoint::new(1., -1., -1.);
-
-    // // Define triangles
-    let triangles = vec![
-        Triangle::new((0, 1, 2), RED),
-        Triangle::new((0, 2, 3), RED),
-        Triangle::new((4, 0, 3), GREEN),
-        Triangle::new((4, 3, 7), GREEN),
-        Triangle::new((5, 4, 7), BLUE),
-        Triangle::new((5, 7, 6), BLUE),
-        Triangle::new((1, 5, 6), YELLOW),
-        Triangle::new((1, 6, 2), YELLOW),
-        Triangle::new((4, 5, 1), PURPLE),
-        Triangle::new((4, 1, 0), PURPLE),
-        Triangle::new((2, 6, 7), CYAN),
-        Triangle::new((2, 7, 3), CYAN),
-    ];
-
-    let vertices = vec![v0, v1, v2, v3, v4, v5, v6, v7];
-
-    let bounds_center = VectorPoint::new(0., 0., 0.);
-
-    let model_instance1 = Model::new(
-        ModelName::CUBE,
-        vertices.clone(),
-        triangles.clone(),
-        Transform::new(0.75, 0, VectorPoint::new(-1.5, 0., 5.)),
-        bounds_center.clone(),
-        f32::sqrt(3.),
-    );
-    let model_instance2 = Model::new(
-        ModelName::CUBE,
-        vertices.clone(),
-        triangles.clone(),
-        Transform::new(1., 195, VectorPoint::new(1.25, 2.5, 7.5)),
-        bounds_center.clone(),
-        f32::sqrt(3.),
-    );
-
-    let model_instance3 = Model::new(
-        ModelName::CUBE,
-        vertices.clone(),
-        triangles.clone(),
-        Transform::new(1., 195, VectorPoint::new(0., 0., -10.)),
-        bounds_center.clone(),
-        f32::sqrt(3.),
-    );
-
-    let s2 = 1.0 / f32::sqrt(2.);
-
-    let clipping_planes = vec![
-        Plane::new(VectorPoint::new(0., 0., 1.), -1.), //Near
-        Plane::new(VectorPoint::new(s2, 0., s2), 0.),  // Left
-        Plane::new(VectorPoint::new(-s2, 0., s2), 0.), // Right
-        Plane::new(VectorPoint::new(0., -s2, s2), 0.), // Top
-        Plane::new(VectorPoint::new(0., s2, s2), 0.),  // Bottom
-    ];
-
-    let camera = Camera::new(
-        VectorPoint::new(-3., 1., 2.),
-        Matrix::new_Y_rotation_matrix(-30),
-        clipping_planes,
-    );
-    render_scene(
-        &mut canvas,
-        camera,
-        vec![model_instance1, model_instance2, model_instance3],
-    );
-
-    canvas.save(path).unwrap();
 }
@@ -1,32 +1,53 @@
-use crate::{Color, Matrix, VectorPoint};
+use image::Rgb;
+use std::sync::Arc;
+
+use crate::{
+    core::{Aabb, BoundingSphere},
+    error::{Error, Result},
+    texture::Texture,
+    Color, Matrix4, Vector3,
+};
 
 #[derive(Clone, Debug)]
 pub struct Triangle {
     pub vertex: (usize, usize, usize),
     pub color: Color,
+    /// Normals at the three corners, in the order of `vertex`. They win over the normals
+    /// of the model's vertices, so triangles sharing a vertex can still meet at a hard edge.
+    pub normals: Option<[Vector3; 3]>,
+    /// Texture coordinates at the three corners, winning over the ones of the vertices.
+    pub uvs: Option<[(f32, f32); 3]>,
 }
 
 impl Triangle {
     pub fn new(vertex: (usize, usize, usize), color: Color) -> Self {
-        Self { vertex, color }
+        Self {
+            vertex,
+            color,
+            normals: None,
+            uvs: None,
+        }
     }
-}
 
-#[derive(Debug)]
+    fn corners(&self) -> [usize; 3] {
+        [self.vertex.0, self.vertex.1, self.vertex.2]
+    }
+}
 
+#[derive(Clone, Copy, Debug)]
 pub enum ModelName {
-    CUBE,
+    Cube,
 }
 
 #[derive(Clone, Debug)]
 pub struct Transform {
     pub scale: f32,
     pub rotation: i32,
-    pub translation: VectorPoint,
+    pub translation: Vector3,
 }
 
 impl Transform {
-    pub fn new(scale: f32, rotation: i32, translation: VectorPoint) -> Self {
+    pub fn new(scale: f32, rotation: i32, translation: Vector3) -> Self {
         Self {
             scale,
             rotation,
@@ -38,34 +59,202 @@ impl Transform {
 #[derive(Debug)]
 pub struct Model {
     pub name: ModelName,
-    pub vertices: Vec<VectorPoint>,
+    pub vertices: Vec<Vector3>,
     pub triangles: Vec<Triangle>,
     pub transform: Transform,
-    pub transform_matrix: Matrix,
-    pub bounds_center: VectorPoint,
-    pub bounds_radius: f32,
+    pub transform_matrix: Matrix4,
+    /// Around `vertices`, in model space, see `update_bounds`.
+    pub bounding_sphere: BoundingSphere,
+    /// Around `vertices`, in model space, see `update_bounds`.
+    pub aabb: Aabb,
+    /// Phong exponent of every triangle, -1 for matte surfaces.
+    pub specular: i32,
+    /// Normal of every vertex, or empty to shade with the normals of the triangles.
+    pub normals: Vec<Vector3>,
+    /// Texture coordinates of every vertex, or empty.
+    pub uvs: Vec<(f32, f32)>,
+    /// Colour of every vertex, or empty to use the colour of the triangles.
+    pub colors: Vec<Color>,
+    /// Replaces the colours of the filled triangles that have texture coordinates.
+    pub texture: Option<Arc<Texture>>,
 }
 
 impl Model {
     pub fn new(
         name: ModelName,
-        vertices: Vec<VectorPoint>,
+        vertices: Vec<Vector3>,
         triangles: Vec<Triangle>,
         transform: Transform,
-        bounds_center: VectorPoint,
-        bounds_radius: f32,
     ) -> Self {
-        let transform_matrix = Matrix::new_translation_matrix(transform.translation)
-            * (Matrix::new_Y_rotation_matrix(transform.rotation)
-                * Matrix::new_scale_matrix(transform.scale));
+        let transform_matrix = Matrix4::translation(transform.translation)
+            * (Matrix4::rotation_y((transform.rotation as f32).to_radians())
+                * Matrix4::scaling(Vector3::splat(transform.scale)));
+        let bounding_sphere = BoundingSphere::from_points(&vertices);
+        let aabb = Aabb::from_points(vertices.iter().copied());
         Self {
             name,
             vertices,
             triangles,
             transform,
             transform_matrix,
-            bounds_center,
-            bounds_radius,
+            bounding_sphere,
+            aabb,
+            specular: -1,
+            normals: vec![],
+            uvs: vec![],
+            colors: vec![],
+            texture: None,
+        }
+    }
+
+    /// Recomputes `bounding_sphere` and `aabb`, to be called after changing `vertices`.
+    pub fn update_bounds(&mut self) {
+        self.bounding_sphere = BoundingSphere::from_points(&self.vertices);
+        self.aabb = Aabb::from_points(self.vertices.iter().copied());
+    }
+
+    /// Normals at the corners of `triangle`: its own, the ones of its vertices,
+    /// or `None` when the model has neither.
+    pub fn corner_normals(&self, triangle: &Triangle) -> Option<[Vector3; 3]> {
+        match triangle.normals {
+            Some(normals) => Some(normals),
+            None if !self.normals.is_empty() => Some(triangle.corners().map(|i| self.normals[i])),
+            None => None,
+        }
+    }
+
+    /// Texture coordinates at the corners of `triangle`: its own, the ones of its vertices,
+    /// or `None` when the model has neither.
+    pub fn corner_uvs(&self, triangle: &Triangle) -> Option<[(f32, f32); 3]> {
+        match triangle.uvs {
+            Some(uvs) => Some(uvs),
+            None if !self.uvs.is_empty() => Some(triangle.corners().map(|i| self.uvs[i])),
+            None => None,
+        }
+    }
+
+    /// Gives every triangle corner the average normal of the triangles around its vertex
+    /// that face less than `max_angle` degrees away from the triangle, weighted by area.
+    /// Edges sharper than that stay hard, 180 smooths everything.
+    /// Triangles only count as neighbours when they share the vertex index, not just its position.
+    pub fn smooth_normals(&mut self, max_angle: f32) {
+        let faces: Vec<Vector3> = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let [a, b, c] = triangle.corners().map(|i| self.vertices[i]);
+                (b - a).cross(c - a)
+            })
+            .collect();
+
+        let mut around = vec![vec![]; self.vertices.len()];
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            for vertex in triangle.corners() {
+                around[vertex].push(index);
+            }
         }
+
+        let min_cos = max_angle.to_radians().cos();
+        for (index, triangle) in self.triangles.iter_mut().enumerate() {
+            let facing = faces[index].normalize();
+            triangle.normals = Some(triangle.corners().map(|vertex| {
+                around[vertex]
+                    .iter()
+                    .filter(|&&other| {
+                        other == index || faces[other].normalize().dot(facing) >= min_cos
+                    })
+                    .fold(Vector3::ZERO, |sum, &other| sum + faces[other])
+                    .normalize()
+            }));
+        }
+    }
+
+    /// Fails when a triangle refers to a vertex that does not exist or has no area,
+    /// or when there are vertex normals, uvs or colours but not one per vertex.
+    pub fn validate(&self) -> Result<()> {
+        for (attribute, count) in [
+            ("normals", self.normals.len()),
+            ("uvs", self.uvs.len()),
+            ("colors", self.colors.len()),
+        ] {
+            if count != 0 && count != self.vertices.len() {
+                return Err(Error::AttributeCount {
+                    attribute,
+                    count,
+                    vertices: self.vertices.len(),
+                });
+            }
+        }
+
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let (a, b, c) = triangle.vertex;
+            if let Some(&vertex) = [a, b, c].iter().find(|&&i| i >= self.vertices.len()) {
+                return Err(Error::InvalidMeshIndex {
+                    triangle: index,
+                    index: vertex,
+                    vertices: self.vertices.len(),
+                });
+            }
+
+            let [a, b, c] = [a, b, c].map(|i| self.vertices[i]);
+            if (b - a).cross(c - a) == Vector3::ZERO {
+                return Err(Error::DegenerateTriangle { triangle: index });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cube from -1 to 1 with a colour per face, placed by `transform`.
+    /// Every face is mapped to the whole (0, 0) to (1, 1) texture square.
+    pub fn cube(transform: Transform) -> Self {
+        let blue = Rgb([0, 0, 255]);
+        let red = Rgb([255, 0, 0]);
+        let green = Rgb([0, 255, 0]);
+        let cyan = Rgb([0, 255, 255]);
+        let purple = Rgb([128, 0, 128]);
+        let yellow = Rgb([255, 255, 0]);
+
+        let vertices = vec![
+            Vector3::new(1., 1., 1.),
+            Vector3::new(-1., 1., 1.),
+            Vector3::new(-1., -1., 1.),
+            Vector3::new(1., -1., 1.),
+            Vector3::new(1., 1., -1.),
+            Vector3::new(-1., 1., -1.),
+            Vector3::new(-1., -1., -1.),
+            Vector3::new(1., -1., -1.),
+        ];
+
+        let triangles = vec![
+            Triangle::new((0, 1, 2), red),
+            Triangle::new((0, 2, 3), red),
+            Triangle::new((4, 0, 3), green),
+            Triangle::new((4, 3, 7), green),
+            Triangle::new((5, 4, 7), blue),
+            Triangle::new((5, 7, 6), blue),
+            Triangle::new((1, 5, 6), yellow),
+            Triangle::new((1, 6, 2), yellow),
+            Triangle::new((4, 5, 1), purple),
+            Triangle::new((4, 1, 0), purple),
+            Triangle::new((2, 6, 7), cyan),
+            Triangle::new((2, 7, 3), cyan),
+        ];
+
+        // both triangles of a face start at the same corner and go around it
+        let triangles = triangles
+            .into_iter()
+            .enumerate()
+            .map(|(index, triangle)| Triangle {
+                uvs: Some(if index % 2 == 0 {
+                    [(1., 1.), (0., 1.), (0., 0.)]
+                } else {
+                    [(1., 1.), (0., 0.), (1., 0.)]
+                }),
+                ..triangle
+            })
+            .collect();
+
+        Self::new(ModelName::Cube, vertices, triangles, transform)
     }
 }
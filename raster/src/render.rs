@@ -0,0 +1,735 @@
+use image::{imageops, Rgb, RgbImage};
+
+use crate::{
+    antialias::{downsample, is_sample_count, AntiAliasing, SampleBuffer},
+    aov::AovBuffers,
+    core::{
+        Aabb, Camera, Matrix4, Plane, Point, Vector3, BACKGROUND_COLOR, PROJECTION_PLANE_Z,
+        THRESHOLD_CANVAS, VIEWPORT_SIZE,
+    },
+    draw::{
+        draw_shaded_triangle, draw_shaded_triangle_subpixel, draw_stroke, draw_wireframe_triangle,
+        LineStyle, MIN_DASH_PERIOD,
+    },
+    error::{Error, Result},
+    light::{compute_illumination, Light, LightType},
+    model::{Model, Transform, Triangle},
+    varying::Varying,
+};
+
+/// Model instances placed in the world and the lights shining on them.
+#[derive(Debug, Default)]
+pub struct Scene {
+    pub instances: Vec<Model>,
+    pub lights: Vec<Light>,
+}
+
+impl Scene {
+    /// Fails on models `Model::validate` rejects and on lights that can not be rendered:
+    /// point lights need a position and directional lights a direction,
+    /// intensities must be finite and not negative.
+    pub fn validate(&self) -> Result<()> {
+        for instance in &self.instances {
+            instance.validate()?;
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let invalid = |message| Err(Error::InvalidLight { index, message });
+
+            if !light.intensity.is_finite() || light.intensity < 0. {
+                return invalid("intensity must be finite and not negative");
+            }
+            match (light.light_type, light.direction) {
+                (LightType::Point, None) => return invalid("point light without a position"),
+                (LightType::Directional, None) => {
+                    return invalid("directional light without a direction")
+                }
+                (LightType::Directional, Some(direction)) if direction == Vector3::ZERO => {
+                    return invalid("directional light with a zero direction")
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How triangles are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShadingMode {
+    /// Outlines in the colour of the triangle, lights are ignored.
+    #[default]
+    Wireframe,
+    /// Filled, lit once per triangle at its centre.
+    Flat,
+    /// Filled, lit at the vertices with the light interpolated in between.
+    Gouraud,
+    /// Filled, lit at every pixel with the normal interpolated from the vertices.
+    Phong,
+}
+
+/// How filled triangles are turned into pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Rasterizer {
+    /// Rows between the edges, walked from vertices rounded to whole pixels.
+    #[default]
+    Scanline,
+    /// Edge functions from sub-pixel vertex positions with the top-left rule,
+    /// see the `edge` module. Meshes come out without cracks.
+    EdgeFunction,
+}
+
+/// Size of the image, the canvas origin sits in its middle.
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Keep only this part of the image, in pixels of the full image with its margin.
+    pub crop: Option<CropRegion>,
+    pub shading: ShadingMode,
+    pub rasterizer: Rasterizer,
+    /// Style of the wireframe lines, `None` for the plain one pixel `draw_line`.
+    pub line: Option<LineStyle>,
+    pub anti_aliasing: AntiAliasing,
+}
+
+/// Rectangle of image pixels, `x` and `y` being its top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 1500,
+            height: 1500,
+            crop: None,
+            shading: ShadingMode::default(),
+            rasterizer: Rasterizer::default(),
+            line: None,
+            anti_aliasing: AntiAliasing::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Fails on a crop region that is empty or reaches outside the image, on lines that are
+    /// not a positive number of pixels thick or whose dashes repeat sooner than
+    /// `MIN_DASH_PERIOD`, on sample counts and supersampling factors `AntiAliasing`
+    /// does not support, and on images whose pixels or samples can not be counted in a `u32`.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message| Err(Error::InvalidSettings { message });
+
+        if let Some(crop) = self.crop {
+            // the image carries the margin around the canvas
+            let margin = THRESHOLD_CANVAS as u32;
+            let width = self.width.saturating_add(margin);
+            let height = self.height.saturating_add(margin);
+            if crop.width == 0
+                || crop.height == 0
+                || crop.x.saturating_add(crop.width) > width
+                || crop.y.saturating_add(crop.height) > height
+            {
+                return invalid("the crop region is empty or reaches outside the image");
+            }
+        }
+
+        if let Some(line) = &self.line {
+            if !(line.thickness > 0. && line.thickness.is_finite()) {
+                return invalid("lines must be a positive number of pixels thick");
+            }
+            let period: f32 = line.dashes.iter().sum();
+            if line
+                .dashes
+                .iter()
+                .any(|&length| !(length >= 0. && length.is_finite()))
+                || !(line.dashes.is_empty() || period >= MIN_DASH_PERIOD)
+            {
+                return invalid(
+                    "dash lengths must not be negative and add up to 1/16 pixel or more",
+                );
+            }
+        }
+
+        let (factor, samples) = match self.anti_aliasing {
+            AntiAliasing::None => (1, 1),
+            AntiAliasing::Multisample(samples) if !is_sample_count(samples) => {
+                return invalid("multisampling takes 1, 2, 4, 8 or 16 samples");
+            }
+            AntiAliasing::Multisample(samples) => (1, samples),
+            AntiAliasing::Supersample { factor, .. } if !(1..=8).contains(&factor) => {
+                return invalid("supersampling takes a factor from 1 to 8");
+            }
+            AntiAliasing::Supersample { factor, .. } => (factor, 1),
+        };
+
+        // the buffers are indexed in `u32`, up to the samples of the largest image drawn
+        let side = |length: u32| {
+            length
+                .checked_mul(factor)?
+                .checked_add(THRESHOLD_CANVAS as u32)
+        };
+        let size = side(self.width)
+            .zip(side(self.height))
+            .and_then(|(width, height)| width.checked_mul(height)?.checked_mul(samples));
+        if size.is_none() {
+            return invalid("the image is too large");
+        }
+        Ok(())
+    }
+
+    /// The same picture `factor` times larger along both axes, lines included, uncropped.
+    /// Plain lines become hard edged ones `factor` pixels thick, so they keep their width.
+    /// Only for settings `validate` accepted, which makes sure the larger image fits.
+    fn supersampled(&self, factor: u32) -> Self {
+        let scale = factor as f32;
+        let line = self.line.clone().unwrap_or(LineStyle {
+            anti_aliased: false,
+            ..LineStyle::default()
+        });
+
+        Self {
+            width: self.width * factor,
+            height: self.height * factor,
+            crop: None,
+            line: Some(LineStyle {
+                thickness: line.thickness * scale,
+                dashes: line.dashes.iter().map(|dash| dash * scale).collect(),
+                ..line
+            }),
+            anti_aliasing: AntiAliasing::None,
+            ..self.clone()
+        }
+    }
+}
+
+/// Draws the scene as seen by `camera` on a white canvas in the shading mode of `settings`,
+/// along with the depth, normal and id of the triangles under every pixel.
+/// Both are `THRESHOLD_CANVAS` pixels larger than asked for, or cut to the crop region.
+/// Fails on scenes `Scene::validate` rejects and settings `RenderSettings::validate` does.
+pub fn render(
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> Result<(RgbImage, AovBuffers)> {
+    scene.validate()?;
+    settings.validate()?;
+
+    let (canvas, aovs) = match settings.anti_aliasing {
+        AntiAliasing::Supersample { factor, filter } => {
+            let (canvas, aovs) = render_image(scene, camera, &settings.supersampled(factor));
+            let width = settings.width + THRESHOLD_CANVAS as u32;
+            let height = settings.height + THRESHOLD_CANVAS as u32;
+            (
+                downsample(&canvas, factor, filter, width, height),
+                aovs.downsample(factor, width, height),
+            )
+        }
+        _ => render_image(scene, camera, settings),
+    };
+
+    Ok(match settings.crop {
+        Some(crop) => (
+            imageops::crop_imm(&canvas, crop.x, crop.y, crop.width, crop.height).to_image(),
+            aovs.crop(crop),
+        ),
+        None => (canvas, aovs),
+    })
+}
+
+/// What filled triangles are drawn on: the canvas and 1/z of its pixels, or their samples
+/// when multisampling.
+struct Target {
+    canvas: RgbImage,
+    depth: Vec<f32>,
+    samples: Option<SampleBuffer>,
+}
+
+/// `render` of the whole image, without supersampling.
+fn render_image(
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> (RgbImage, AovBuffers) {
+    let canvas = RgbImage::from_pixel(
+        settings.width + THRESHOLD_CANVAS as u32,
+        settings.height + THRESHOLD_CANVAS as u32,
+        BACKGROUND_COLOR,
+    );
+    let (width, height) = canvas.dimensions();
+    let samples = match settings.anti_aliasing {
+        AntiAliasing::Multisample(samples) if settings.shading != ShadingMode::Wireframe => {
+            SampleBuffer::new(width, height, samples)
+        }
+        _ => None,
+    };
+    let mut target = Target {
+        canvas,
+        depth: vec![0.; (width * height) as usize],
+        samples,
+    };
+    let mut aovs = AovBuffers::new(width, height);
+
+    render_scene(&mut target, &mut aovs, scene, camera, settings);
+
+    let canvas = match &target.samples {
+        Some(samples) => samples.resolve(),
+        None => target.canvas,
+    };
+    (canvas, aovs)
+}
+
+fn project_vertex(v: Vector3, settings: &RenderSettings) -> Point {
+    Point::viewport_to_canvas(
+        v.x * PROJECTION_PLANE_Z / v.z,
+        v.y * PROJECTION_PLANE_Z / v.z,
+        settings.width,
+    )
+}
+
+/// Same as `project_vertex` without rounding to whole pixels.
+fn project_vertex_exact(v: Vector3, settings: &RenderSettings) -> (f32, f32) {
+    let scale = settings.width as f32 / VIEWPORT_SIZE;
+    (
+        v.x * PROJECTION_PLANE_Z / v.z * scale,
+        v.y * PROJECTION_PLANE_Z / v.z * scale,
+    )
+}
+
+/// Where the corners of a triangle land on the canvas and 1/z there.
+struct ScreenTriangle<'a> {
+    points: [&'a Point; 3],
+    exact: [(f32, f32); 3],
+    inv_depths: [f32; 3],
+}
+
+/// Fills `triangle` with the rasterizer of `settings`, see `draw_shaded_triangle`,
+/// or into the samples of `target` when multisampling.
+fn fill_triangle<V: Varying>(
+    target: &mut Target,
+    settings: &RenderSettings,
+    triangle: &ScreenTriangle,
+    attributes: [V; 3],
+    shade: impl Fn(Point, f32, V) -> Rgb<u8>,
+) {
+    if let Some(samples) = &mut target.samples {
+        samples.fill_triangle(triangle.exact, triangle.inv_depths, attributes, shade);
+        return;
+    }
+
+    let (canvas, depth) = (&mut target.canvas, &mut target.depth);
+    match settings.rasterizer {
+        Rasterizer::Scanline => draw_shaded_triangle(
+            canvas,
+            depth,
+            triangle.points,
+            triangle.inv_depths,
+            attributes,
+            shade,
+        ),
+        Rasterizer::EdgeFunction => draw_shaded_triangle_subpixel(
+            canvas,
+            depth,
+            triangle.exact,
+            triangle.inv_depths,
+            attributes,
+            shade,
+        ),
+    }
+}
+
+fn render_triangle(
+    canvas: &mut RgbImage,
+    triangle: &Triangle,
+    projected: &[Point],
+    exact: &[(f32, f32)],
+    line: Option<&LineStyle>,
+) {
+    if let Some(style) = line {
+        let (a, b, c) = triangle.vertex;
+        for (from, to) in [(a, b), (b, c), (a, c)] {
+            draw_stroke(canvas, exact[from], exact[to], triangle.color, style);
+        }
+        return;
+    }
+
+    let mut p0 = projected[triangle.vertex.0].clone();
+    let mut p1 = projected[triangle.vertex.1].clone();
+    let mut p2 = projected[triangle.vertex.2].clone();
+    let mut color = triangle.color;
+    draw_wireframe_triangle(&mut p0, &mut p1, &mut p2, &mut color, canvas)
+}
+
+fn clip_triangle(
+    triangle: &Triangle,
+    plane: &Plane,
+    triangles: &mut Vec<Triangle>,
+    vertices: &[Vector3],
+) {
+    let v0 = vertices[triangle.vertex.0];
+    let v1 = vertices[triangle.vertex.1];
+    let v2 = vertices[triangle.vertex.2];
+
+    let in0 = plane.normal.dot(v0) + plane.distance > 0.;
+    let in1 = plane.normal.dot(v1) + plane.distance > 0.;
+    let in2 = plane.normal.dot(v2) + plane.distance > 0.;
+
+    let mut in_count = 0;
+
+    if in0 {
+        in_count += 1;
+    }
+
+    if in1 {
+        in_count += 1;
+    }
+
+    if in2 {
+        in_count += 1;
+    }
+
+    if in_count == 3 {
+        triangles.push(triangle.clone());
+    }
+}
+
+/// `model` moved by `transform` and cut down to the triangles inside every plane,
+/// `None` when its bounding sphere or box lies wholly outside one of them.
+/// The result already sits where `transform` puts it, so its own transform is the identity.
+fn transform_and_clip(
+    clipping_planes: &[Plane],
+    model: &Model,
+    transform: Matrix4,
+) -> Option<Model> {
+    let sphere = model.bounding_sphere.transform_matrix(&transform);
+    // the box turns with the model, so its corners are tighter than a box around them
+    let corners = model
+        .aabb
+        .corners()
+        .map(|corner| transform.transform_point(corner));
+
+    for p in clipping_planes {
+        let distance = |point: Vector3| p.normal.dot(point) + p.distance;
+        if distance(sphere.center) < -sphere.radius
+            || corners.iter().all(|&corner| distance(corner) < 0.)
+        {
+            return None;
+        }
+    }
+
+    let mut vertices: Vec<Vector3> = vec![];
+
+    for v in &model.vertices {
+        vertices.push(transform.transform_point(*v));
+    }
+
+    let transform_normal = |n: Vector3| transform_normal(&transform, n);
+
+    let mut triangles = model.triangles.clone();
+    for t in &mut triangles {
+        t.normals = t.normals.map(|normals| normals.map(transform_normal));
+    }
+
+    for p in clipping_planes {
+        let mut new_triangles = vec![];
+
+        for t in &triangles {
+            clip_triangle(t, p, &mut new_triangles, &vertices);
+        }
+
+        triangles = new_triangles;
+    }
+
+    Some(Model {
+        name: model.name,
+        vertices,
+        triangles,
+        transform: Transform::new(1., 0, Vector3::ZERO),
+        transform_matrix: Matrix4::identity(),
+        bounding_sphere: sphere,
+        aabb: Aabb::from_points(corners),
+        specular: model.specular,
+        normals: model
+            .normals
+            .iter()
+            .copied()
+            .map(transform_normal)
+            .collect(),
+        uvs: model.uvs.clone(),
+        colors: model.colors.clone(),
+        texture: model.texture.clone(),
+    })
+}
+
+/// `n` turned the way the affine `transform` turns the surface it is normal to: by the
+/// inverse transpose of the linear part, which keeps it normal under non uniform scales and
+/// shears. Worked out from the cofactors, the inverse transpose times the determinant, so
+/// a model flattened by a singular transform still gets the normal of its plane.
+fn transform_normal(transform: &Matrix4, n: Vector3) -> Vector3 {
+    let m = &transform.values;
+    let column = |j: usize| Vector3::new(m[0][j], m[1][j], m[2][j]);
+    let [x, y, z] = [column(0), column(1), column(2)];
+    let cofactors = y.cross(z) * n.x + z.cross(x) * n.y + x.cross(y) * n.z;
+    // mirrors have a negative determinant, which would turn the normal inside out
+    let determinant = x.dot(y.cross(z));
+    if determinant < 0. {
+        -cofactors.normalize()
+    } else {
+        cofactors.normalize()
+    }
+}
+
+/// Draws every instance and fills `aovs` with the triangles under it.
+fn render_scene(
+    target: &mut Target,
+    aovs: &mut AovBuffers,
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+) {
+    let camera_matrix = camera.orientation.transpose() * Matrix4::translation(-camera.position);
+    let lights: Vec<Light> = scene
+        .lights
+        .iter()
+        .map(|light| light.transform(&camera_matrix))
+        .collect();
+
+    for (index, i) in scene.instances.iter().enumerate() {
+        let transform = camera_matrix * i.transform_matrix;
+        let clipped = transform_and_clip(&camera.clipping_planes, i, transform);
+
+        if let Some(clipped) = clipped {
+            let id = index as u32 + 1;
+            render_instance(target, aovs, clipped, &lights, id, settings);
+        }
+    }
+}
+
+/// `instance` and `lights` are already in camera space, as `transform_and_clip` returns it.
+fn render_instance(
+    target: &mut Target,
+    aovs: &mut AovBuffers,
+    instance: Model,
+    lights: &[Light],
+    id: u32,
+    settings: &RenderSettings,
+) {
+    let mut projected = vec![];
+    let mut exact = vec![];
+
+    for v in &instance.vertices {
+        projected.push(project_vertex(*v, settings));
+        exact.push(project_vertex_exact(*v, settings));
+    }
+
+    for t in &instance.triangles {
+        let v0 = instance.vertices[t.vertex.0];
+        let v1 = instance.vertices[t.vertex.1];
+        let v2 = instance.vertices[t.vertex.2];
+
+        let normal = (v1 - v0).cross(v2 - v0).normalize();
+        let points = [
+            &projected[t.vertex.0],
+            &projected[t.vertex.1],
+            &projected[t.vertex.2],
+        ];
+
+        aovs.fill_triangle(points, [v0.z, v1.z, v2.z], normal, id);
+
+        let corners = [t.vertex.0, t.vertex.1, t.vertex.2];
+        let normals = instance.corner_normals(t).unwrap_or([normal; 3]);
+        let colors = corners.map(|i| {
+            let color = instance.colors.get(i).unwrap_or(&t.color);
+            Vector3::from(color.0.map(f32::from))
+        });
+
+        let inv_depths = [1. / v0.z, 1. / v1.z, 1. / v2.z];
+        let screen = ScreenTriangle {
+            points,
+            exact: corners.map(|i| exact[i]),
+            inv_depths,
+        };
+        let texture = instance.texture.as_deref().zip(instance.corner_uvs(t));
+        let uvs_over_z = match texture {
+            Some((_, uvs)) => {
+                [0, 1, 2].map(|k| Vector3::new(uvs[k].0, uvs[k].1, 0.) * inv_depths[k])
+            }
+            None => [Vector3::ZERO; 3],
+        };
+        let (uv_over_z_dx, uv_over_z_dy) = screen_gradients(points, uvs_over_z);
+        let (inv_depth_dx, inv_depth_dy) = screen_gradients(points, inv_depths);
+        let surface = |color, uv_over_z: Vector3, inv_depth| match texture {
+            // u/z and v/z interpolate linearly on screen, unlike u and v
+            Some((texture, _)) => {
+                let uv = uv_over_z / inv_depth;
+                // quotient rule on (u/z) / (1/z)
+                let duv_dx = (uv_over_z_dx - uv * inv_depth_dx) / inv_depth;
+                let duv_dy = (uv_over_z_dy - uv * inv_depth_dy) / inv_depth;
+                let color = texture.sample_footprint(
+                    (uv.x, uv.y),
+                    (duv_dx.x, duv_dx.y),
+                    (duv_dy.x, duv_dy.y),
+                );
+                Vector3::from(color.0.map(f32::from))
+            }
+            None => color,
+        };
+
+        let illumination =
+            |position, normal| compute_illumination(lights, position, normal, instance.specular);
+        match settings.shading {
+            ShadingMode::Wireframe => render_triangle(
+                &mut target.canvas,
+                t,
+                &projected,
+                &exact,
+                settings.line.as_ref(),
+            ),
+            ShadingMode::Flat => {
+                let intensity = illumination((v0 + v1 + v2) / 3., normal);
+                fill_triangle(
+                    target,
+                    settings,
+                    &screen,
+                    [0, 1, 2].map(|k| (colors[k], uvs_over_z[k])),
+                    |_, inv_depth, (color, uv_over_z)| {
+                        shade(surface(color, uv_over_z, inv_depth), intensity)
+                    },
+                );
+            }
+            ShadingMode::Gouraud => {
+                let attributes = [0, 1, 2].map(|k| {
+                    let position = instance.vertices[corners[k]];
+                    (colors[k], uvs_over_z[k], illumination(position, normals[k]))
+                });
+                fill_triangle(
+                    target,
+                    settings,
+                    &screen,
+                    attributes,
+                    |_, inv_depth, (color, uv_over_z, intensity)| {
+                        shade(surface(color, uv_over_z, inv_depth), intensity)
+                    },
+                );
+            }
+            ShadingMode::Phong => {
+                fill_triangle(
+                    target,
+                    settings,
+                    &screen,
+                    [0, 1, 2].map(|k| (colors[k], uvs_over_z[k], normals[k])),
+                    |point, inv_depth, (color, uv_over_z, normal)| {
+                        let position = unproject_point(point, inv_depth, settings);
+                        shade(
+                            surface(color, uv_over_z, inv_depth),
+                            illumination(position, normal),
+                        )
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// How much `values` at the corners of the triangle between `points` change per pixel
+/// along x and along y, for values that are affine on screen.
+fn screen_gradients<V: Varying>(points: [&Point; 3], values: [V; 3]) -> (V, V) {
+    let [p0, p1, p2] = points.map(|p| (p.x as f32, p.y as f32));
+    let (x1, y1) = (p1.0 - p0.0, p1.1 - p0.1);
+    let (x2, y2) = (p2.0 - p0.0, p2.1 - p0.1);
+    let det = x1 * y2 - x2 * y1;
+    let d1 = values[1].sub(values[0]);
+    let d2 = values[2].sub(values[0]);
+    if det == 0. {
+        return (d1.mul(0.), d1.mul(0.));
+    }
+
+    (
+        d1.mul(y2).sub(d2.mul(y1)).div(det),
+        d2.mul(x1).sub(d1.mul(x2)).div(det),
+    )
+}
+
+/// Camera space position of the canvas point `point` at depth 1/`inv_depth`,
+/// undoing `project_vertex`.
+fn unproject_point(point: Point, inv_depth: f32, settings: &RenderSettings) -> Vector3 {
+    let z = 1. / inv_depth;
+    let scale = VIEWPORT_SIZE / settings.width as f32 * z / PROJECTION_PLANE_Z;
+    Vector3::new(point.x as f32 * scale, point.y as f32 * scale, z)
+}
+
+/// `color`, with channels from 0 to 255, lit by `intensity` and saturating at white.
+fn shade(color: Vector3, intensity: f32) -> Rgb<u8> {
+    let [r, g, b]: [f32; 3] = (color * intensity).into();
+    Rgb([r, g, b].map(|c| c.round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antialias::DownsampleFilter;
+
+    #[test]
+    fn normals_stay_normal_under_any_transform() {
+        let mut shear = Matrix4::identity();
+        shear.values[0][1] = 0.7;
+        let transforms = [
+            Matrix4::scaling(Vector3::new(3., 0.5, 1.)),
+            Matrix4::scaling(Vector3::new(-1., 2., 1.)),
+            Matrix4::translation(Vector3::new(4., 5., 6.)) * Matrix4::rotation_y(0.8) * shear,
+        ];
+        // a surface point with two directions along the surface and its outward normal
+        let tangents = [Vector3::new(1., -1., 0.), Vector3::new(0., 0., 1.)];
+        let normal = Vector3::new(1., 1., 0.).normalize();
+
+        for transform in &transforms {
+            let moved = transform_normal(transform, normal);
+            assert!((moved.length() - 1.).abs() < 1e-5);
+            for tangent in tangents {
+                assert!(moved.dot(transform.transform_vector(tangent)).abs() < 1e-5);
+            }
+            // still on the side the transformed normal direction points to
+            assert!(moved.dot(transform.transform_vector(normal)) > 0.);
+        }
+    }
+
+    #[test]
+    fn flattened_models_get_the_normal_of_their_plane() {
+        let flatten = Matrix4::scaling(Vector3::new(1., 1., 0.));
+        let moved = transform_normal(&flatten, Vector3::new(0.6, 0., 0.8));
+        assert!((moved - Vector3::new(0., 0., 1.)).length() < 1e-5);
+    }
+
+    #[test]
+    fn images_too_large_to_index_are_rejected() {
+        let settings = |width, anti_aliasing| RenderSettings {
+            width,
+            height: width,
+            anti_aliasing,
+            ..RenderSettings::default()
+        };
+        let too_large = |settings: RenderSettings| {
+            matches!(settings.validate(), Err(Error::InvalidSettings { .. }))
+        };
+        let supersample = |factor| AntiAliasing::Supersample {
+            factor,
+            filter: DownsampleFilter::Box,
+        };
+
+        assert!(!too_large(settings(65_000, AntiAliasing::None)));
+        assert!(too_large(settings(66_000, AntiAliasing::None)));
+        assert!(too_large(settings(u32::MAX, AntiAliasing::None)));
+
+        assert!(!too_large(settings(16_000, AntiAliasing::Multisample(16))));
+        assert!(too_large(settings(17_000, AntiAliasing::Multisample(16))));
+
+        assert!(!too_large(settings(8_000, supersample(8))));
+        assert!(too_large(settings(9_000, supersample(8))));
+        assert!(too_large(settings(u32::MAX / 4, supersample(8))));
+    }
+}
@@ -0,0 +1,216 @@
+//! Plain text scenes, one camera, light or model instance per line.
+//! Every line starts with a keyword followed by properties, `#` starts a comment:
+//!
+//! ```text
+//! camera position -3 1 2 yaw 30
+//! light ambient 0.2
+//! light directional 0.2 -1 0 1
+//! light point 0.6 -3 2 -10
+//! cube scale 0.75 rotation 0 translate -1.5 0 5 specular 50
+//! cube scale 1 rotation 195 translate 1.25 2.5 7.5
+//! ```
+//!
+//! Lights take their intensity, then the direction towards directional lights or the
+//! position of point ones. Cubes are scaled, then turned around the y axis by whole
+//! degrees, then moved; `specular` is their Phong exponent, matte without it, and
+//! `smooth` rounds off edges sharper than that many degrees for Gouraud and Phong shading.
+//! `texture FILE` covers every face with an image, relative to the scene file, which
+//! `wrap repeat|clamp` and `filter nearest|bilinear|trilinear|anisotropic N` sample,
+//! the last two from mip maps with up to `N` samples per pixel for the anisotropic one.
+//! Without a `camera` line the camera sits at the origin looking down +z.
+
+use std::{
+    fs,
+    path::Path,
+    str::{FromStr, SplitWhitespace},
+    sync::Arc,
+};
+
+use crate::{
+    core::{Camera, Matrix4, Vector3},
+    error::Error,
+    light::{Light, LightType},
+    model::{Model, Transform},
+    render::Scene,
+    texture::{Filter, Texture, WrapMode},
+};
+
+/// Reads the scene at `path` along with the camera it is seen through.
+pub fn load(path: &Path) -> Result<(Scene, Camera), Error> {
+    let source = fs::read_to_string(path).map_err(Error::io(path))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut camera = Camera::with_frustum(Vector3::ZERO, Matrix4::identity());
+    let mut scene = Scene::default();
+    let mut light_lines = vec![];
+
+    for (line_number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+
+        let result = match words.next() {
+            None => continue,
+            Some("camera") => parse_camera(&mut words)
+                .map(|parsed| camera = parsed)
+                .map_err(LineError::from),
+            Some("light") => parse_light(&mut words)
+                .map(|light| {
+                    scene.lights.push(light);
+                    light_lines.push(line_number + 1);
+                })
+                .map_err(LineError::from),
+            Some("cube") => {
+                parse_cube(&mut words, directory).map(|cube| scene.instances.push(cube))
+            }
+            Some(keyword) => Err(format!("unknown keyword `{keyword}`").into()),
+        };
+        result.map_err(|error| match error {
+            LineError::Syntax(message) => Error::BadScene {
+                path: path.to_path_buf(),
+                line: line_number + 1,
+                message,
+            },
+            LineError::Load(error) => error,
+        })?;
+    }
+
+    // point a light the scene rejects at its line
+    scene.validate().map_err(|error| match error {
+        Error::InvalidLight { index, message } => Error::BadScene {
+            path: path.to_path_buf(),
+            line: light_lines[index],
+            message: message.to_string(),
+        },
+        error => error,
+    })?;
+    Ok((scene, camera))
+}
+
+/// What went wrong on a line: bad syntax, which still needs the file and line number,
+/// or a file it names that did not load.
+enum LineError {
+    Syntax(String),
+    Load(Error),
+}
+
+impl From<String> for LineError {
+    fn from(message: String) -> Self {
+        LineError::Syntax(message)
+    }
+}
+
+impl From<Error> for LineError {
+    fn from(error: Error) -> Self {
+        LineError::Load(error)
+    }
+}
+
+fn parse_camera(words: &mut SplitWhitespace) -> Result<Camera, String> {
+    let mut position = Vector3::ZERO;
+    let mut yaw: f32 = 0.;
+
+    while let Some(key) = words.next() {
+        match key {
+            "position" => position = vector(words, "camera position")?,
+            "yaw" => yaw = number(words, "camera yaw")?,
+            _ => return Err(format!("unknown camera property `{key}`")),
+        }
+    }
+
+    Ok(Camera::with_frustum(
+        position,
+        Matrix4::rotation_y(yaw.to_radians()),
+    ))
+}
+
+fn parse_light(words: &mut SplitWhitespace) -> Result<Light, String> {
+    let light_type = match words.next() {
+        Some("ambient") => LightType::Ambient,
+        Some("point") => LightType::Point,
+        Some("directional") => LightType::Directional,
+        Some(other) => return Err(format!("unknown light type `{other}`")),
+        None => return Err("missing light type".to_string()),
+    };
+    let intensity = number(words, "light intensity")?;
+    let direction = match light_type {
+        LightType::Ambient => None,
+        LightType::Point => Some(vector(words, "light position")?),
+        LightType::Directional => Some(vector(words, "light direction")?),
+    };
+    if let Some(word) = words.next() {
+        return Err(format!("unexpected `{word}` after the light"));
+    }
+
+    Ok(Light {
+        intensity,
+        light_type,
+        direction,
+    })
+}
+
+/// Texture paths are relative to `directory`, the one of the scene file.
+fn parse_cube(words: &mut SplitWhitespace, directory: &Path) -> Result<Model, LineError> {
+    let mut transform = Transform::new(1., 0, Vector3::ZERO);
+    let mut specular = -1;
+    let mut smooth = None;
+    let mut texture = None;
+    let mut wrap = WrapMode::default();
+    let mut filter = Filter::default();
+
+    while let Some(key) = words.next() {
+        match key {
+            "scale" => transform.scale = number(words, "scale")?,
+            "rotation" => transform.rotation = number(words, "rotation")?,
+            "translate" => transform.translation = vector(words, "offset")?,
+            "specular" => specular = number(words, "specular exponent")?,
+            "smooth" => smooth = Some(number(words, "smoothing angle")?),
+            "texture" => texture = Some(words.next().ok_or("missing texture path".to_string())?),
+            "wrap" => {
+                wrap = match words.next() {
+                    Some("repeat") => WrapMode::Repeat,
+                    Some("clamp") => WrapMode::Clamp,
+                    Some(other) => return Err(format!("unknown wrap mode `{other}`").into()),
+                    None => return Err("missing wrap mode".to_string().into()),
+                }
+            }
+            "filter" => {
+                filter = match words.next() {
+                    Some("nearest") => Filter::Nearest,
+                    Some("bilinear") => Filter::Bilinear,
+                    Some("trilinear") => Filter::Trilinear,
+                    Some("anisotropic") => Filter::Anisotropic(number(words, "sample count")?),
+                    Some(other) => return Err(format!("unknown filter `{other}`").into()),
+                    None => return Err("missing filter".to_string().into()),
+                }
+            }
+            _ => return Err(format!("unknown cube property `{key}`").into()),
+        }
+    }
+
+    let mut cube = Model {
+        specular,
+        ..Model::cube(transform)
+    };
+    if let Some(max_angle) = smooth {
+        cube.smooth_normals(max_angle);
+    }
+    if let Some(name) = texture {
+        let texture = Texture::load(&directory.join(name), wrap, filter)?;
+        cube.texture = Some(Arc::new(texture));
+    }
+    Ok(cube)
+}
+
+fn number<T: FromStr>(words: &mut SplitWhitespace, what: &str) -> Result<T, String> {
+    let word = words.next().ok_or_else(|| format!("missing {what}"))?;
+    word.parse()
+        .map_err(|_| format!("expected a number for {what}, found `{word}`"))
+}
+
+fn vector(words: &mut SplitWhitespace, what: &str) -> Result<Vector3, String> {
+    Ok(Vector3::new(
+        number(words, what)?,
+        number(words, what)?,
+        number(words, what)?,
+    ))
+}
@@ -0,0 +1,327 @@
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// How texel coordinates outside of the image are mapped back into it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WrapMode {
+    /// Tiles the image.
+    #[default]
+    Repeat,
+    /// Stretches the edge texels.
+    Clamp,
+}
+
+impl WrapMode {
+    fn apply(self, i: i32, size: i32) -> i32 {
+        match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+        }
+    }
+}
+
+/// How the texels around a (u, v) coordinate become its colour.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    /// The closest texel of the full size image, blocky up close.
+    Nearest,
+    /// The four closest texels of the full size image, weighted by distance.
+    #[default]
+    Bilinear,
+    /// Bilinear in the two mip levels closest to the size of the pixel on the texture,
+    /// blended by how close each one is.
+    Trilinear,
+    /// Up to this many trilinear samples along the longer side of the pixel on the texture,
+    /// each from the mip level of its shorter side, so surfaces seen edge on stay sharp.
+    Anisotropic(u32),
+}
+
+/// Image looked up by the (u, v) coordinates of a surface, v growing upwards,
+/// along with its mip chain: every level half the size of the one before, down to 1x1.
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub wrap: WrapMode,
+    pub filter: Filter,
+    /// The full size image first.
+    levels: Vec<RgbImage>,
+}
+
+impl Texture {
+    /// Fails for an image without pixels.
+    pub fn new(image: RgbImage, wrap: WrapMode, filter: Filter) -> Result<Self> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::EmptyImage { path: None });
+        }
+
+        let mut levels = vec![image];
+        while let Some(level) = levels.last().and_then(downsample) {
+            levels.push(level);
+        }
+
+        Ok(Self {
+            wrap,
+            filter,
+            levels,
+        })
+    }
+
+    pub fn load(path: &Path, wrap: WrapMode, filter: Filter) -> Result<Self> {
+        let image = image::open(path).map_err(Error::image(path))?.to_rgb8();
+        Self::new(image, wrap, filter).map_err(|error| match error {
+            Error::EmptyImage { .. } => Error::EmptyImage {
+                path: Some(path.to_path_buf()),
+            },
+            error => error,
+        })
+    }
+
+    /// The full size image.
+    pub fn image(&self) -> &RgbImage {
+        &self.levels[0]
+    }
+
+    /// Colour at `uv` without a footprint, from the full size image whatever the filter.
+    pub fn sample(&self, uv: (f32, f32)) -> Rgb<u8> {
+        let color = match self.filter {
+            Filter::Nearest => self.nearest(0, uv),
+            _ => self.bilinear(0, uv),
+        };
+        to_rgb(color)
+    }
+
+    /// Colour at `uv` for a pixel whose neighbours to the right and below sit
+    /// `duv_dx` and `duv_dy` away on the texture, which picks the mip level.
+    pub fn sample_footprint(
+        &self,
+        uv: (f32, f32),
+        duv_dx: (f32, f32),
+        duv_dy: (f32, f32),
+    ) -> Rgb<u8> {
+        let (width, height) = self.image().dimensions();
+        let texels = |(du, dv): (f32, f32)| (du * width as f32).hypot(dv * height as f32);
+        let (x_length, y_length) = (texels(duv_dx), texels(duv_dy));
+
+        let color = match self.filter {
+            Filter::Nearest => self.nearest(0, uv),
+            Filter::Bilinear => self.bilinear(0, uv),
+            Filter::Trilinear => self.trilinear(uv, x_length.max(y_length).log2()),
+            Filter::Anisotropic(max_samples) => {
+                let (major, minor, axis) = if x_length > y_length {
+                    (x_length, y_length, duv_dx)
+                } else {
+                    (y_length, x_length, duv_dy)
+                };
+                let samples = (major / minor.max(f32::MIN_POSITIVE))
+                    .ceil()
+                    .clamp(1., max_samples.max(1) as f32);
+                let lod = (major / samples).log2();
+
+                let mut sum = [0.; 3];
+                let count = samples as u32;
+                for i in 0..count {
+                    // spread evenly over the footprint, centred on `uv`
+                    let t = (i as f32 + 0.5) / samples - 0.5;
+                    let color = self.trilinear((uv.0 + axis.0 * t, uv.1 + axis.1 * t), lod);
+                    for (sum, c) in sum.iter_mut().zip(color) {
+                        *sum += c;
+                    }
+                }
+                sum.map(|c| c / samples)
+            }
+        };
+        to_rgb(color)
+    }
+
+    fn trilinear(&self, uv: (f32, f32), lod: f32) -> [f32; 3] {
+        let lod = lod.clamp(0., (self.levels.len() - 1) as f32);
+        let level = lod.floor() as usize;
+        let t = lod - level as f32;
+
+        let near = self.bilinear(level, uv);
+        if t == 0. {
+            return near;
+        }
+        mix(near, self.bilinear(level + 1, uv), t)
+    }
+
+    /// Texel coordinates of `uv` in `level`, texel centres sitting at half coordinates.
+    fn texel_position(&self, level: usize, uv: (f32, f32)) -> (f32, f32) {
+        let (width, height) = self.levels[level].dimensions();
+        (uv.0 * width as f32 - 0.5, (1. - uv.1) * height as f32 - 0.5)
+    }
+
+    fn texel(&self, level: usize, x: i32, y: i32) -> [f32; 3] {
+        let image = &self.levels[level];
+        let x = self.wrap.apply(x, image.width() as i32);
+        let y = self.wrap.apply(y, image.height() as i32);
+        image.get_pixel(x as u32, y as u32).0.map(f32::from)
+    }
+
+    fn nearest(&self, level: usize, uv: (f32, f32)) -> [f32; 3] {
+        let (x, y) = self.texel_position(level, uv);
+        self.texel(level, x.round() as i32, y.round() as i32)
+    }
+
+    fn bilinear(&self, level: usize, uv: (f32, f32)) -> [f32; 3] {
+        let (x, y) = self.texel_position(level, uv);
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let top = mix(self.texel(level, x0, y0), self.texel(level, x0 + 1, y0), fx);
+        let bottom = mix(
+            self.texel(level, x0, y0 + 1),
+            self.texel(level, x0 + 1, y0 + 1),
+            fx,
+        );
+        mix(top, bottom, fy)
+    }
+}
+
+/// Next mip level: every texel the average of the 2x2 block above it,
+/// `None` once `image` is down to 1x1.
+fn downsample(image: &RgbImage) -> Option<RgbImage> {
+    let (width, height) = image.dimensions();
+    if width == 1 && height == 1 {
+        return None;
+    }
+
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+    Some(RgbImage::from_fn(half_width, half_height, |x, y| {
+        // odd sizes leave the last row or column out of the blocks, clamping keeps 1 wide images
+        let xs = [2 * x, (2 * x + 1).min(width - 1)];
+        let ys = [2 * y, (2 * y + 1).min(height - 1)];
+
+        let mut sum = [0.; 3];
+        for y in ys {
+            for x in xs {
+                for (sum, c) in sum.iter_mut().zip(image.get_pixel(x, y).0) {
+                    *sum += f32::from(c);
+                }
+            }
+        }
+        to_rgb(sum.map(|c| c / 4.))
+    }))
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| a[i] * (1. - t) + b[i] * t)
+}
+
+fn to_rgb(color: [f32; 3]) -> Rgb<u8> {
+    Rgb(color.map(|c| c.round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `width` by `height` texels whose colour is the texel column times 10 and row times 10.
+    fn ramp(width: u32, height: u32, wrap: WrapMode, filter: Filter) -> Texture {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([x as u8 * 10, y as u8 * 10, 0]));
+        Texture::new(image, wrap, filter).unwrap()
+    }
+
+    #[test]
+    fn v_grows_upwards() {
+        let texture = ramp(4, 4, WrapMode::Clamp, Filter::Nearest);
+        // the centres of the top left and bottom right texels
+        assert_eq!(texture.sample((0.125, 0.875)), Rgb([0, 0, 0]));
+        assert_eq!(texture.sample((0.875, 0.125)), Rgb([30, 30, 0]));
+    }
+
+    #[test]
+    fn repeat_tiles_the_image() {
+        let texture = ramp(4, 4, WrapMode::Repeat, Filter::Nearest);
+        for (u, v) in [(0.375, 0.625), (0.125, 0.125)] {
+            let inside = texture.sample((u, v));
+            assert_eq!(texture.sample((u + 1., v)), inside);
+            assert_eq!(texture.sample((u - 3., v + 2.)), inside);
+        }
+        // half a texel past the right edge blends the last column with the first
+        let texture = ramp(4, 4, WrapMode::Repeat, Filter::Bilinear);
+        assert_eq!(texture.sample((1., 0.875)), Rgb([15, 0, 0]));
+    }
+
+    #[test]
+    fn clamp_stretches_the_edges() {
+        let texture = ramp(4, 4, WrapMode::Clamp, Filter::Bilinear);
+        assert_eq!(texture.sample((-3., 0.875)), Rgb([0, 0, 0]));
+        assert_eq!(texture.sample((5., -2.)), Rgb([30, 30, 0]));
+        assert_eq!(texture.sample((1., 0.875)), Rgb([30, 0, 0]));
+    }
+
+    #[test]
+    fn bilinear_blends_neighbouring_texels() {
+        let texture = ramp(4, 4, WrapMode::Clamp, Filter::Bilinear);
+        // on the corner between the four top left texels
+        assert_eq!(texture.sample((0.25, 0.75)), Rgb([5, 5, 0]));
+        // a quarter of the way from one texel centre to the next
+        assert_eq!(texture.sample((0.1875, 0.875)), Rgb([3, 0, 0]));
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        let result = Texture::new(RgbImage::new(0, 3), WrapMode::Repeat, Filter::Bilinear);
+        assert!(matches!(result, Err(Error::EmptyImage { path: None })));
+    }
+
+    /// 8x8 texels in rows alternating between white, at the top, and black.
+    fn stripes(filter: Filter) -> Texture {
+        let image = RgbImage::from_fn(8, 8, |_, y| Rgb([if y % 2 == 0 { 255 } else { 0 }; 3]));
+        Texture::new(image, WrapMode::Repeat, filter).unwrap()
+    }
+
+    /// Footprint of `u_texels` by `v_texels` texels on the 8x8 stripes.
+    fn footprint(filter: Filter, u_texels: f32, v_texels: f32) -> u8 {
+        // the centre of the top left texel, which is white
+        let uv = (1. / 16., 15. / 16.);
+        let color = stripes(filter).sample_footprint(uv, (u_texels / 8., 0.), (0., v_texels / 8.));
+        color.0[0]
+    }
+
+    #[test]
+    fn mip_chain_halves_down_to_one_texel() {
+        let image = RgbImage::from_pixel(5, 3, Rgb([10, 20, 30]));
+        let texture = Texture::new(image, WrapMode::Repeat, Filter::Trilinear).unwrap();
+        let sizes: Vec<_> = texture.levels.iter().map(RgbImage::dimensions).collect();
+        assert_eq!(sizes, [(5, 3), (2, 1), (1, 1)]);
+        assert!(texture
+            .levels
+            .iter()
+            .flat_map(|level| level.pixels())
+            .all(|&p| p == Rgb([10, 20, 30])));
+
+        // every level averages the one above
+        let texture = stripes(Filter::Trilinear);
+        assert_eq!(texture.levels.len(), 4);
+        assert!(texture.levels[1].pixels().all(|&p| p == Rgb([128; 3])));
+    }
+
+    #[test]
+    fn trilinear_picks_the_level_of_the_footprint() {
+        // a texel or less per pixel reads the full size image
+        assert_eq!(footprint(Filter::Trilinear, 0.25, 0.25), 255);
+        assert_eq!(footprint(Filter::Trilinear, 1., 1.), 255);
+        // two texels per pixel read the first mip level, where the rows have averaged out
+        assert_eq!(footprint(Filter::Trilinear, 2., 2.), 128);
+        // in between blends the two, and the longer side of the footprint counts
+        assert_eq!(footprint(Filter::Trilinear, 1., 2f32.sqrt()), 192);
+        // beyond the smallest level stays on it
+        assert_eq!(footprint(Filter::Trilinear, 1000., 1000.), 128);
+    }
+
+    #[test]
+    fn anisotropic_keeps_long_footprints_sharp() {
+        // four texels along the stripes, one across them
+        assert_eq!(footprint(Filter::Trilinear, 4., 1.), 128);
+        assert_eq!(footprint(Filter::Anisotropic(8), 4., 1.), 255);
+        // too few samples for the length fall back to coarser levels
+        assert!(footprint(Filter::Anisotropic(2), 4., 1.) < 255);
+        // bilinear ignores the footprint altogether
+        assert_eq!(footprint(Filter::Bilinear, 4., 4.), 255);
+    }
+}
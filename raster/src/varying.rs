@@ -0,0 +1,306 @@
+//! Values that vary across a primitive, like depth, colours, normals or texture
+//! coordinates, and the stepping that carries any number of them along edges and spans
+//! without collecting them first.
+
+use crate::{
+    clip::{clip_triangle, ClipRect},
+    core::{Point, Vector3},
+};
+
+/// Anything interpolated linearly between vertices. Tuples and arrays of varyings
+/// are varyings too, so several attributes travel through the rasterizer as one.
+pub trait Varying: Copy {
+    fn add(self, other: Self) -> Self;
+    fn sub(self, other: Self) -> Self;
+    fn mul(self, k: f32) -> Self;
+    fn div(self, k: f32) -> Self;
+}
+
+impl Varying for f32 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn mul(self, k: f32) -> Self {
+        self * k
+    }
+
+    fn div(self, k: f32) -> Self {
+        self / k
+    }
+}
+
+impl Varying for Vector3 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn mul(self, k: f32) -> Self {
+        self * k
+    }
+
+    fn div(self, k: f32) -> Self {
+        self / k
+    }
+}
+
+impl<V: Varying, const N: usize> Varying for [V; N] {
+    fn add(self, other: Self) -> Self {
+        let mut res = self;
+        for (a, b) in res.iter_mut().zip(other) {
+            *a = a.add(b);
+        }
+        res
+    }
+
+    fn sub(self, other: Self) -> Self {
+        let mut res = self;
+        for (a, b) in res.iter_mut().zip(other) {
+            *a = a.sub(b);
+        }
+        res
+    }
+
+    fn mul(self, k: f32) -> Self {
+        self.map(|a| a.mul(k))
+    }
+
+    fn div(self, k: f32) -> Self {
+        self.map(|a| a.div(k))
+    }
+}
+
+macro_rules! impl_varying_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Varying),+> Varying for ($($name,)+) {
+            fn add(self, other: Self) -> Self {
+                ($(self.$index.add(other.$index),)+)
+            }
+
+            fn sub(self, other: Self) -> Self {
+                ($(self.$index.sub(other.$index),)+)
+            }
+
+            fn mul(self, k: f32) -> Self {
+                ($(self.$index.mul(k),)+)
+            }
+
+            fn div(self, k: f32) -> Self {
+                ($(self.$index.div(k),)+)
+            }
+        }
+    };
+}
+
+impl_varying_tuple!(A 0);
+impl_varying_tuple!(A 0, B 1);
+impl_varying_tuple!(A 0, B 1, C 2);
+impl_varying_tuple!(A 0, B 1, C 2, D 3);
+
+/// Values going from `d0` at `i0` to `d1` at `i1` in even steps, one per whole `i`,
+/// both ends included. A single `d0` when `i0 == i1`, nothing when `i1 < i0`.
+#[derive(Clone, Debug)]
+pub struct Interpolation<V> {
+    value: V,
+    step: V,
+    remaining: u32,
+}
+
+pub fn interpolate<V: Varying>(i0: i32, d0: V, i1: i32, d1: V) -> Interpolation<V> {
+    if i0 == i1 {
+        return Interpolation {
+            value: d0,
+            step: d0.sub(d0),
+            remaining: 1,
+        };
+    }
+
+    Interpolation {
+        value: d0,
+        step: d1.sub(d0).div((i1 - i0) as f32),
+        remaining: (i1 - i0 + 1).max(0) as u32,
+    }
+}
+
+impl<V: Varying> Iterator for Interpolation<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let value = self.value;
+        self.value = self.value.add(self.step);
+        self.remaining -= 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+/// `values` weighted by the barycentric coordinates `weights`.
+pub fn barycentric<V: Varying>(values: [V; 3], weights: [f32; 3]) -> V {
+    values[0]
+        .mul(weights[0])
+        .add(values[1].mul(weights[1]))
+        .add(values[2].mul(weights[2]))
+}
+
+/// Walks the rows of the part of the triangle between `points` inside `bounds` from the
+/// top, calling `span` with the y of every row and its left and right ends: their x and
+/// `values` interpolated there. Vertices on the same row stay in the order of `points`.
+/// Triangles reaching out of `bounds` are clipped to it first, then walked piece by piece.
+pub fn triangle_spans<V: Varying>(
+    points: [&Point; 3],
+    values: [V; 3],
+    bounds: &ClipRect,
+    mut span: impl FnMut(i32, (i32, V), (i32, V)),
+) {
+    let corners = [0, 1, 2].map(|i| ((points[i].x as f32, points[i].y as f32), values[i]));
+    for piece in clip_triangle(corners, bounds) {
+        let points = piece.map(|((x, y), _)| Point::new(x.round() as i32, y.round() as i32));
+        let [p0, p1, p2] = &points;
+        walk_spans([p0, p1, p2], piece.map(|(_, value)| value), &mut span);
+    }
+}
+
+fn walk_spans<V: Varying>(
+    points: [&Point; 3],
+    values: [V; 3],
+    span: &mut impl FnMut(i32, (i32, V), (i32, V)),
+) {
+    let mut order = [0, 1, 2];
+    order.sort_by_key(|&i| points[i].y);
+    let [p0, p1, p2] = order.map(|i| points[i]);
+    let [v0, v1, v2] = order.map(|i| values[i]);
+
+    let edge = |a: &Point, va: V, b: &Point, vb: V| {
+        interpolate(a.y, (a.x as f32, va), b.y, (b.x as f32, vb))
+    };
+    // the row of p1 belongs to the lower part of the short side
+    let short = edge(p0, v0, p1, v1)
+        .take((p1.y - p0.y) as usize)
+        .chain(edge(p1, v1, p2, v2));
+    let long = edge(p0, v0, p2, v2);
+
+    // which side is left, looking at the middle row
+    let middle = ((p2.y - p0.y + 1) / 2) as usize;
+    let round_x = |(x, _): (f32, V)| x.round() as i32;
+    let long_is_left =
+        long.clone().nth(middle).map(round_x) < short.clone().nth(middle).map(round_x);
+
+    for (y, (a, b)) in (p0.y..=p2.y).zip(long.zip(short)) {
+        let (left, right) = if long_is_left { (a, b) } else { (b, a) };
+        span(
+            y,
+            (left.0.round() as i32, left.1),
+            (right.0.round() as i32, right.1),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(size: f32) -> ClipRect {
+        ClipRect {
+            min_x: -size,
+            min_y: -size,
+            max_x: size,
+            max_y: size,
+        }
+    }
+
+    #[test]
+    fn interpolation_includes_both_ends() {
+        let values: Vec<f32> = interpolate(2, 10., 6, 30.).collect();
+        assert_eq!(values, [10., 15., 20., 25., 30.]);
+
+        let down: Vec<f32> = interpolate(-1, 4., 1, -4.).collect();
+        assert_eq!(down, [4., 0., -4.]);
+    }
+
+    #[test]
+    fn degenerate_ranges() {
+        let single: Vec<f32> = interpolate(3, 7., 3, 100.).collect();
+        assert_eq!(single, [7.]);
+
+        assert_eq!(interpolate(3, 7., 2, 100.).count(), 0);
+        assert_eq!(interpolate(0, 0., 4, 1.).size_hint(), (5, Some(5)));
+    }
+
+    #[test]
+    fn tuples_and_arrays_step_every_component() {
+        let start = (0., [1., 2.], Vector3::new(0., 0., 4.));
+        let end = (4., [5., -2.], Vector3::new(8., 0., 0.));
+        let values: Vec<_> = interpolate(0, start, 4, end).collect();
+
+        assert_eq!(values.len(), 5);
+        let (a, [b, c], v) = values[1];
+        assert_eq!((a, b, c), (1., 2., 1.));
+        assert_eq!((v.x, v.y, v.z), (2., 0., 3.));
+        let (a, [b, c], v) = values[4];
+        assert_eq!((a, b, c), (4., 5., -2.));
+        assert_eq!((v.x, v.y, v.z), (8., 0., 0.));
+    }
+
+    #[test]
+    fn barycentric_weights_pick_the_vertices() {
+        let values = [1., 10., 100.];
+        assert_eq!(barycentric(values, [1., 0., 0.]), 1.);
+        assert_eq!(barycentric(values, [0., 0., 1.]), 100.);
+        assert_eq!(barycentric(values, [0.5, 0.5, 0.]), 5.5);
+    }
+
+    #[test]
+    fn spans_cover_every_row_with_the_vertex_values() {
+        let (a, b, c) = (Point::new(0, 0), Point::new(8, 4), Point::new(0, 8));
+        let mut rows = vec![];
+        triangle_spans(
+            [&a, &b, &c],
+            [0., 4., 8.],
+            &bounds(100.),
+            |y, left, right| rows.push((y, left, right)),
+        );
+
+        assert_eq!(rows.len(), 9);
+        assert_eq!(
+            rows.iter().map(|row| row.0).collect::<Vec<_>>(),
+            (0..=8).collect::<Vec<_>>()
+        );
+        // the vertical edge is on the left and carries the values of its ends
+        assert_eq!(rows[0], (0, (0, 0.), (0, 0.)));
+        assert_eq!(rows[4], (4, (0, 4.), (8, 4.)));
+        assert_eq!(rows[8], (8, (0, 8.), (0, 8.)));
+        for (y, left, right) in rows {
+            assert!(left.0 <= right.0, "row {y} is reversed");
+        }
+    }
+
+    #[test]
+    fn spans_stay_inside_the_bounds() {
+        let (a, b, c) = (Point::new(-20, -20), Point::new(20, -20), Point::new(0, 20));
+        let mut rows = 0;
+        triangle_spans([&a, &b, &c], [0., 0., 0.], &bounds(5.), |y, left, right| {
+            rows += 1;
+            assert!((-5..=5).contains(&y));
+            assert!(
+                -5 <= left.0 && right.0 <= 5,
+                "row {y} spans {left:?} to {right:?}"
+            );
+        });
+        assert!(rows > 0);
+    }
+}
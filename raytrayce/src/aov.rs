@@ -0,0 +1,184 @@
+use image::{DynamicImage, Rgb, Rgb32FImage, RgbImage};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::{Error, Result},
+    to_f32, to_rgb, Color, Float, Vector3,
+};
+
+/// Arbitrary output variables of one sample, taken at the first surface the camera ray hits.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AovSample {
+    /// Distance of the hit along the camera view axis.
+    pub depth: Float,
+    /// World space normal.
+    pub normal: Vector3,
+    pub albedo: Color,
+    /// Index of the object in the scene plus one, 0 when the ray left the scene.
+    /// Every object carries its own material so this is the material id as well.
+    pub object_id: u32,
+    /// Light that took one bounce from a light or the environment to the camera,
+    /// the environment itself where nothing was hit.
+    pub direct: Color,
+    /// Everything else: ambient, diffuse interreflection and mirror reflections.
+    pub indirect: Color,
+}
+
+/// Sums of the AOV samples of one pixel.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct AovPixel {
+    depth: Float,
+    /// Samples that hit something, depth and normal are averaged over those only.
+    hits: u32,
+    normal: Vector3,
+    albedo: Color,
+    /// Taken from the first sample, ids can not be averaged.
+    object_id: u32,
+    direct: Color,
+    indirect: Color,
+}
+
+impl AovPixel {
+    pub const WORDS: usize = 15;
+
+    pub fn add(&mut self, sample: &AovSample, first: bool) {
+        if first {
+            self.object_id = sample.object_id;
+        }
+        if sample.object_id != 0 {
+            self.depth += sample.depth;
+            self.normal += sample.normal;
+            self.hits += 1;
+        }
+        self.albedo += sample.albedo;
+        self.direct += sample.direct;
+        self.indirect += sample.indirect;
+    }
+
+    /// Raw bits of every field, for checkpoints.
+    pub fn to_words(self) -> [u32; Self::WORDS] {
+        let f = |value: Float| to_f32(value).to_bits();
+        [
+            f(self.depth),
+            self.hits,
+            f(self.normal.x),
+            f(self.normal.y),
+            f(self.normal.z),
+            f(self.albedo.r),
+            f(self.albedo.g),
+            f(self.albedo.b),
+            self.object_id,
+            f(self.direct.r),
+            f(self.direct.g),
+            f(self.direct.b),
+            f(self.indirect.r),
+            f(self.indirect.g),
+            f(self.indirect.b),
+        ]
+    }
+
+    pub fn from_words(words: [u32; Self::WORDS]) -> Self {
+        let f = |i: usize| Float::from(f32::from_bits(words[i]));
+        Self {
+            depth: f(0),
+            hits: words[1],
+            normal: Vector3::new(f(2), f(3), f(4)),
+            albedo: Color::new(f(5), f(6), f(7)),
+            object_id: words[8],
+            direct: Color::new(f(9), f(10), f(11)),
+            indirect: Color::new(f(12), f(13), f(14)),
+        }
+    }
+}
+
+/// Writes every AOV next to the beauty image at `path`:
+/// `name.depth.exr`, `name.normal.exr`, `name.albedo.exr`, `name.direct.exr`,
+/// `name.indirect.exr`, raw ids in `name.id.exr` and a false colour `name.id.png`.
+/// Depth is infinite where no sample hit anything.
+pub(crate) fn save_aovs(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[AovPixel],
+    samples: &[u32],
+) -> Result<()> {
+    let float_image = |value: &dyn Fn(&AovPixel, Float) -> [Float; 3]| {
+        Rgb32FImage::from_fn(width, height, |x, y| {
+            let index = (y * width + x) as usize;
+            Rgb(value(&pixels[index], samples[index].max(1) as Float).map(to_f32))
+        })
+    };
+
+    let depth = float_image(&|pixel, _| {
+        let depth = if pixel.hits > 0 {
+            pixel.depth / pixel.hits as Float
+        } else {
+            Float::INFINITY
+        };
+        [depth; 3]
+    });
+
+    let normal = float_image(&|pixel, _| {
+        let normal_length = pixel.normal.length();
+        if normal_length > 0. {
+            (pixel.normal / normal_length).into()
+        } else {
+            [0.; 3]
+        }
+    });
+
+    let albedo = float_image(&|pixel, samples| (pixel.albedo / samples).into());
+    let direct = float_image(&|pixel, samples| (pixel.direct / samples).into());
+    let indirect = float_image(&|pixel, samples| (pixel.indirect / samples).into());
+
+    let id = float_image(&|pixel, _| {
+        let id = pixel.object_id as Float;
+        [id; 3]
+    });
+    let id_colors = RgbImage::from_fn(width, height, |x, y| {
+        id_color(pixels[(y * width + x) as usize].object_id)
+    });
+
+    let images = [
+        ("depth", "exr", DynamicImage::from(depth)),
+        ("normal", "exr", normal.into()),
+        ("albedo", "exr", albedo.into()),
+        ("direct", "exr", direct.into()),
+        ("indirect", "exr", indirect.into()),
+        ("id", "exr", id.into()),
+        ("id", "png", id_colors.into()),
+    ];
+    for (name, extension, image) in images {
+        let path = aov_path(path, name, extension);
+        image.save(&path).map_err(Error::image(&path))?;
+    }
+
+    Ok(())
+}
+
+fn aov_path(path: &Path, name: &str, extension: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{name}.{extension}"))
+}
+
+/// Distinct, stable colour per id so neighbouring objects are easy to tell apart.
+/// The rasterizer uses the same palette, so one id looks alike in both.
+fn id_color(id: u32) -> Rgb<u8> {
+    if id == 0 {
+        return Rgb([0, 0, 0]);
+    }
+
+    // golden ratio steps around the hue circle
+    let hue = (id as Float * 0.618_034).fract() * 6.;
+    let x = 1. - (hue % 2. - 1.).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1., x, 0.),
+        1 => (x, 1., 0.),
+        2 => (0., 1., x),
+        3 => (0., x, 1.),
+        4 => (x, 0., 1.),
+        _ => (1., 0., x),
+    };
+
+    to_rgb(Color::new(r, g, b) * 0.8 + Color::new(0.2, 0.2, 0.2))
+}
@@ -0,0 +1,219 @@
+use crate::{stats::record_bvh_visits, Aabb, Float, Ray, Vector3};
+
+#[derive(Clone, Debug)]
+enum BvhNode {
+    Leaf {
+        first: usize,
+        count: usize,
+    },
+    /// The left child holds the boxes with the smaller centers along `axis`.
+    Inner {
+        left: usize,
+        right: usize,
+        axis: usize,
+    },
+}
+
+/// Bounding volume hierarchy over a list of boxes, split at the median
+/// of the box centers along the longest axis.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    bounds: Vec<Aabb>,
+    nodes: Vec<BvhNode>,
+    /// Indices of the original boxes, leaves own a contiguous range.
+    indices: Vec<usize>,
+}
+
+const LEAF_SIZE: usize = 4;
+
+impl Bvh {
+    pub fn build(boxes: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            bounds: vec![],
+            nodes: vec![],
+            indices: (0..boxes.len()).collect(),
+        };
+
+        if !boxes.is_empty() {
+            bvh.build_node(boxes, 0, boxes.len());
+        }
+
+        bvh
+    }
+
+    /// Bounds of everything in the hierarchy.
+    pub fn bounds(&self) -> Aabb {
+        self.bounds.first().copied().unwrap_or(Aabb::EMPTY)
+    }
+
+    fn build_node(&mut self, boxes: &[Aabb], first: usize, count: usize) -> usize {
+        let items = &mut self.indices[first..first + count];
+        let bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |bounds, &i| bounds.union(&boxes[i]));
+
+        let node = self.nodes.len();
+        self.bounds.push(bounds);
+        self.nodes.push(BvhNode::Leaf { first, count });
+
+        if count <= LEAF_SIZE {
+            return node;
+        }
+
+        let centroids = Aabb::from_points(items.iter().map(|&i| boxes[i].centroid()));
+        let axis = centroids.extent().max_axis();
+        let key = |i: usize| boxes[i].centroid()[axis];
+
+        let half = count / 2;
+        items.select_nth_unstable_by(half, |&a, &b| key(a).total_cmp(&key(b)));
+
+        let left = self.build_node(boxes, first, half);
+        let right = self.build_node(boxes, first + half, count - half);
+        self.nodes[node] = BvhNode::Inner { left, right, axis };
+
+        node
+    }
+
+    /// Calls `hit` with every box index the ray may hit, nearer nodes first.
+    /// `hit` gets the current `t_max` and returns the new one when it found something closer.
+    pub fn traverse(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        mut t_max: Float,
+        mut hit: impl FnMut(usize, Float) -> Option<Float>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let direction = ray.direction;
+        let inv_direction = Vector3::new(1. / direction.x, 1. / direction.y, 1. / direction.z);
+        let negative = [direction.x < 0., direction.y < 0., direction.z < 0.];
+        // median splits keep the depth near log2 of the box count
+        let mut stack = [0; 64];
+        let mut stack_size = 1;
+        let mut visits = 0;
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node = stack[stack_size];
+            visits += 1;
+
+            if !self.bounds[node].hit(ray.origin, inv_direction, t_min, t_max) {
+                continue;
+            }
+
+            match self.nodes[node] {
+                BvhNode::Leaf { first, count } => {
+                    for &index in &self.indices[first..first + count] {
+                        if let Some(t) = hit(index, t_max) {
+                            t_max = t;
+                        }
+                    }
+                }
+                BvhNode::Inner { left, right, axis } => {
+                    // the child on the near side of the split is popped first
+                    let (near, far) = if negative[axis] {
+                        (right, left)
+                    } else {
+                        (left, right)
+                    };
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+            }
+        }
+
+        record_bvh_visits(visits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sampling::Rng, shape::Shape};
+
+    /// Spheres of all sizes scattered over a box 20 units wide, overlapping in places.
+    fn spheres(rng: &mut Rng) -> Vec<Shape> {
+        (0..300)
+            .map(|_| Shape::Sphere {
+                center: Vector3::new(rng.next_float(), rng.next_float(), rng.next_float()) * 20.
+                    - Vector3::splat(10.),
+                radius: 0.05 + rng.next_float() * rng.next_float() * 2.,
+            })
+            .collect()
+    }
+
+    fn random_ray(rng: &mut Rng) -> Ray {
+        let mut point = || Vector3::new(rng.next_float(), rng.next_float(), rng.next_float());
+        let origin = point() * 30. - Vector3::splat(15.);
+        let target = point() * 16. - Vector3::splat(8.);
+        Ray::new(origin, target - origin, 0.)
+    }
+
+    #[test]
+    fn closest_hit_matches_brute_force() {
+        let mut rng = Rng::new(21);
+        let shapes = spheres(&mut rng);
+        let bvh = Bvh::build(&shapes.iter().map(Shape::bounds).collect::<Vec<_>>());
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = random_ray(&mut rng);
+            let (t_min, t_max) = (0., 0.9);
+
+            let brute = shapes
+                .iter()
+                .enumerate()
+                .filter_map(|(index, shape)| Some((index, shape.hit(&ray, t_min, t_max)?.t)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+
+            let mut closest = None;
+            bvh.traverse(&ray, t_min, t_max, |index, t_max| {
+                let t = shapes[index].hit(&ray, t_min, t_max)?.t;
+                closest = Some((index, t));
+                Some(t)
+            });
+
+            assert_eq!(closest, brute);
+            hits += usize::from(brute.is_some());
+        }
+        assert!(hits > 500, "{hits}");
+    }
+
+    #[test]
+    fn every_box_on_the_ray_is_visited() {
+        let mut rng = Rng::new(23);
+        let boxes: Vec<Aabb> = spheres(&mut rng).iter().map(Shape::bounds).collect();
+        let bvh = Bvh::build(&boxes);
+
+        for _ in 0..500 {
+            let ray = random_ray(&mut rng);
+            let direction = ray.direction;
+            let inv_direction = Vector3::new(1. / direction.x, 1. / direction.y, 1. / direction.z);
+
+            let mut visited = vec![false; boxes.len()];
+            bvh.traverse(&ray, 0., Float::INFINITY, |index, _| {
+                assert!(!visited[index], "box {index} visited twice");
+                visited[index] = true;
+                None
+            });
+
+            for (index, bounds) in boxes.iter().enumerate() {
+                if bounds.hit(ray.origin, inv_direction, 0., Float::INFINITY) {
+                    assert!(visited[index], "box {index} is skipped");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_hierarchy_visits_nothing() {
+        let bvh = Bvh::build(&[]);
+        assert!(bvh.bounds().is_empty());
+        let ray = Ray::new(Vector3::ZERO, Vector3::new(0., 0., 1.), 0.);
+        bvh.traverse(&ray, 0., Float::INFINITY, |_, _| panic!("nothing to visit"));
+    }
+}
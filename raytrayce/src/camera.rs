@@ -0,0 +1,157 @@
+use crate::{sampling::*, Float, Ray, Vector3};
+
+/// Width of the viewport on the projection plane, the horizontal field of view.
+pub const VIEWPORT_SIZE: Float = 2.0;
+/// Distance of the projection plane from the camera, camera rays reach it at t = 1.
+pub const PROJECTION_PLANE_Z: Float = 1.0;
+
+/// Shape of the lens opening, decides how out of focus highlights look.
+#[derive(Clone, Copy, Debug)]
+pub enum Bokeh {
+    Circle,
+    Polygon { blades: u32, rotation: Float },
+}
+
+/// Thin-lens camera. With zero aperture it is the usual pinhole.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub position: Vector3,
+    /// Turns directions from camera space, where the camera looks down +z, to the world.
+    pub rotation: [[Float; 3]; 3],
+    /// Diameter of the lens opening.
+    pub aperture: Float,
+    /// Distance along the view axis of the plane that stays sharp.
+    pub focus_distance: Float,
+    pub bokeh: Bokeh,
+    /// Rays get a time uniformly picked between the shutter opening and closing.
+    pub shutter_open: Float,
+    pub shutter_close: Float,
+}
+
+impl Camera {
+    /// Primary ray through the viewport point `viewport`.
+    /// The returned direction still reaches the projection plane at t = 1.
+    pub fn ray(&self, viewport: Vector3, rng: &mut Rng) -> Ray {
+        let time = self.shutter_open + (self.shutter_close - self.shutter_open) * rng.next_float();
+
+        if self.aperture <= 0. {
+            return Ray::new(self.position, self.rotate(viewport), time);
+        }
+
+        let focus_point = viewport * (self.focus_distance / viewport.z);
+
+        let (lx, ly) = match self.bokeh {
+            Bokeh::Circle => sample_unit_disk(rng),
+            Bokeh::Polygon { blades, rotation } => sample_polygon(rng, blades, rotation),
+        };
+        let lens_radius = self.aperture / 2.;
+        let lens_point = Vector3::new(lx * lens_radius, ly * lens_radius, 0.);
+
+        let direction = (focus_point - lens_point) * (PROJECTION_PLANE_Z / self.focus_distance);
+
+        Ray::new(
+            self.position + self.rotate(lens_point),
+            self.rotate(direction),
+            time,
+        )
+    }
+
+    fn rotate(&self, direction: Vector3) -> Vector3 {
+        Vector3::from(self.rotation.map(|row| Vector3::from(row).dot(direction)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+
+    /// Turned a quarter around the y axis, looking down +x.
+    fn camera(aperture: Float, bokeh: Bokeh) -> Camera {
+        Camera {
+            position: Vector3::new(1., 2., 3.),
+            rotation: [[0., 0., 1.], [0., 1., 0.], [-1., 0., 0.]],
+            aperture,
+            focus_distance: 4.,
+            bokeh,
+            shutter_open: 0.25,
+            shutter_close: 0.75,
+        }
+    }
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-4, "{a:?} is not {b:?}");
+    }
+
+    #[test]
+    fn pinhole_rays_start_at_the_camera() {
+        let camera = camera(0., Bokeh::Circle);
+        let mut rng = Rng::new(1);
+        let ray = camera.ray(Vector3::new(0.5, -0.25, PROJECTION_PLANE_Z), &mut rng);
+        assert_eq!(ray.origin, camera.position);
+        assert_close(ray.direction, Vector3::new(1., -0.25, -0.5));
+    }
+
+    #[test]
+    fn thin_lens_rays_meet_on_the_focus_plane() {
+        let viewport = Vector3::new(0.3, 0.2, PROJECTION_PLANE_Z);
+        for bokeh in [
+            Bokeh::Circle,
+            Bokeh::Polygon {
+                blades: 5,
+                rotation: 0.3,
+            },
+        ] {
+            let camera = camera(0.5, bokeh);
+            let pinhole = Camera {
+                aperture: 0.,
+                ..camera.clone()
+            };
+            let mut rng = Rng::new(7);
+            let sharp = pinhole.ray(viewport, &mut rng).at(camera.focus_distance);
+
+            let mut spread: Float = 0.;
+            for _ in 0..200 {
+                let ray = camera.ray(viewport, &mut rng);
+                assert_close(ray.at(camera.focus_distance), sharp);
+                // the lens lies across the view axis, within the aperture
+                let offset = ray.origin - camera.position;
+                assert!(offset.length() <= camera.aperture / 2. + 1e-5);
+                assert!(offset.x.abs() < 1e-5);
+                // and the ray still reaches the projection plane at t = 1
+                assert!((ray.direction.x - PROJECTION_PLANE_Z).abs() < 1e-5);
+                spread = spread.max(offset.length());
+            }
+            assert!(spread > camera.aperture / 4.);
+        }
+    }
+
+    #[test]
+    fn rays_are_spread_over_the_shutter_interval() {
+        let camera = camera(0., Bokeh::Circle);
+        let mut rng = Rng::new(3);
+        let times: Vec<Float> = (0..1000)
+            .map(|_| camera.ray(Vector3::new(0., 0., 1.), &mut rng).time)
+            .collect();
+        assert!(times.iter().all(|t| (0.25..0.75).contains(t)));
+        let mean = times.iter().sum::<Float>() / times.len() as Float;
+        assert!((mean - 0.5).abs() < 0.02, "{mean}");
+    }
+
+    #[test]
+    fn lens_samples_stay_inside_the_opening() {
+        let mut rng = Rng::new(5);
+        for _ in 0..1000 {
+            let (x, y) = sample_unit_disk(&mut rng);
+            assert!(x * x + y * y <= 1. + 1e-5);
+
+            // inside every side of the hexagon
+            let (x, y) = sample_polygon(&mut rng, 6, 0.);
+            let apothem = (PI / 6.).cos();
+            for side in 0..6 {
+                let angle = (side as Float + 0.5) * PI / 3.;
+                assert!(x * angle.cos() + y * angle.sin() <= apothem + 1e-5);
+            }
+        }
+    }
+}
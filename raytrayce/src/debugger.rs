@@ -0,0 +1,348 @@
+use std::fmt::Write;
+
+use crate::{Color, Float, Ray, RayKind, Vector3};
+
+/// Collects the rays traced for one sample, handed down the trace by `debug_sample`.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    /// Rays that are being traced, innermost last.
+    stack: Vec<RayRecord>,
+    roots: Vec<RayRecord>,
+}
+
+/// One traced ray with everything that was computed for it.
+#[derive(Clone, Debug)]
+pub struct RayRecord {
+    pub kind: RayKind,
+    /// Number of bounces from the camera.
+    pub depth: usize,
+    pub origin: Vector3,
+    pub direction: Vector3,
+    pub time: Float,
+    pub t_min: Float,
+    pub t_max: Float,
+    pub hit: Option<HitRecord>,
+    pub lights: Vec<LightSample>,
+    /// What the ray returned.
+    pub color: Color,
+    pub children: Vec<RayRecord>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HitRecord {
+    /// Index into the scene objects.
+    pub object: usize,
+    pub t: Float,
+    pub position: Vector3,
+    pub normal: Vector3,
+    pub uv: (Float, Float),
+    pub albedo: Color,
+    pub specular: i32,
+    pub reflective: Float,
+}
+
+/// Light gathered at a hit, `light` indexes the scene lights.
+#[derive(Clone, Copy, Debug)]
+pub enum LightSample {
+    Ambient {
+        light: usize,
+        intensity: Float,
+    },
+    /// Point and directional lights, `occluder` is the object that blocked the shadow ray.
+    Direct {
+        light: usize,
+        direction: Vector3,
+        occluder: Option<usize>,
+        diffuse: Float,
+        specular: Float,
+    },
+    Environment {
+        direction: Vector3,
+        pdf: Float,
+        occluder: Option<usize>,
+        radiance: Color,
+    },
+}
+
+impl Recorder {
+    /// The trees of the camera rays traced so far.
+    pub(crate) fn finish(self) -> Vec<RayRecord> {
+        self.roots
+    }
+
+    pub(crate) fn begin_ray(&mut self, ray: &Ray, kind: RayKind, t_min: Float, t_max: Float) {
+        let depth = self.stack.len();
+        self.stack.push(RayRecord {
+            kind,
+            depth,
+            origin: ray.origin,
+            direction: ray.direction,
+            time: ray.time,
+            t_min,
+            t_max,
+            hit: None,
+            lights: vec![],
+            color: Color::BLACK,
+            children: vec![],
+        });
+    }
+
+    pub(crate) fn record_hit(&mut self, hit: HitRecord) {
+        if let Some(ray) = self.stack.last_mut() {
+            ray.hit = Some(hit);
+        }
+    }
+
+    pub(crate) fn record_light(&mut self, light: LightSample) {
+        if let Some(ray) = self.stack.last_mut() {
+            ray.lights.push(light);
+        }
+    }
+
+    pub(crate) fn end_ray(&mut self, color: Color) {
+        let Some(mut ray) = self.stack.pop() else {
+            return;
+        };
+        ray.color = color;
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(ray),
+            None => self.roots.push(ray),
+        }
+    }
+}
+
+fn kind_name(kind: RayKind) -> &'static str {
+    match kind {
+        RayKind::Camera => "camera",
+        RayKind::Reflection => "reflection",
+        RayKind::Diffuse => "diffuse",
+        RayKind::Shadow => "shadow",
+    }
+}
+
+/// Vectors and colours alike.
+fn vector_text(v: impl Into<[Float; 3]>) -> String {
+    let [x, y, z] = v.into();
+    format!("({x:.4}, {y:.4}, {z:.4})")
+}
+
+/// Indented, human readable dump of the ray trees.
+pub fn to_text(rays: &[RayRecord]) -> String {
+    let mut out = String::new();
+    for ray in rays {
+        write_text(&mut out, ray);
+    }
+    out
+}
+
+fn write_text(out: &mut String, ray: &RayRecord) {
+    let indent = "    ".repeat(ray.depth);
+
+    let _ = writeln!(
+        out,
+        "{indent}{} ray, depth {}: origin {} direction {} t in ({}, {}) time {:.3}",
+        kind_name(ray.kind),
+        ray.depth,
+        vector_text(ray.origin),
+        vector_text(ray.direction),
+        ray.t_min,
+        ray.t_max,
+        ray.time
+    );
+
+    match &ray.hit {
+        Some(hit) => {
+            let _ = writeln!(
+                out,
+                "{indent}  hit object {} at t {:.4}: position {} normal {} uv ({:.3}, {:.3})",
+                hit.object,
+                hit.t,
+                vector_text(hit.position),
+                vector_text(hit.normal),
+                hit.uv.0,
+                hit.uv.1
+            );
+            let _ = writeln!(
+                out,
+                "{indent}  albedo {} specular {} reflective {:.3}",
+                vector_text(hit.albedo),
+                hit.specular,
+                hit.reflective
+            );
+        }
+        None => {
+            let _ = writeln!(out, "{indent}  miss");
+        }
+    }
+
+    for light in &ray.lights {
+        let _ = match *light {
+            LightSample::Ambient { light, intensity } => {
+                writeln!(out, "{indent}  light {light} ambient: {intensity:.4}")
+            }
+            LightSample::Direct {
+                light,
+                direction,
+                occluder: Some(occluder),
+                ..
+            } => writeln!(
+                out,
+                "{indent}  light {light} towards {}: shadowed by object {occluder}",
+                vector_text(direction)
+            ),
+            LightSample::Direct {
+                light,
+                direction,
+                occluder: None,
+                diffuse,
+                specular,
+            } => writeln!(
+                out,
+                "{indent}  light {light} towards {}: diffuse {diffuse:.4} specular {specular:.4}",
+                vector_text(direction)
+            ),
+            LightSample::Environment {
+                direction,
+                pdf,
+                occluder: Some(occluder),
+                ..
+            } => writeln!(
+                out,
+                "{indent}  environment towards {} (pdf {pdf:.4}): shadowed by object {occluder}",
+                vector_text(direction)
+            ),
+            LightSample::Environment {
+                direction,
+                pdf,
+                occluder: None,
+                radiance,
+            } => writeln!(
+                out,
+                "{indent}  environment towards {} (pdf {pdf:.4}): {}",
+                vector_text(direction),
+                vector_text(radiance)
+            ),
+        };
+    }
+
+    for child in &ray.children {
+        write_text(out, child);
+    }
+
+    let _ = writeln!(out, "{indent}  color {}", vector_text(ray.color));
+}
+
+/// The ray trees as a JSON array, children nested in their parents.
+pub fn to_json(rays: &[RayRecord]) -> String {
+    let mut out = String::from("[");
+    for (index, ray) in rays.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_json(&mut out, ray);
+    }
+    out.push(']');
+    out
+}
+
+/// JSON has no infinity, non finite numbers become null.
+fn json_number(value: Float) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_vector(v: impl Into<[Float; 3]>) -> String {
+    let [x, y, z] = v.into();
+    format!("[{},{},{}]", json_number(x), json_number(y), json_number(z))
+}
+
+fn json_occluder(occluder: Option<usize>) -> String {
+    occluder.map_or("null".to_string(), |object| object.to_string())
+}
+
+fn write_json(out: &mut String, ray: &RayRecord) {
+    let _ = write!(
+        out,
+        "{{\"kind\":\"{}\",\"depth\":{},\"origin\":{},\"direction\":{},\"time\":{},\"t_min\":{},\"t_max\":{},",
+        kind_name(ray.kind),
+        ray.depth,
+        json_vector(ray.origin),
+        json_vector(ray.direction),
+        json_number(ray.time),
+        json_number(ray.t_min),
+        json_number(ray.t_max)
+    );
+
+    match &ray.hit {
+        Some(hit) => {
+            let _ = write!(
+                out,
+                "\"hit\":{{\"object\":{},\"t\":{},\"position\":{},\"normal\":{},\"uv\":[{},{}],\"albedo\":{},\"specular\":{},\"reflective\":{}}},",
+                hit.object,
+                json_number(hit.t),
+                json_vector(hit.position),
+                json_vector(hit.normal),
+                json_number(hit.uv.0),
+                json_number(hit.uv.1),
+                json_vector(hit.albedo),
+                hit.specular,
+                json_number(hit.reflective)
+            );
+        }
+        None => out.push_str("\"hit\":null,"),
+    }
+
+    out.push_str("\"lights\":[");
+    for (index, light) in ray.lights.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        let _ = match *light {
+            LightSample::Ambient { light, intensity } => write!(
+                out,
+                "{{\"type\":\"ambient\",\"light\":{light},\"intensity\":{}}}",
+                json_number(intensity)
+            ),
+            LightSample::Direct {
+                light,
+                direction,
+                occluder,
+                diffuse,
+                specular,
+            } => write!(
+                out,
+                "{{\"type\":\"direct\",\"light\":{light},\"direction\":{},\"occluder\":{},\"diffuse\":{},\"specular\":{}}}",
+                json_vector(direction),
+                json_occluder(occluder),
+                json_number(diffuse),
+                json_number(specular)
+            ),
+            LightSample::Environment {
+                direction,
+                pdf,
+                occluder,
+                radiance,
+            } => write!(
+                out,
+                "{{\"type\":\"environment\",\"direction\":{},\"pdf\":{},\"occluder\":{},\"radiance\":{}}}",
+                json_vector(direction),
+                json_number(pdf),
+                json_occluder(occluder),
+                json_vector(radiance)
+            ),
+        };
+    }
+
+    let _ = write!(out, "],\"color\":{},\"children\":[", json_vector(ray.color));
+    for (index, child) in ray.children.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_json(out, child);
+    }
+    out.push_str("]}");
+}
@@ -0,0 +1,233 @@
+//! The scene the `raytrayce` binary renders: four textured spheres, a machined part,
+//! a few transformed shapes and a field of icosahedra under three lights.
+
+use image::Rgb;
+use std::sync::Arc;
+
+use crate::{
+    camera::{Bokeh, Camera},
+    consts::{FRAC_PI_4, PI},
+    environment::Environment,
+    material::{Channel, Material},
+    mesh::Mesh,
+    sampling::Rng,
+    scene::{Light, LightType, Object, Scene},
+    shape::{CsgOperation, Shape},
+    texture::Texture,
+    Color, Float, Transform, Vector3,
+};
+
+/// At the origin looking down +z, focused 4 units away through a small hexagonal
+/// aperture, with the shutter open for one unit of time.
+pub fn camera() -> Camera {
+    Camera {
+        position: Vector3::ZERO,
+        rotation: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+        aperture: 0.1,
+        focus_distance: 4.,
+        shutter_open: 0.,
+        shutter_close: 1.,
+        bokeh: Bokeh::Polygon {
+            blades: 6,
+            rotation: 0.,
+        },
+    }
+}
+
+/// The objects and lights, with a black environment.
+pub fn scene(camera: &Camera) -> Scene {
+    let mut objects = vec![SPHERE_1, SPHERE_2, SPHERE_3, SPHERE_4, machined_part()];
+    objects.extend(instanced_objects());
+
+    Scene::new(
+        objects,
+        vec![LIGHT_1, LIGHT_2, LIGHT_3],
+        Environment::Color(Color::BLACK),
+        camera,
+    )
+    .expect("demo lights are valid")
+}
+
+const SPHERE_1: Object = Object {
+    shape: Shape::Sphere {
+        center: Vector3::new(0., -1., 3.),
+        radius: 1.0,
+    },
+    transform: None,
+    velocity: Vector3::new(0., 0.25, 0.),
+    material: Material {
+        albedo: Texture::Solid(Rgb([255u8, 0u8, 0u8])),
+        specular: Channel::Constant(500.),
+        reflective: Channel::Constant(0.2),
+    },
+};
+
+const SPHERE_2: Object = Object {
+    shape: Shape::Sphere {
+        center: Vector3::new(2., 0., 4.),
+        radius: 1.0,
+    },
+    transform: None,
+    velocity: Vector3::ZERO,
+    material: Material {
+        albedo: Texture::Marble {
+            color: Rgb([230u8, 230u8, 255u8]),
+            vein: Rgb([0u8, 0u8, 160u8]),
+            scale: 4.,
+            turbulence: 6.,
+        },
+        specular: Channel::Constant(500.),
+        reflective: Channel::Constant(0.3),
+    },
+};
+
+const SPHERE_3: Object = Object {
+    shape: Shape::Sphere {
+        center: Vector3::new(-2., 0., 4.),
+        radius: 1.0,
+    },
+    transform: None,
+    velocity: Vector3::ZERO,
+    material: Material {
+        albedo: Texture::Stripes {
+            even: Rgb([0u8, 255u8, 0u8]),
+            odd: Rgb([0u8, 120u8, 0u8]),
+            scale: 0.25,
+        },
+        specular: Channel::Constant(10.),
+        reflective: Channel::Texture {
+            texture: Texture::Noise {
+                color: Rgb([255u8, 255u8, 255u8]),
+                scale: 3.,
+            },
+            scale: 0.4,
+        },
+    },
+};
+
+const SPHERE_4: Object = Object {
+    shape: Shape::Sphere {
+        center: Vector3::new(0., -5001., 0.),
+        radius: 5000.0,
+    },
+    transform: None,
+    velocity: Vector3::ZERO,
+    material: Material {
+        albedo: Texture::Checker {
+            even: Rgb([255u8, 255u8, 0u8]),
+            odd: Rgb([120u8, 120u8, 0u8]),
+            scale: 1.,
+        },
+        specular: Channel::Constant(1000.),
+        reflective: Channel::Constant(0.5),
+    },
+};
+
+const LIGHT_1: Light = Light {
+    light_type: LightType::Ambient,
+    intensity: 0.2,
+    direction: None,
+};
+
+const LIGHT_2: Light = Light {
+    light_type: LightType::Point,
+    intensity: 0.6,
+    direction: Some(Vector3::new(2., 1., 0.)),
+};
+
+const LIGHT_3: Light = Light {
+    light_type: LightType::Directional,
+    intensity: 0.2,
+    direction: Some(Vector3::new(1., 4., 4.)),
+};
+
+/// Cube with a sphere carved out of its middle, floating over the red sphere.
+fn machined_part() -> Object {
+    Object {
+        shape: Shape::Csg {
+            operation: CsgOperation::Difference,
+            left: Box::new(Shape::Cuboid {
+                min: Vector3::new(-0.5, 0.7, 4.5),
+                max: Vector3::new(0.5, 1.7, 5.5),
+            }),
+            right: Box::new(Shape::Sphere {
+                center: Vector3::new(0., 1.2, 5.),
+                radius: 0.65,
+            }),
+        },
+        transform: None,
+        velocity: Vector3::ZERO,
+        material: Material {
+            albedo: Texture::Solid(Rgb([200u8, 200u8, 210u8])),
+            specular: Channel::Constant(100.),
+            reflective: Channel::Constant(0.1),
+        },
+    }
+}
+
+/// Objects placed by transforms: a flattened sphere, a turned box
+/// and a field of icosahedra all sharing one mesh.
+fn instanced_objects() -> Vec<Object> {
+    let matte = |color: Rgb<u8>| Material {
+        albedo: Texture::Solid(color),
+        specular: Channel::Constant(50.),
+        reflective: Channel::Constant(0.),
+    };
+    let unit_sphere = Shape::Sphere {
+        center: Vector3::ZERO,
+        radius: 1.,
+    };
+
+    let mut objects = vec![
+        Object {
+            shape: unit_sphere,
+            transform: Some(
+                Transform::scaling(Vector3::new(0.35, 0.2, 0.35))
+                    .then(&Transform::rotation_z(0.3))
+                    .then(&Transform::translation(Vector3::new(1.3, -0.8, 2.3))),
+            ),
+            velocity: Vector3::ZERO,
+            material: matte(Rgb([255u8, 140u8, 0u8])),
+        },
+        Object {
+            shape: Shape::Cuboid {
+                min: Vector3::new(-0.25, -0.25, -0.25),
+                max: Vector3::new(0.25, 0.25, 0.25),
+            },
+            transform: Some(
+                Transform::rotation_y(FRAC_PI_4)
+                    .then(&Transform::translation(Vector3::new(-1.3, -0.75, 2.4))),
+            ),
+            velocity: Vector3::ZERO,
+            material: matte(Rgb([0u8, 160u8, 200u8])),
+        },
+    ];
+
+    let icosahedron = Arc::new(Mesh::icosahedron());
+    let mut rng = Rng::new(35);
+    for row in 0..25 {
+        for column in 0..40 {
+            let size = 0.12 + 0.1 * rng.next_float();
+            let x = -10. + 0.5 * column as Float + 0.2 * rng.next_float();
+            let z = 6. + 0.5 * row as Float + 0.2 * rng.next_float();
+            let shade = (120. + 135. * rng.next_float()) as u8;
+
+            objects.push(Object {
+                shape: Shape::Mesh(icosahedron.clone()),
+                transform: Some(
+                    Transform::scaling(Vector3::splat(size))
+                        .then(&Transform::rotation_y(2. * PI * rng.next_float()))
+                        .then(&Transform::translation(Vector3::new(
+                            x,
+                            -1. + size * 0.8,
+                            z,
+                        ))),
+                ),
+                velocity: Vector3::ZERO,
+                material: matte(Rgb([shade, 255 - shade / 2, 255 - shade])),
+            });
+        }
+    }
+
+    objects
+}
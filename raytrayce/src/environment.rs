@@ -0,0 +1,368 @@
+use image::Rgb32FImage;
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    consts::PI,
+    error::{Error, Result},
+    sampling::Rng,
+    Color, Float, Vector3,
+};
+
+/// What a ray sees when it leaves the scene.
+/// Images are linear radiance, HDR files keep their values above 1.
+#[derive(Clone)]
+pub enum Environment {
+    Color(Color),
+    /// Latitude-longitude map, +y is up and the center of the image looks down +z.
+    Equirectangular {
+        image: Arc<Rgb32FImage>,
+        sampler: Arc<EquirectangularSampler>,
+    },
+    /// Faces in the +x, -x, +y, -y, +z, -z order.
+    CubeMap(Arc<[Rgb32FImage; 6]>),
+}
+
+impl Environment {
+    pub fn load_equirectangular(path: &Path) -> Result<Self> {
+        let image = load_image(path)?;
+        let sampler = EquirectangularSampler::new(&image)?;
+
+        Ok(Environment::Equirectangular {
+            image: Arc::new(image),
+            sampler: Arc::new(sampler),
+        })
+    }
+
+    pub fn load_cube_map(paths: [&Path; 6]) -> Result<Self> {
+        let [px, nx, py, ny, pz, nz] = paths;
+
+        Ok(Environment::CubeMap(Arc::new([
+            load_image(px)?,
+            load_image(nx)?,
+            load_image(py)?,
+            load_image(ny)?,
+            load_image(pz)?,
+            load_image(nz)?,
+        ])))
+    }
+
+    /// Whether the map, or a face of it, is an image without pixels.
+    pub fn has_empty_image(&self) -> bool {
+        let empty = |image: &Rgb32FImage| image.width() == 0 || image.height() == 0;
+        match self {
+            Environment::Color(_) => false,
+            Environment::Equirectangular { image, .. } => empty(image),
+            Environment::CubeMap(faces) => faces.iter().any(empty),
+        }
+    }
+
+    pub fn lookup(&self, direction: Vector3) -> Color {
+        match self {
+            Environment::Color(color) => *color,
+            Environment::Equirectangular { image, .. } => {
+                let (u, v) = direction_to_equirectangular(direction);
+                sample_bilinear(image, u, v, true)
+            }
+            Environment::CubeMap(faces) => {
+                let (face, u, v) = direction_to_cube_face(direction);
+                sample_bilinear(&faces[face], u, v, false)
+            }
+        }
+    }
+
+    /// Picks a direction towards the environment, bright regions more often.
+    /// Returns the direction with its probability density per solid angle.
+    pub fn sample(&self, rng: &mut Rng) -> (Vector3, Float) {
+        match self {
+            Environment::Equirectangular { sampler, .. } => sampler.sample(rng),
+            _ => {
+                // uniform over the sphere
+                let z = 1. - 2. * rng.next_float();
+                let r = (1. - z * z).max(0.).sqrt();
+                let phi = 2. * PI * rng.next_float();
+                (
+                    Vector3::new(r * phi.cos(), r * phi.sin(), z),
+                    1. / (4. * PI),
+                )
+            }
+        }
+    }
+}
+
+/// Fails for an image that does not load or has no pixels.
+fn load_image(path: &Path) -> Result<Rgb32FImage> {
+    let image = image::open(path).map_err(Error::image(path))?.to_rgb32f();
+    if image.width() == 0 || image.height() == 0 {
+        return Err(Error::EmptyImage {
+            path: Some(path.to_path_buf()),
+        });
+    }
+    Ok(image)
+}
+
+fn direction_to_equirectangular(direction: Vector3) -> (Float, Float) {
+    let d = direction.normalize();
+    let u = 0.5 + d.x.atan2(d.z) / (2. * PI);
+    let v = d.y.clamp(-1., 1.).acos() / PI;
+    (u, v)
+}
+
+fn equirectangular_to_direction(u: Float, v: Float) -> Vector3 {
+    let phi = (u - 0.5) * 2. * PI;
+    let theta = v * PI;
+    Vector3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        theta.sin() * phi.cos(),
+    )
+}
+
+/// Major axis face selection, same orientation as OpenGL cube maps.
+fn direction_to_cube_face(d: Vector3) -> (usize, Float, Float) {
+    let (ax, ay, az) = (d.x.abs(), d.y.abs(), d.z.abs());
+
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if d.x > 0. {
+            (0, -d.z, -d.y, ax)
+        } else {
+            (1, d.z, -d.y, ax)
+        }
+    } else if ay >= az {
+        if d.y > 0. {
+            (2, d.x, d.z, ay)
+        } else {
+            (3, d.x, -d.z, ay)
+        }
+    } else if d.z > 0. {
+        (4, d.x, -d.y, az)
+    } else {
+        (5, -d.x, -d.y, az)
+    };
+
+    (face, 0.5 * (sc / ma + 1.), 0.5 * (tc / ma + 1.))
+}
+
+/// (u, v) in [0, 1], v grows downwards like image rows.
+/// Equirectangular maps wrap around horizontally, cube faces are clamped.
+fn sample_bilinear(image: &Rgb32FImage, u: Float, v: Float, wrap_u: bool) -> Color {
+    let width = image.width() as i32;
+    let height = image.height() as i32;
+
+    let x = u * width as Float - 0.5;
+    let y = v * height as Float - 0.5;
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+
+    let texel = |dx: i32, dy: i32| {
+        let tx = if wrap_u {
+            (x0 as i32 + dx).rem_euclid(width)
+        } else {
+            (x0 as i32 + dx).clamp(0, width - 1)
+        };
+        let ty = (y0 as i32 + dy).clamp(0, height - 1);
+        image.get_pixel(tx as u32, ty as u32).0.map(Float::from)
+    };
+
+    let [a, b, c, d] = [texel(0, 0), texel(1, 0), texel(0, 1), texel(1, 1)];
+    let mix = |i: usize| {
+        let top = a[i] * (1. - fx) + b[i] * fx;
+        let bottom = c[i] * (1. - fx) + d[i] * fx;
+        top * (1. - fy) + bottom * fy
+    };
+
+    Color::new(mix(0), mix(1), mix(2))
+}
+
+/// Piecewise constant distribution over the pixels of an equirectangular map,
+/// weighted by luminance and by the solid angle each row covers.
+pub struct EquirectangularSampler {
+    width: usize,
+    height: usize,
+    /// Cumulative distribution of picking a row.
+    marginal: Vec<Float>,
+    /// Cumulative distribution of picking a column in each row.
+    conditional: Vec<Vec<Float>>,
+    /// Probability of every pixel, row major.
+    probability: Vec<Float>,
+}
+
+impl EquirectangularSampler {
+    /// Fails for an image without pixels.
+    pub fn new(image: &Rgb32FImage) -> Result<Self> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::EmptyImage { path: None });
+        }
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+
+        let mut weights = vec![0.; width * height];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let color = Color::from(pixel.0.map(Float::from));
+            let sin_theta = (PI * (y as Float + 0.5) / height as Float).sin();
+            weights[y as usize * width + x as usize] = color.luminance().max(0.) * sin_theta;
+        }
+
+        let mut total: Float = weights.iter().sum();
+        if total <= 0. {
+            // black map, fall back to uniform pixels
+            weights.iter_mut().for_each(|w| *w = 1.);
+            total = (width * height) as Float;
+        }
+
+        let mut marginal = Vec::with_capacity(height);
+        let mut conditional = Vec::with_capacity(height);
+        let mut row_sum = 0.;
+
+        for row in weights.chunks(width) {
+            let row_total: Float = row.iter().sum();
+            let mut sum = 0.;
+            conditional.push(
+                row.iter()
+                    .map(|w| {
+                        sum += if row_total > 0. {
+                            w / row_total
+                        } else {
+                            1. / width as Float
+                        };
+                        sum
+                    })
+                    .collect(),
+            );
+
+            row_sum += row_total / total;
+            marginal.push(row_sum);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            marginal,
+            conditional,
+            probability: weights.iter().map(|w| w / total).collect(),
+        })
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> (Vector3, Float) {
+        let pick =
+            |cdf: &[Float], value: Float| cdf.partition_point(|&c| c < value).min(cdf.len() - 1);
+
+        let y = pick(&self.marginal, rng.next_float());
+        let x = pick(&self.conditional[y], rng.next_float());
+
+        let u = (x as Float + rng.next_float()) / self.width as Float;
+        let v = (y as Float + rng.next_float()) / self.height as Float;
+        let direction = equirectangular_to_direction(u, v);
+
+        // pixel probability -> density over (u, v) -> density over solid angle
+        let sin_theta = (v * PI).sin().max(1e-6);
+        let pdf = self.probability[y * self.width + x] * (self.width * self.height) as Float
+            / (2. * PI * PI * sin_theta);
+
+        (direction, pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// Dim sky with a sun 50 times brighter at pixel (12, 2).
+    fn sky() -> Rgb32FImage {
+        Rgb32FImage::from_fn(16, 8, |x, y| {
+            let value = if (x, y) == (12, 2) {
+                50.
+            } else {
+                0.1 + 0.05 * y as f32
+            };
+            Rgb([value; 3])
+        })
+    }
+
+    #[test]
+    fn equirectangular_mapping_round_trips() {
+        for (u, v) in [(0.5, 0.5), (0.1, 0.3), (0.9, 0.8), (0.25, 0.05)] {
+            let direction = equirectangular_to_direction(u, v);
+            assert!((direction.length() - 1.).abs() < 1e-5);
+            let (u2, v2) = direction_to_equirectangular(direction);
+            assert!((u - u2).abs() < 1e-4 && (v - v2).abs() < 1e-4, "{u2} {v2}");
+        }
+        // the middle of the image looks down +z, the top row up
+        let forward = equirectangular_to_direction(0.5, 0.5);
+        assert!((forward - Vector3::new(0., 0., 1.)).length() < 1e-5);
+        assert!(equirectangular_to_direction(0.3, 0.).y > 0.999);
+    }
+
+    #[test]
+    fn densities_integrate_to_one() {
+        let image = sky();
+        let sampler = EquirectangularSampler::new(&image).unwrap();
+        let environment = Environment::Equirectangular {
+            image: Arc::new(image),
+            sampler: Arc::new(sampler),
+        };
+
+        // the mean of 1 / pdf estimates the solid angle of the whole sphere,
+        // summed in chunks so single precision does not drift
+        let mut rng = Rng::new(11);
+        let mut chunk = || {
+            (0..1000)
+                .map(|_| {
+                    let (direction, pdf) = environment.sample(&mut rng);
+                    assert!((direction.length() - 1.).abs() < 1e-4);
+                    assert!(pdf > 0.);
+                    1. / pdf
+                })
+                .sum::<Float>()
+        };
+        let estimate = (0..100).map(|_| chunk()).sum::<Float>() / 100_000.;
+        assert!((estimate / (4. * PI) - 1.).abs() < 0.02, "{estimate}");
+    }
+
+    #[test]
+    fn plain_environments_are_sampled_uniformly() {
+        let environment = Environment::Color(Color::BLACK);
+        let mut rng = Rng::new(17);
+        let mut mean = Vector3::ZERO;
+        for _ in 0..10_000 {
+            let (direction, pdf) = environment.sample(&mut rng);
+            assert!((direction.length() - 1.).abs() < 1e-4);
+            assert_eq!(pdf, 1. / (4. * PI));
+            mean += direction / 10_000.;
+        }
+        assert!(mean.length() < 0.03, "{mean:?}");
+    }
+
+    #[test]
+    fn bright_pixels_are_picked_as_often_as_they_weigh() {
+        let image = sky();
+        let sampler = EquirectangularSampler::new(&image).unwrap();
+        let sun = sampler.probability[2 * 16 + 12];
+        assert!(sun > 0.5);
+
+        let mut rng = Rng::new(13);
+        let count = 20_000;
+        let hits = (0..count)
+            .filter(|_| {
+                let (direction, _) = sampler.sample(&mut rng);
+                let (u, v) = direction_to_equirectangular(direction);
+                ((u * 16.) as u32, (v * 8.) as u32) == (12, 2)
+            })
+            .count();
+        let fraction = hits as Float / count as Float;
+        assert!((fraction - sun).abs() < 0.02, "{fraction} {sun}");
+    }
+
+    #[test]
+    fn black_maps_are_sampled_evenly() {
+        let black = Rgb32FImage::new(4, 2);
+        let sampler = EquirectangularSampler::new(&black).unwrap();
+        assert!(sampler
+            .probability
+            .iter()
+            .all(|&p| (p - 1. / 8.).abs() < 1e-6));
+        assert!(EquirectangularSampler::new(&Rgb32FImage::new(0, 4)).is_err());
+    }
+}
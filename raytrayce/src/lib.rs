@@ -1,55 +1,92 @@
-use std::{
-    sync::{mpsc, Arc, Mutex},
-    thread,
-};
-
-type Job = Box<dyn FnOnce() + Send + 'static>;
-
-struct Worker {
-    id: usize,
-    thread: thread::JoinHandle<()>,
-}
-
-impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let job = receiver.lock().unwrap().recv().unwrap();
+//! Raytracer for spheres, boxes, CSG shapes and triangle meshes, lit by ambient, point
+//! and directional lights and an environment, rendered Whitted style or path traced.
+//!
+//! Put [`Object`]s and [`Light`]s in a [`Scene`], aim a [`Camera`] at it and hand both
+//! to [`render`] along with the [`RenderSettings`]:
+//!
+//! ```no_run
+//! use raytrayce::{demo, render, RenderSettings};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let camera = demo::camera();
+//! let scene = demo::scene(&camera);
+//! let (accumulator, stats) = render(&scene, &camera, &RenderSettings::default())?;
+//! accumulator.to_image().save("render.png")?;
+//! println!("{stats}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Loading, building and rendering report what went wrong as an [`Error`].
+//!
+//! [`scene_file`] reads scenes from text files, [`render_progressive`] driving
+//! [`render_sample`] adds previews, resumable checkpoints and time limits,
+//! [`debug_sample`] records the ray tree behind a single sample for the [`debugger`] to print.
 
-            println!("Worker {id} got a job; executing.");
+use image::Rgb;
 
-            job();
-        });
-        Worker { id, thread }
-    }
-}
+pub mod aov;
+mod bvh;
+pub mod camera;
+pub mod debugger;
+pub mod demo;
+pub mod environment;
+mod error;
+pub mod material;
+pub mod mesh;
+pub mod progressive;
+mod render;
+pub mod sampling;
+mod scene;
+pub mod scene_file;
+pub mod shape;
+pub mod stats;
+pub mod texture;
+mod thread_pool;
 
-pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: mpsc::Sender<Job>
-}
-
-impl ThreadPool {
-    pub fn new(size: usize) -> ThreadPool {
-        assert!(size > 0);
+pub use camera::{Bokeh, Camera};
+pub use environment::Environment;
+pub use error::{Error, Result};
+pub use material::{Channel, Material};
+pub use mesh::Mesh;
+pub use progressive::{render_progressive, Accumulator, Progress, ProgressiveSettings};
+pub use render::{
+    debug_sample, render, render_sample, CropRegion, RayKind, RenderMode, RenderSettings,
+};
+pub use sampling::Rng;
+pub use scene::{Light, LightType, Object, Scene};
+pub use shape::{CsgOperation, Shape};
+pub use stats::RenderStats;
+pub use texture::{Texture, WrapMode};
+pub use thread_pool::ThreadPool;
 
-        let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
+/// Precision of the geometry and shading math, `f64` with the `double-precision` feature.
+#[cfg(not(feature = "double-precision"))]
+pub type Float = f32;
+#[cfg(feature = "double-precision")]
+pub type Float = f64;
 
-        let mut workers = Vec::with_capacity(size);
+#[cfg(not(feature = "double-precision"))]
+use std::f32::consts;
+#[cfg(feature = "double-precision")]
+use std::f64::consts;
 
-        for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
-        }
+pub type Vector3 = math::Vector3<Float>;
+pub type Color = math::Color<Float>;
+pub type Ray = math::Ray<Float>;
+pub type Aabb = math::Aabb<Float>;
+pub type Transform = math::Transform<Float>;
 
-        ThreadPool { workers, sender }
-    }
+fn to_color(color: Rgb<u8>) -> Color {
+    Color::from_rgb8(color.0)
+}
 
-    pub fn execute<F>(&self, f: F)
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        let job = Box::new(f);
+/// Images and checkpoints store single precision whatever `Float` is.
+#[allow(clippy::unnecessary_cast)]
+fn to_f32(value: Float) -> f32 {
+    value as f32
+}
 
-        self.sender.send(job).unwrap();
-    }
+fn to_rgb(color: Color) -> Rgb<u8> {
+    Rgb(color.to_rgb8())
 }
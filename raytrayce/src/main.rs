@@ -1,339 +1,327 @@
-use image::{Rgb, RgbImage};
-use std::path::Path;
+use image::ImageFormat;
+use std::{env, path::PathBuf, process::ExitCode, str::FromStr, time::Duration};
 
-struct Point {
-    x: i32,
-    y: i32,
-}
-
-#[derive(Clone)]
-struct Sphere {
-    center: VectorPoint,
-    radius: f32,
-    color: Rgb<u8>,
-    specular: f32,
-    reflective: f32,
-}
-
-const SPHERE_1: Sphere = Sphere {
-    center: (0., -1., 3.),
-    radius: 1.0,
-    color: Rgb([255u8, 0u8, 0u8]),
-    specular: 500.,
-    reflective: 0.2,
-};
-
-const SPHERE_2: Sphere = Sphere {
-    center: (2., 0., 4.),
-    radius: 1.0,
-    color: Rgb([0u8, 0u8, 255u8]),
-    specular: 500.,
-    reflective: 0.3,
+use raytrayce::{
+    debug_sample, debugger, demo, render_progressive, render_sample, scene_file, Accumulator,
+    CropRegion, Environment, Error, Progress, ProgressiveSettings, RenderMode, RenderSettings, Rng,
+    Texture, WrapMode,
 };
 
-const SPHERE_3: Sphere = Sphere {
-    center: (-2., 0., 4.),
-    radius: 1.0,
-    color: Rgb([0u8, 255u8, 0u8]),
-    specular: 10.,
-    reflective: 0.4,
-};
-
-const SPHERE_4: Sphere = Sphere {
-    center: (0., -5001., 0.),
-    radius: 5000.0,
-    color: Rgb([255u8, 255u8, 0u8]),
-    specular: 1000.,
-    reflective: 0.5,
-};
-
-type VectorPoint = (f32, f32, f32);
-
-const CANVAS_WIDTH: i32 = 1500;
-const CANVAS_HEIGHT: i32 = 1500;
-const VIEWPORT_SIZE: f32 = 2.0;
-const PROJECTION_PLANE_Z: f32 = 1.0;
-const BACKGROUND_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
-
-#[derive(PartialEq)]
-enum LightType {
-    Ambient,
-    Point,
-    Directional,
-}
-
-struct Light {
-    intensity: f32,
-    light_type: LightType,
-    direction: Option<VectorPoint>,
+const USAGE: &str = "\
+Usage: raytrayce [OPTIONS]
+
+Renders a scene file, or the built-in demo scene without one.
+
+Options:
+  -s, --scene FILE          scene to render, in the format of the `scene_file` module
+  -o, --output FILE         image to write [default: ./imgs/5_rotation.png]
+  -f, --format FORMAT       png, jpeg, bmp, tga, tiff, ... [default: from the output extension]
+  -r, --resolution WxH      size of the whole image in pixels [default: 1500x1500]
+      --crop X,Y,W,H        render only this rectangle of the image
+  -n, --samples N           samples per pixel [default: 16]
+  -d, --depth N             how many times a ray may bounce [default: 3]
+  -j, --threads N           worker threads [default: one per core]
+  -m, --mode MODE           whitted or path [default: whitted]
+      --environment FILE    light the scene with this equirectangular map instead of its own
+      --texture FILE        wrap this image around the red sphere of the demo scene
+      --time-limit SECONDS  stop when the limit is hit, even in the middle of a pass
+      --aovs                also write depth, normal, albedo, id and light AOVs next to the image
+      --preview FILE        keep writing the image so far to FILE while rendering
+      --checkpoint FILE     save the progress to FILE every 30 seconds
+      --resume              continue from the --checkpoint file if it exists
+      --heatmap FILE        write how many rays and intersection tests every pixel took
+      --debug-pixel X,Y     only trace this pixel and print the ray tree behind its first sample
+      --json                print the ray tree as JSON instead of text
+  -h, --help                print this help
+";
+
+/// Everything the command line decides.
+struct Options {
+    scene: Option<PathBuf>,
+    output: PathBuf,
+    format: Option<ImageFormat>,
+    settings: RenderSettings,
+    environment: Option<PathBuf>,
+    texture: Option<PathBuf>,
+    threads: Option<usize>,
+    time_limit: Option<Duration>,
+    preview: Option<PathBuf>,
+    checkpoint: Option<PathBuf>,
+    resume: bool,
+    heatmap: Option<PathBuf>,
+    debug_pixel: Option<(u32, u32)>,
+    debug_json: bool,
 }
 
-const LIGHT_1: Light = Light {
-    light_type: LightType::Ambient,
-    intensity: 0.2,
-    direction: None,
-};
-
-const LIGHT_2: Light = Light {
-    light_type: LightType::Point,
-    intensity: 0.6,
-    direction: Some((2., 1., 0.)),
-};
-
-const LIGHT_3: Light = Light {
-    light_type: LightType::Directional,
-    intensity: 0.2,
-    direction: Some((1., 4., 4.)),
-};
-
-const CAMERA_ROTATION: [[f32; 3]; 3] = [[0.7071, 0., -0.7071], [0., 1., 0.], [0.7071, 0., 0.7071]];
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {message}\n\nTry `raytrayce --help`.");
+            return ExitCode::from(2);
+        }
+    };
 
-fn put_pixel(canvas: &mut RgbImage, color: Rgb<u8>, coord: Point) {
-    if coord.x >= 0
-        && coord.x < canvas.width() as i32
-        && coord.y >= 0
-        && coord.y < canvas.height() as i32
-    {
-        canvas.put_pixel(coord.x as u32, coord.y as u32, color);
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
     }
 }
 
-fn canvas_to_viewport(x: f32, y: f32) -> VectorPoint {
-    (
-        x * VIEWPORT_SIZE / CANVAS_WIDTH as f32,
-        y * VIEWPORT_SIZE / CANVAS_HEIGHT as f32,
-        PROJECTION_PLANE_Z,
-    )
-}
-
-fn substract_vector(a: VectorPoint, b: VectorPoint) -> VectorPoint {
-    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
-}
-
-fn add_vector(a: VectorPoint, b: VectorPoint) -> VectorPoint {
-    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
-}
-
-fn dot_number(a: VectorPoint, b: f32) -> VectorPoint {
-    (a.0 * b, a.1 * b, a.2 * b)
-}
-
-fn dot_vector(a: VectorPoint, b: VectorPoint) -> f32 {
-    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
-}
-
-fn length(a: VectorPoint) -> f32 {
-    (a.0 * a.0 + a.1 * a.1 + a.2 * a.2).sqrt()
-}
-
-fn negate(a: VectorPoint) -> VectorPoint {
-    (-a.0, -a.1, -a.2)
-}
-
-fn divide_number(a: VectorPoint, b: f32) -> VectorPoint {
-    (a.0 / b, a.1 / b, a.2 / b)
-}
-
-fn intersect_ray_sphere(
-    origin: VectorPoint,
-    direction: VectorPoint,
-    sphere: &Sphere,
-) -> (f32, f32) {
-    let r = sphere.radius;
-    let c0 = substract_vector(origin, sphere.center);
+fn run(options: &Options) -> Result<(), String> {
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|error| error.to_string())?;
+    }
 
-    let a = dot_vector(direction, direction);
-    let b = 2. * dot_vector(c0, direction);
-    let c = dot_vector(c0, c0) - r * r;
+    let (mut scene, camera) = match &options.scene {
+        Some(path) => scene_file::load(path).map_err(|error| error.to_string())?,
+        None => {
+            let camera = demo::camera();
+            (demo::scene(&camera), camera)
+        }
+    };
 
-    let discriminant = b * b - 4. * a * c;
-    if discriminant < 0. {
-        return (f32::INFINITY, f32::INFINITY);
+    if let Some(path) = &options.environment {
+        scene.environment =
+            Environment::load_equirectangular(path).map_err(|error| error.to_string())?;
+    }
+    if let Some(path) = &options.texture {
+        scene.objects[0].material.albedo =
+            Texture::load(path, WrapMode::Mirror).map_err(|error| error.to_string())?;
     }
 
-    let t1 = (-b + discriminant.sqrt()) / (2. * a);
-    let t2 = (-b - discriminant.sqrt()) / (2. * a);
-    (t1, t2)
-}
+    let settings = &options.settings;
+    let (width, height) = settings.output_size();
+    let mut accumulator = match &options.checkpoint {
+        Some(path) if options.resume && path.exists() => {
+            let accumulator =
+                Accumulator::load_checkpoint(path).map_err(|error| error.to_string())?;
+            if (accumulator.width, accumulator.height) != (width, height) {
+                return Err(format!(
+                    "{} holds a {}x{} render, this one is {width}x{height}",
+                    path.display(),
+                    accumulator.width,
+                    accumulator.height,
+                ));
+            }
+            if accumulator.has_aovs() != settings.aovs {
+                let with = if accumulator.has_aovs() {
+                    "with"
+                } else {
+                    "without"
+                };
+                return Err(format!(
+                    "{} holds a render {with} AOVs, resume it the same way",
+                    path.display(),
+                ));
+            }
+            accumulator
+        }
+        _ => Accumulator::new(width, height, settings.aovs).map_err(|error| error.to_string())?,
+    };
 
-fn closest_intersection(
-    origin: VectorPoint,
-    direction: VectorPoint,
-    t_min: f32,
-    t_max: f32,
-) -> (Option<&'static Sphere>, f32) {
-    let mut closest_t = f32::INFINITY;
-    let mut closest_sphere: Option<&Sphere> = None;
+    if let Some((x, y)) = options.debug_pixel {
+        // same seed as the first pass of a render, so this is the sample that lands in the image
+        let mut rng = Rng::new(u64::from(y) * u64::from(width) + u64::from(x));
 
-    for sphere in [&SPHERE_1, &SPHERE_2, &SPHERE_3, &SPHERE_4] {
-        let (t1, t2) = intersect_ray_sphere(origin, direction, sphere);
-        if t1 > t_min && t1 < t_max && t1 < closest_t {
-            closest_t = t1;
-            closest_sphere = Some(sphere);
-        }
-        if t2 > t_min && t2 < t_max && t2 < closest_t {
-            closest_t = t2;
-            closest_sphere = Some(sphere);
+        let (_, rays) = debug_sample(&scene, &camera, settings, x, y, &mut rng)
+            .map_err(|error| error.to_string())?;
+
+        if options.debug_json {
+            println!("{}", debugger::to_json(&rays));
+        } else {
+            print!("{}", debugger::to_text(&rays));
         }
+        return Ok(());
     }
 
-    (closest_sphere, closest_t)
-}
-
-fn reflect_ray(r_vector: VectorPoint, normal: VectorPoint) -> VectorPoint {
-    substract_vector(
-        dot_number(dot_number(normal, dot_vector(normal, r_vector)), 2.),
-        r_vector,
+    let progressive = ProgressiveSettings {
+        max_samples: settings.samples,
+        time_limit: options.time_limit,
+        checkpoint_interval: Duration::from_secs(30),
+        preview_path: options.preview.clone(),
+        checkpoint_path: options.checkpoint.clone(),
+        heatmap_path: options.heatmap.clone(),
+    };
+    let stats = render_progressive(
+        &mut accumulator,
+        &progressive,
+        |x, y, rng| render_sample(&scene, &camera, settings, x, y, rng),
+        |progress| match progress {
+            Progress::PassDone {
+                pass,
+                max_samples,
+                elapsed,
+            } => println!(
+                "Pass {pass}/{max_samples} done in {:.1}s",
+                elapsed.as_secs_f32()
+            ),
+            Progress::TimeLimitReached => println!("Time limit reached"),
+        },
     )
+    .map_err(|error| error.to_string())?;
+    println!("{stats}");
+
+    let canvas = accumulator.to_image();
+    let saved = match options.format {
+        Some(format) => canvas.save_with_format(&options.output, format),
+        None => canvas.save(&options.output),
+    };
+    saved
+        .map_err(Error::image(&options.output))
+        .and_then(|()| accumulator.save_aovs(&options.output))
+        .map_err(|error| error.to_string())
 }
 
-fn trace_ray(
-    origin: VectorPoint,
-    direction: VectorPoint,
-    t_min: f32,
-    t_max: f32,
-    rec_depth: u32,
-) -> Rgb<u8> {
-    let (closest_sphere, closest_t) = closest_intersection(origin, direction, t_min, t_max);
-
-    match closest_sphere {
-        Some(sphere) => {
-            let position = add_vector(origin, dot_number(direction, closest_t));
-            let normal = substract_vector(position, closest_sphere.unwrap().center);
-            let normal = divide_number(normal, length(normal));
-            let lightning_koef = compute_lightning(
-                position,
-                normal,
-                negate(direction),
-                sphere.specular.round() as i32,
-            );
-            let mut local_color = sphere
-                .color
-                .0
-                .map(|color| (f32::try_from(color).unwrap() * lightning_koef))
-                .map(|color| color.round() as u8);
-
-            let reflective = sphere.reflective;
-
-            if rec_depth <= 0 || reflective <= 0. {
-                return Rgb(local_color);
+/// `None` when help was asked for.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        scene: None,
+        output: PathBuf::from("./imgs/5_rotation.png"),
+        format: None,
+        settings: RenderSettings::default(),
+        environment: None,
+        texture: None,
+        threads: None,
+        time_limit: None,
+        preview: None,
+        checkpoint: None,
+        resume: false,
+        heatmap: None,
+        debug_pixel: None,
+        debug_json: false,
+    };
+    let settings = &mut options.settings;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // `--flag=value` as well as `--flag value`
+        let (flag, mut inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
             }
-
-            let reflect_ray = reflect_ray(negate(direction), normal);
-            let reflected_color =
-                trace_ray(position, reflect_ray, 0.001, f32::INFINITY, rec_depth - 1);
-            // let mut result_color: [u8; 3] = [0; 3];
-
-            for (index, col) in local_color.iter_mut().enumerate() {
-                *col = (f32::try_from(*col).unwrap() * (1. - reflective)
-                    + f32::try_from(reflected_color.0[index]).unwrap() * reflective)
-                    .round() as u8;
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .take()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{flag} needs a value"))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-s" | "--scene" => options.scene = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => options.output = PathBuf::from(value()?),
+            "-f" | "--format" => {
+                let name = value()?;
+                options.format = Some(
+                    ImageFormat::from_extension(&name)
+                        .filter(|format| format.can_write())
+                        .ok_or_else(|| format!("can not write `{name}` images"))?,
+                );
+            }
+            "-r" | "--resolution" => {
+                (settings.width, settings.height) = parse_resolution(&value()?)?;
+            }
+            "--crop" => {
+                let [x, y, width, height] = parse_list(&flag, &value()?)?;
+                settings.crop = Some(CropRegion {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+            "-n" | "--samples" => settings.samples = parse_positive(&flag, &value()?)?,
+            "-d" | "--depth" => settings.max_depth = parse_number(&flag, &value()?)?,
+            "-j" | "--threads" => options.threads = Some(parse_positive(&flag, &value()?)?),
+            "-m" | "--mode" => {
+                settings.mode = match value()?.as_str() {
+                    "whitted" => RenderMode::Whitted,
+                    "path" => RenderMode::PathTrace,
+                    other => return Err(format!("unknown render mode `{other}`")),
+                };
             }
-            Rgb(local_color)
+            "--time-limit" => {
+                let seconds: f64 = parse_number(&flag, &value()?)?;
+                options.time_limit = Some(
+                    Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| format!("{flag} needs a positive number of seconds"))?,
+                );
+            }
+            "--environment" => options.environment = Some(PathBuf::from(value()?)),
+            "--texture" => options.texture = Some(PathBuf::from(value()?)),
+            "--aovs" => settings.aovs = true,
+            "--preview" => options.preview = Some(PathBuf::from(value()?)),
+            "--checkpoint" => options.checkpoint = Some(PathBuf::from(value()?)),
+            "--resume" => options.resume = true,
+            "--heatmap" => options.heatmap = Some(PathBuf::from(value()?)),
+            "--debug-pixel" => {
+                let [x, y] = parse_list(&flag, &value()?)?;
+                options.debug_pixel = Some((x, y));
+            }
+            "--json" => options.debug_json = true,
+            _ if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            _ => return Err(format!("unexpected argument `{flag}`")),
         }
-        None => {
-            BACKGROUND_COLOR
+
+        if let Some(value) = inline {
+            return Err(format!("{flag} does not take a value, found `{value}`"));
         }
     }
-}
-
-fn compute_lightning(
-    position: VectorPoint,
-    normal: VectorPoint,
-    vector: VectorPoint,
-    specular: i32,
-) -> f32 {
-    let mut i = 0.;
-    for light in [&LIGHT_1, &LIGHT_2, &LIGHT_3] {
-        match light.light_type {
-            LightType::Ambient => {
-                i += light.intensity;
-            }
-            _ => {
-                let light_direction: VectorPoint;
-                let t_max: f32;
-                if light.light_type == LightType::Point {
-                    t_max = 1.;
-                    light_direction = substract_vector(light.direction.unwrap(), position);
-                } else {
-                    light_direction = light.direction.unwrap();
-                    t_max = f32::INFINITY;
-                }
-
-                let (shadow_sphere, _shadow_t) =
-                    closest_intersection(position, light_direction, 0.001, t_max);
-                if shadow_sphere.is_some() {
-                    continue;
-                }
-
-                let normal_dot_1 = dot_vector(normal, light_direction);
-                if normal_dot_1 > 0. {
-                    i +=
-                        light.intensity * normal_dot_1 / (length(normal) * length(light_direction));
-                }
 
-                if specular != -1 {
-                    let reflection = substract_vector(
-                        dot_number(dot_number(normal, 2.), dot_vector(normal, light_direction)),
-                        light_direction,
-                    );
-                    let reflection_dot_v = dot_vector(reflection, vector);
-                    if reflection_dot_v > 0. {
-                        i += light.intensity
-                            * f32::powf(
-                                reflection_dot_v / (length(reflection) * length(vector)),
-                                specular as f32,
-                            )
-                    }
-                }
-            }
-        }
+    settings.validate().map_err(|error| error.to_string())?;
+    if options.texture.is_some() && options.scene.is_some() {
+        return Err("--texture only applies to the demo scene".to_string());
+    }
+    if options.resume && options.checkpoint.is_none() {
+        return Err("--resume needs a --checkpoint file".to_string());
     }
 
-    i
+    Ok(Some(options))
 }
 
+fn parse_number<T: FromStr>(flag: &str, text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("{flag} expects a number, found `{text}`"))
+}
 
-fn rotate_camera(direction: VectorPoint) -> VectorPoint {
-    let mut result = [0., 0., 0.];
-    let array_direction = [direction.0, direction.1, direction.2];
-
-    for i in 0..3 {
-        for j in 0..3 {
-            result[i] += array_direction[j] * CAMERA_ROTATION[i][j]
-        }
+fn parse_positive<T: FromStr + Default + PartialEq>(flag: &str, text: &str) -> Result<T, String> {
+    let number = parse_number(flag, text)?;
+    if number == T::default() {
+        return Err(format!("{flag} must be at least 1"));
     }
-
-    (result[0], result[1], result[2])
+    Ok(number)
 }
-fn main() {
-    let path = Path::new("./imgs/5_rotation.png");
 
-    let mut canvas = RgbImage::new(
-        u32::try_from(CANVAS_WIDTH).unwrap(),
-        u32::try_from(CANVAS_HEIGHT).unwrap(),
-    );
-    let camera_position: VectorPoint = (0., 0., 0.);
-    // let pool = rayon::ThreadPoolBuilder::new().num_threads(20).build().unwrap();
+/// `N` comma separated numbers, like `10,20` or `0,0,64,64`.
+fn parse_list<const N: usize>(flag: &str, text: &str) -> Result<[u32; N], String> {
+    let numbers: Vec<u32> = text
+        .split(',')
+        .map(|number| parse_number(flag, number.trim()))
+        .collect::<Result<_, _>>()?;
+    numbers
+        .try_into()
+        .map_err(|_| format!("{flag} expects {N} comma separated numbers, found `{text}`"))
+}
 
-    for x in -CANVAS_WIDTH / 2..CANVAS_WIDTH / 2 {
-        for y in -CANVAS_HEIGHT / 2..CANVAS_HEIGHT / 2 {
-            // pool.install(|| {
-                let direction = canvas_to_viewport(x as f32, y as f32);
-                // let direction = rotate_camera(direction);
-                let color = trace_ray(camera_position, direction, 1., f32::INFINITY, 3);
-                put_pixel(
-                    &mut canvas,
-                    color,
-                    Point {
-                        x: x + CANVAS_WIDTH / 2,
-                        y: CANVAS_HEIGHT / 2 - y,
-                    },
-                )
-            // });
-        
-        }
+/// `WIDTHxHEIGHT`, both at least 1.
+fn parse_resolution(text: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected a resolution like 1920x1080, found `{text}`");
+    let (width, height) = text.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
     }
-
-    canvas.save(path).unwrap();
 }
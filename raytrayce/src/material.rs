@@ -0,0 +1,45 @@
+use crate::{texture::Texture, Float, Vector3};
+
+/// Scalar surface property, either fixed or driven by a texture.
+/// Textured values are the texel luminance in [0, 1] multiplied by `scale`.
+#[derive(Clone)]
+pub enum Channel {
+    Constant(Float),
+    Texture { texture: Texture, scale: Float },
+}
+
+impl Channel {
+    pub fn sample(&self, uv: (Float, Float), position: Vector3) -> Float {
+        match self {
+            Channel::Constant(value) => *value,
+            Channel::Texture { texture, scale } => {
+                let [r, g, b] = texture.sample(uv, position).0;
+                let luminance =
+                    (0.2126 * Float::from(r) + 0.7152 * Float::from(g) + 0.0722 * Float::from(b))
+                        / 255.;
+                luminance * scale
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Material {
+    pub albedo: Texture,
+    /// Specular exponent, -1 for matte surfaces.
+    pub specular: Channel,
+    pub reflective: Channel,
+}
+
+impl Material {
+    /// Whether one of the textures is an image without pixels.
+    pub fn has_empty_image(&self) -> bool {
+        let channel_empty = |channel: &Channel| match channel {
+            Channel::Constant(_) => false,
+            Channel::Texture { texture, .. } => texture.is_empty_image(),
+        };
+        self.albedo.is_empty_image()
+            || channel_empty(&self.specular)
+            || channel_empty(&self.reflective)
+    }
+}
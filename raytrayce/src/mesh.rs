@@ -0,0 +1,372 @@
+use std::{fs, path::Path};
+
+use crate::{
+    bvh::Bvh,
+    error::{Error, Result},
+    shape::{rounding_error, Span, SurfaceHit},
+    stats::record_intersection_test,
+    Aabb, Float, Ray, Vector3,
+};
+
+/// Triangle mesh with its own hierarchy, meant to be shared between many objects.
+/// Triangles wound counterclockwise seen from outside get outward normals.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    positions: Vec<Vector3>,
+    triangles: Vec<[usize; 3]>,
+    bvh: Bvh,
+}
+
+impl Mesh {
+    /// Fails when a triangle refers to a vertex that does not exist or has no area.
+    pub fn new(positions: Vec<Vector3>, triangles: Vec<[usize; 3]>) -> Result<Self> {
+        for (index, triangle) in triangles.iter().enumerate() {
+            if let Some(&vertex) = triangle.iter().find(|&&i| i >= positions.len()) {
+                return Err(Error::InvalidMeshIndex {
+                    triangle: index,
+                    index: vertex,
+                    vertices: positions.len(),
+                });
+            }
+
+            let [a, b, c] = triangle.map(|i| positions[i]);
+            if (b - a).cross(c - a) == Vector3::ZERO {
+                return Err(Error::DegenerateTriangle { triangle: index });
+            }
+        }
+
+        let boxes: Vec<Aabb> = triangles
+            .iter()
+            .map(|triangle| Aabb::from_points(triangle.map(|i| positions[i])))
+            .collect();
+        let bvh = Bvh::build(&boxes);
+
+        Ok(Self {
+            positions,
+            triangles,
+            bvh,
+        })
+    }
+
+    /// Reads the vertices and faces of a Wavefront OBJ file, everything else is skipped.
+    /// Polygons are split into fans of triangles.
+    pub fn load_obj(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path).map_err(Error::io(path))?;
+        let invalid = |line: usize, message: &str| Error::BadScene {
+            path: path.to_path_buf(),
+            line: line + 1,
+            message: message.to_string(),
+        };
+
+        let mut positions = vec![];
+        let mut triangles = vec![];
+        let mut triangle_lines = vec![];
+
+        for (line_number, line) in source.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let values: Vec<Float> = words
+                        .take(3)
+                        .map(|word| {
+                            word.parse()
+                                .map_err(|_| invalid(line_number, "bad vertex coordinate"))
+                        })
+                        .collect::<Result<_>>()?;
+                    let [x, y, z] = values[..] else {
+                        return Err(invalid(line_number, "vertex needs three coordinates"));
+                    };
+                    positions.push(Vector3::new(x, y, z));
+                }
+                Some("f") => {
+                    // "f 1/2/3 4/5/6 ...", only the position index matters, negative counts from the end
+                    let indices: Vec<usize> = words
+                        .map(|word| {
+                            let index: i64 = word
+                                .split('/')
+                                .next()
+                                .unwrap_or_default()
+                                .parse()
+                                .map_err(|_| invalid(line_number, "bad face index"))?;
+                            let index = if index < 0 {
+                                positions.len() as i64 + index
+                            } else {
+                                index - 1
+                            };
+                            // faces may only refer to the vertices above them
+                            usize::try_from(index)
+                                .ok()
+                                .filter(|&index| index < positions.len())
+                                .ok_or_else(|| invalid(line_number, "face index out of range"))
+                        })
+                        .collect::<Result<_>>()?;
+
+                    if indices.len() < 3 {
+                        return Err(invalid(line_number, "face needs three vertices"));
+                    }
+                    for i in 1..indices.len() - 1 {
+                        triangles.push([indices[0], indices[i], indices[i + 1]]);
+                        triangle_lines.push(line_number);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // point a face without area at its line
+        Self::new(positions, triangles).map_err(|error| match error {
+            Error::DegenerateTriangle { triangle } => {
+                invalid(triangle_lines[triangle], "face has no area")
+            }
+            error => error,
+        })
+    }
+
+    /// Icosahedron with unit circumradius, a handy stand-in for a round mesh.
+    pub fn icosahedron() -> Self {
+        let phi = (1. + Float::sqrt(5.)) / 2.;
+        let positions: Vec<Vector3> = [
+            Vector3::new(-1., phi, 0.),
+            Vector3::new(1., phi, 0.),
+            Vector3::new(-1., -phi, 0.),
+            Vector3::new(1., -phi, 0.),
+            Vector3::new(0., -1., phi),
+            Vector3::new(0., 1., phi),
+            Vector3::new(0., -1., -phi),
+            Vector3::new(0., 1., -phi),
+            Vector3::new(phi, 0., -1.),
+            Vector3::new(phi, 0., 1.),
+            Vector3::new(-phi, 0., -1.),
+            Vector3::new(-phi, 0., 1.),
+        ]
+        .into_iter()
+        .map(Vector3::normalize)
+        .collect();
+
+        let triangles = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        Self::new(positions, triangles).expect("icosahedron indices are valid")
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    /// Closest triangle with `t_min < t < t_max`.
+    pub fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<SurfaceHit> {
+        let mut closest = None;
+
+        self.bvh.traverse(ray, t_min, t_max, |index, t_max| {
+            let hit = self.hit_triangle(index, ray)?;
+            if hit.t <= t_min || hit.t >= t_max {
+                return None;
+            }
+            closest = Some(hit);
+            Some(hit.t)
+        });
+
+        closest
+    }
+
+    /// Every interval of the whole line inside the mesh, ordered by t, taking the mesh as
+    /// closed. A ray through an edge or a vertex crosses every triangle around it, or
+    /// misses them all through rounding, so crossings are not simply paired up: those
+    /// at the same point facing the same way count once, and the inside is where more
+    /// of them entered than left, from the side the normals face.
+    pub fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let mut hits = vec![];
+
+        self.bvh
+            .traverse(ray, Float::NEG_INFINITY, Float::INFINITY, |index, _| {
+                hits.extend(self.hit_triangle(index, ray));
+                None
+            });
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+
+        let entering = |hit: &SurfaceHit| hit.normal.dot(ray.direction) < 0.;
+        let mut depth = 0;
+        let mut enter = None;
+        let mut previous: Option<SurfaceHit> = None;
+        let mut spans = vec![];
+
+        for hit in hits {
+            if let Some(previous) = previous {
+                let apart = (ray.direction * (hit.t - previous.t)).length();
+                if entering(&hit) == entering(&previous) && apart <= hit.error + previous.error {
+                    continue;
+                }
+            }
+            previous = Some(hit);
+
+            if entering(&hit) {
+                depth += 1;
+                if depth == 1 {
+                    enter = Some(hit);
+                }
+            } else if depth > 0 {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(enter) = enter.take() {
+                        spans.push(Span { enter, exit: hit });
+                    }
+                }
+            }
+        }
+
+        spans
+    }
+
+    /// Möller-Trumbore, the barycentric coordinates of the hit become its uv.
+    fn hit_triangle(&self, index: usize, ray: &Ray) -> Option<SurfaceHit> {
+        record_intersection_test();
+
+        let [a, b, c] = self.triangles[index].map(|i| self.positions[i]);
+        let edge_1 = b - a;
+        let edge_2 = c - a;
+
+        let p = ray.direction.cross(edge_2);
+        let determinant = edge_1.dot(p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inv_determinant = 1. / determinant;
+
+        let s = ray.origin - a;
+        let u = s.dot(p) * inv_determinant;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge_1);
+        let v = ray.direction.dot(q) * inv_determinant;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+
+        let t = edge_2.dot(q) * inv_determinant;
+        let magnitude = a.max_abs().max(b.max_abs()).max(c.max_abs());
+        Some(SurfaceHit {
+            t,
+            normal: edge_1.cross(edge_2).normalize(),
+            uv: (u, v),
+            error: rounding_error(magnitude + ray.origin.max_abs() + (ray.direction * t).max_abs()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{CsgOperation, Shape};
+    use std::sync::Arc;
+
+    /// Cube from -1 to 1, every face split along the diagonal through its corner
+    /// with the lowest index. Bit 0 of a vertex index is x, bit 1 y and bit 2 z.
+    fn cube() -> Mesh {
+        let positions = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i >> bit & 1 == 1 { 1. } else { -1. };
+                Vector3::new(coordinate(0), coordinate(1), coordinate(2))
+            })
+            .collect::<Vec<_>>();
+        let faces = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        let triangles = faces
+            .iter()
+            .flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]])
+            .collect();
+        Mesh::new(positions, triangles).unwrap()
+    }
+
+    fn assert_single_span(mesh: &Mesh, ray: &Ray, enter: Float, exit: Float) {
+        let spans = mesh.spans(ray);
+        assert_eq!(spans.len(), 1, "{spans:?}");
+        assert!((spans[0].enter.t - enter).abs() < 1e-4, "{spans:?}");
+        assert!((spans[0].exit.t - exit).abs() < 1e-4, "{spans:?}");
+        assert!(spans[0].enter.normal.dot(ray.direction) < 0.);
+        assert!(spans[0].exit.normal.dot(ray.direction) > 0.);
+    }
+
+    #[test]
+    fn cube_faces_point_out() {
+        let mesh = cube();
+        for triangle in &mesh.triangles {
+            let [a, b, c] = triangle.map(|i| mesh.positions[i]);
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.);
+        }
+    }
+
+    #[test]
+    fn ray_through_an_edge() {
+        // through the diagonals splitting the front and the back face
+        let ray = Ray::new(Vector3::new(0.25, 0.25, 5.), Vector3::new(0., 0., -1.), 0.);
+        assert_single_span(&cube(), &ray, 4., 6.);
+
+        // along the edge between the top and the right face
+        let ray = Ray::new(Vector3::new(1., 1., 5.), Vector3::new(0., 0., -1.), 0.);
+        assert!(cube().spans(&ray).len() <= 1);
+    }
+
+    #[test]
+    fn ray_through_a_vertex() {
+        // in at one corner of the cube and out at the opposite one
+        let direction = Vector3::new(-1., -1., -1.).normalize();
+        let ray = Ray::new(Vector3::splat(3.), direction, 0.);
+        let corner = Float::sqrt(3.);
+        assert_single_span(&cube(), &ray, 3. * corner - corner, 3. * corner + corner);
+
+        // five triangles meet at every vertex of the icosahedron
+        let icosahedron = Mesh::icosahedron();
+        let vertex = icosahedron.positions[0];
+        let ray = Ray::new(vertex * 5., -vertex, 0.);
+        assert_single_span(&icosahedron, &ray, 4., 6.);
+    }
+
+    #[test]
+    fn csg_through_a_vertex() {
+        // the cube with its front half cut away by a bigger box
+        let shape = Shape::Csg {
+            operation: CsgOperation::Difference,
+            left: Box::new(Shape::Mesh(Arc::new(cube()))),
+            right: Box::new(Shape::Cuboid {
+                min: Vector3::new(-2., -2., 0.),
+                max: Vector3::splat(2.),
+            }),
+        };
+        let direction = Vector3::new(-1., -1., -1.).normalize();
+        let ray = Ray::new(Vector3::splat(3.), direction, 0.);
+        let corner = Float::sqrt(3.);
+
+        let spans = shape.spans(&ray);
+        assert_eq!(spans.len(), 1, "{spans:?}");
+        assert!((spans[0].enter.t - 3. * corner).abs() < 1e-4);
+        assert!((spans[0].exit.t - 4. * corner).abs() < 1e-4);
+    }
+}
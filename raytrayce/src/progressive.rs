@@ -0,0 +1,509 @@
+use image::RgbImage;
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    aov::{save_aovs, AovPixel, AovSample},
+    error::{Error, Result},
+    sampling::Rng,
+    stats::{save_heatmap, take_counts, RayCounts, RenderStats},
+    to_f32, to_rgb, Color, Float,
+};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"RTCK";
+/// Version 2 added the AOV flag and data, version 1 files still load.
+const CHECKPOINT_VERSION: u32 = 2;
+
+/// Running sum of radiance and the number of samples behind it, per pixel.
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    radiance: Vec<Color>,
+    samples: Vec<u32>,
+    /// Only kept when the AOVs were asked for.
+    aovs: Option<Vec<AovPixel>>,
+}
+
+/// Pixels of a `width` by `height` image, `None` when there are too many for the buffers
+/// of an `Accumulator` to be allocated.
+fn pixel_count(width: u32, height: u32) -> Option<usize> {
+    let size = usize::try_from(width)
+        .ok()?
+        .checked_mul(usize::try_from(height).ok()?)?;
+    // the AOVs take the most memory per pixel
+    let bytes = size.checked_mul(std::mem::size_of::<AovPixel>())?;
+    (bytes <= isize::MAX as usize).then_some(size)
+}
+
+impl Accumulator {
+    /// Fails when the image has too many pixels to even allocate the buffers.
+    pub fn new(width: u32, height: u32, aovs: bool) -> Result<Self> {
+        let size = pixel_count(width, height).ok_or(Error::ImageTooLarge { width, height })?;
+        Ok(Self {
+            width,
+            height,
+            radiance: vec![Color::BLACK; size],
+            samples: vec![0; size],
+            aovs: aovs.then(|| vec![AovPixel::default(); size]),
+        })
+    }
+
+    /// Whether the AOVs are accumulated too.
+    pub fn has_aovs(&self) -> bool {
+        self.aovs.is_some()
+    }
+
+    /// Smallest sample count of any pixel, the number of finished passes.
+    pub fn passes(&self) -> u32 {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        let mut image = RgbImage::new(self.width, self.height);
+
+        for (index, pixel) in image.pixels_mut().enumerate() {
+            let samples = self.samples[index].max(1) as Float;
+            *pixel = to_rgb(self.radiance[index] / samples);
+        }
+
+        image
+    }
+
+    /// Writes the AOVs next to the beauty image at `path`, if there are any.
+    pub fn save_aovs(&self, path: &Path) -> Result<()> {
+        match &self.aovs {
+            Some(aovs) => save_aovs(path, self.width, self.height, aovs, &self.samples),
+            None => Ok(()),
+        }
+    }
+
+    /// Little endian: magic, version, width, height, whether AOVs follow,
+    /// then r, g, b as Float, the sample count as u32 and the AOV sums for every pixel.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<()> {
+        self.write_checkpoint(path).map_err(Error::io(path))
+    }
+
+    pub fn load_checkpoint(path: &Path) -> Result<Self> {
+        Self::read_checkpoint(path).map_err(Error::io(path))
+    }
+
+    fn write_checkpoint(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        let has_aovs = u32::from(self.aovs.is_some());
+        for value in [CHECKPOINT_VERSION, self.width, self.height, has_aovs] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        for (index, (color, samples)) in self.radiance.iter().zip(&self.samples).enumerate() {
+            for channel in [color.r, color.g, color.b] {
+                writer.write_all(&to_f32(channel).to_le_bytes())?;
+            }
+            writer.write_all(&samples.to_le_bytes())?;
+
+            if let Some(aovs) = &self.aovs {
+                for word in aovs[index].to_words() {
+                    writer.write_all(&word.to_le_bytes())?;
+                }
+            }
+        }
+
+        writer.flush()
+    }
+
+    fn read_checkpoint(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_length = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut word = [0u8; 4];
+
+        reader.read_exact(&mut word)?;
+        if &word != CHECKPOINT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a render checkpoint",
+            ));
+        }
+
+        let mut read_u32 = |reader: &mut BufReader<File>| -> io::Result<u32> {
+            reader.read_exact(&mut word)?;
+            Ok(u32::from_le_bytes(word))
+        };
+
+        let version = read_u32(&mut reader)?;
+        if version == 0 || version > CHECKPOINT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported checkpoint version",
+            ));
+        }
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+        let has_aovs = version >= 2 && read_u32(&mut reader)? != 0;
+
+        // the header is trusted with nothing before the file proves to hold that many pixels
+        let header_words = if version >= 2 { 4 } else { 3 };
+        let pixel_words = 4 + if has_aovs { AovPixel::WORDS } else { 0 };
+        let size = pixel_count(width, height);
+        let expected_length = size.and_then(|size| {
+            u64::try_from(size)
+                .ok()?
+                .checked_mul(pixel_words as u64 * 4)?
+                .checked_add(CHECKPOINT_MAGIC.len() as u64 + header_words * 4)
+        });
+        let Some(size) = size.filter(|_| expected_length == Some(file_length)) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "checkpoint size does not match its header",
+            ));
+        };
+
+        let mut accumulator = Accumulator::new(width, height, has_aovs)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        for index in 0..size {
+            let r = Float::from(f32::from_bits(read_u32(&mut reader)?));
+            let g = Float::from(f32::from_bits(read_u32(&mut reader)?));
+            let b = Float::from(f32::from_bits(read_u32(&mut reader)?));
+            accumulator.radiance[index] = Color::new(r, g, b);
+            accumulator.samples[index] = read_u32(&mut reader)?;
+
+            if let Some(aovs) = &mut accumulator.aovs {
+                let mut words = [0; AovPixel::WORDS];
+                for word in &mut words {
+                    *word = read_u32(&mut reader)?;
+                }
+                aovs[index] = AovPixel::from_words(words);
+            }
+        }
+
+        Ok(accumulator)
+    }
+}
+
+pub struct ProgressiveSettings {
+    /// Stop once every pixel has this many samples.
+    pub max_samples: u32,
+    /// Stop once the limit is hit, checked before every row. The pass running then is
+    /// left partly done, a resumed render finishes it first.
+    pub time_limit: Option<Duration>,
+    /// How often the preview and the checkpoint are written.
+    pub checkpoint_interval: Duration,
+    pub preview_path: Option<PathBuf>,
+    pub checkpoint_path: Option<PathBuf>,
+    /// Where to write the per pixel cost of this run, passes loaded from a checkpoint are not included.
+    pub heatmap_path: Option<PathBuf>,
+}
+
+/// What `render_progressive` tells its caller while it runs.
+#[derive(Clone, Copy, Debug)]
+pub enum Progress {
+    /// Every pixel has `pass` samples out of `max_samples`, `elapsed` after the start.
+    PassDone {
+        pass: u32,
+        max_samples: u32,
+        elapsed: Duration,
+    },
+    /// The time limit ran out, no more passes follow.
+    TimeLimitReached,
+}
+
+/// Adds one sample per pixel and pass until the sample budget or the time limit runs out.
+/// `sample` traces a single sample for the pixel (x, y) of the image
+/// and reports what its camera ray hit for the AOVs, `progress` hears about every pass.
+pub fn render_progressive<F>(
+    accumulator: &mut Accumulator,
+    settings: &ProgressiveSettings,
+    sample: F,
+    mut progress: impl FnMut(Progress),
+) -> Result<RenderStats>
+where
+    F: Fn(u32, u32, &mut Rng) -> (Color, AovSample) + Sync,
+{
+    let start = Instant::now();
+    let deadline = settings.time_limit.map(|time_limit| start + time_limit);
+    let mut last_checkpoint = Instant::now();
+    let width = accumulator.width as usize;
+    let mut stats = RenderStats::default();
+    let mut cost = vec![0u64; accumulator.radiance.len()];
+
+    while accumulator.passes() < settings.max_samples {
+        let pass = accumulator.passes();
+
+        let mut aov_rows: Vec<Option<&mut [AovPixel]>> = match &mut accumulator.aovs {
+            Some(aovs) => aovs.chunks_mut(width).map(Some).collect(),
+            None => (0..accumulator.height).map(|_| None).collect(),
+        };
+
+        let pass_start = Instant::now();
+        let counts = accumulator
+            .radiance
+            .par_chunks_mut(width)
+            .zip(accumulator.samples.par_chunks_mut(width))
+            .zip(aov_rows.par_iter_mut())
+            .zip(cost.par_chunks_mut(width))
+            .enumerate()
+            .map(|(y, (((radiance_row, samples_row), aov_row), cost_row))| {
+                let mut row_counts = RayCounts::default();
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return row_counts;
+                }
+                // drop whatever this thread counted outside of sampling
+                take_counts();
+
+                for x in 0..width {
+                    if samples_row[x] > pass {
+                        continue;
+                    }
+
+                    // seeded by pixel and pass so a resumed render continues the same sequence
+                    let pixel = (y * width + x) as u64;
+                    let mut rng = Rng::new((u64::from(pass) << 32) | pixel);
+
+                    let (color, aov) = sample(x as u32, y as u32, &mut rng);
+                    radiance_row[x] += color;
+                    if let Some(aov_row) = aov_row.as_deref_mut() {
+                        aov_row[x].add(&aov, samples_row[x] == 0);
+                    }
+                    samples_row[x] += 1;
+
+                    let counts = take_counts();
+                    cost_row[x] += counts.cost();
+                    row_counts += counts;
+                }
+
+                row_counts
+            })
+            .reduce(RayCounts::default, |mut a, b| {
+                a += b;
+                a
+            });
+
+        stats.counts += counts;
+        stats.render_time += pass_start.elapsed();
+
+        let elapsed = start.elapsed();
+        if accumulator.passes() > pass {
+            stats.passes += 1;
+            progress(Progress::PassDone {
+                pass: pass + 1,
+                max_samples: settings.max_samples,
+                elapsed,
+            });
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            progress(Progress::TimeLimitReached);
+            break;
+        }
+
+        if last_checkpoint.elapsed() >= settings.checkpoint_interval {
+            let output_start = Instant::now();
+            write_progress(accumulator, settings)?;
+            stats.output_time += output_start.elapsed();
+            last_checkpoint = Instant::now();
+        }
+    }
+
+    let output_start = Instant::now();
+    write_progress(accumulator, settings)?;
+    if let Some(path) = &settings.heatmap_path {
+        save_heatmap(path, accumulator.width, accumulator.height, &cost)?;
+    }
+    stats.output_time += output_start.elapsed();
+
+    Ok(stats)
+}
+
+fn write_progress(accumulator: &Accumulator, settings: &ProgressiveSettings) -> Result<()> {
+    if let Some(path) = &settings.preview_path {
+        accumulator
+            .to_image()
+            .save(path)
+            .map_err(Error::image(path))?;
+    }
+
+    if let Some(path) = &settings.checkpoint_path {
+        accumulator.save_checkpoint(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector3;
+
+    fn settings(max_samples: u32) -> ProgressiveSettings {
+        ProgressiveSettings {
+            max_samples,
+            time_limit: None,
+            checkpoint_interval: Duration::ZERO,
+            preview_path: None,
+            checkpoint_path: None,
+            heatmap_path: None,
+        }
+    }
+
+    /// Noisy but reproducible samples, depending on the pixel and the random numbers.
+    fn sample(x: u32, y: u32, rng: &mut Rng) -> (Color, AovSample) {
+        let value = rng.next_float();
+        let color = Color::new(value, x as Float / 4., y as Float / 4.);
+        let aov = AovSample {
+            depth: 1. + value,
+            normal: Vector3::new(0., 1., 0.),
+            albedo: color,
+            object_id: x + 1,
+            direct: color,
+            indirect: Color::BLACK,
+        };
+        (color, aov)
+    }
+
+    fn render(accumulator: &mut Accumulator, max_samples: u32) {
+        render_progressive(accumulator, &settings(max_samples), sample, |_| {}).unwrap();
+    }
+
+    fn assert_same(a: &Accumulator, b: &Accumulator) {
+        assert_eq!((a.width, a.height), (b.width, b.height));
+        assert_eq!(a.samples, b.samples);
+        // the checkpoint keeps single precision radiance
+        for (a, b) in a.radiance.iter().zip(&b.radiance) {
+            let close = [a.r - b.r, a.g - b.g, a.b - b.b].map(|d| d.abs() < 1e-5);
+            assert!(close.iter().all(|&close| close), "{a:?} {b:?}");
+        }
+        let words = |accumulator: &Accumulator| {
+            accumulator.aovs.as_ref().map(|aovs| {
+                aovs.iter()
+                    .flat_map(|pixel| pixel.to_words())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let (a, b) = (words(a), words(b));
+        assert_eq!(a.is_some(), b.is_some());
+        // double precision renders resume from the single precision sums of the checkpoint,
+        // which may round off the last bit
+        let mut words = a.iter().flatten().zip(b.iter().flatten());
+        assert!(words.all(|(a, b)| a.abs_diff(*b) <= 1));
+    }
+
+    fn checkpoint_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytrayce-{name}.rtck"))
+    }
+
+    #[test]
+    fn checkpoints_round_trip() {
+        for aovs in [false, true] {
+            let mut accumulator = Accumulator::new(5, 3, aovs).unwrap();
+            render(&mut accumulator, 3);
+
+            let path = checkpoint_path(&format!("round-trip-{aovs}"));
+            accumulator.save_checkpoint(&path).unwrap();
+            let loaded = Accumulator::load_checkpoint(&path).unwrap();
+            fs_remove(&path);
+
+            assert_eq!(loaded.aovs.is_some(), aovs);
+            assert_eq!(loaded.passes(), 3);
+            assert_same(&loaded, &accumulator);
+        }
+    }
+
+    #[test]
+    fn resuming_continues_the_same_render() {
+        let mut straight = Accumulator::new(4, 4, true).unwrap();
+        render(&mut straight, 4);
+
+        let mut first = Accumulator::new(4, 4, true).unwrap();
+        render(&mut first, 2);
+        let path = checkpoint_path("resume");
+        first.save_checkpoint(&path).unwrap();
+        let mut resumed = Accumulator::load_checkpoint(&path).unwrap();
+        fs_remove(&path);
+        render(&mut resumed, 4);
+
+        assert_same(&resumed, &straight);
+    }
+
+    #[test]
+    fn time_limits_cut_passes_short() {
+        let mut straight = Accumulator::new(4, 16, true).unwrap();
+        render(&mut straight, 3);
+
+        // nothing at all when the time is up from the start
+        let mut accumulator = Accumulator::new(4, 16, true).unwrap();
+        let settings = ProgressiveSettings {
+            time_limit: Some(Duration::ZERO),
+            ..settings(3)
+        };
+        let mut reports = vec![];
+        let stats =
+            render_progressive(&mut accumulator, &settings, sample, |p| reports.push(p)).unwrap();
+        assert!(matches!(reports[..], [Progress::TimeLimitReached]));
+        assert_eq!(stats.passes, 0);
+        assert!(accumulator.samples.iter().all(|&samples| samples == 0));
+
+        // slow samples running out of time in the middle of some pass
+        let settings = ProgressiveSettings {
+            time_limit: Some(Duration::from_millis(20)),
+            ..settings
+        };
+        let slow = |x, y, rng: &mut Rng| {
+            std::thread::sleep(Duration::from_millis(2));
+            sample(x, y, rng)
+        };
+        let stats = render_progressive(&mut accumulator, &settings, slow, |_| {}).unwrap();
+        assert_eq!(stats.passes, accumulator.passes());
+        assert!(accumulator
+            .samples
+            .iter()
+            .all(|&samples| samples <= stats.passes + 1));
+
+        render(&mut accumulator, 3);
+        assert_same(&accumulator, &straight);
+    }
+
+    #[test]
+    fn images_too_large_to_allocate_are_errors() {
+        assert!(matches!(
+            Accumulator::new(u32::MAX, u32::MAX, false),
+            Err(Error::ImageTooLarge { .. })
+        ));
+        assert!(Accumulator::new(0, 0, true).is_ok());
+    }
+
+    #[test]
+    fn version_1_checkpoints_still_load() {
+        let path = checkpoint_path("version-1");
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        for word in [1u32, 2, 1] {
+            bytes.extend(word.to_le_bytes());
+        }
+        for (value, samples) in [(0.5f32, 2u32), (0.25, 3)] {
+            for _ in 0..3 {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend(samples.to_le_bytes());
+        }
+        std::fs::write(&path, &bytes).unwrap();
+
+        let loaded = Accumulator::load_checkpoint(&path).unwrap();
+        assert!(loaded.aovs.is_none());
+        assert_eq!(loaded.samples, [2, 3]);
+        assert_eq!(loaded.radiance[1], Color::new(0.25, 0.25, 0.25));
+
+        // one pixel short
+        std::fs::write(&path, &bytes[..bytes.len() - 16]).unwrap();
+        assert!(Accumulator::load_checkpoint(&path).is_err());
+        fs_remove(&path);
+    }
+
+    fn fs_remove(path: &Path) {
+        std::fs::remove_file(path).unwrap();
+    }
+}
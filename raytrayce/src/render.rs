@@ -0,0 +1,616 @@
+use std::time::Duration;
+
+use crate::{
+    aov::AovSample,
+    camera::{Camera, PROJECTION_PLANE_Z, VIEWPORT_SIZE},
+    consts::PI,
+    debugger::{HitRecord, LightSample, RayRecord, Recorder},
+    error::{Error, Result},
+    progressive::{render_progressive, Accumulator, ProgressiveSettings},
+    sampling::{sample_cosine_direction, Rng},
+    scene::{LightType, Object, Scene},
+    shape::SurfaceHit,
+    stats::{record_ray, RenderStats},
+    to_color, Color, Float, Ray, Vector3,
+};
+
+/// Light that does not arrive, the contribution of what is not there.
+const BACKGROUND_COLOR: Color = Color::BLACK;
+
+/// Why a ray was traced, for the statistics and the debugger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayKind {
+    Camera,
+    Reflection,
+    /// Bounce off a diffuse surface in path tracing mode.
+    Diffuse,
+    Shadow,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    /// Direct lights and mirror reflections only, ambient light stands in for the rest.
+    Whitted,
+    /// Adds importance sampled light from the environment and diffuse interreflection.
+    PathTrace,
+}
+
+/// What to render, independent of the scene.
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Samples per pixel, a single one goes through the pixel center.
+    pub samples: u32,
+    /// How many times a ray may bounce, off mirrors or diffuse surfaces.
+    pub max_depth: u32,
+    pub mode: RenderMode,
+    /// Collect depth, normal, albedo, object id and the direct and indirect light.
+    pub aovs: bool,
+    /// Render only this part of the `width` by `height` image.
+    pub crop: Option<CropRegion>,
+}
+
+/// Rectangle of image pixels, `x` and `y` being its top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RenderSettings {
+    /// Size of the rendered image, the crop region when there is one.
+    pub fn output_size(&self) -> (u32, u32) {
+        match self.crop {
+            Some(crop) => (crop.width, crop.height),
+            None => (self.width, self.height),
+        }
+    }
+
+    /// Fails on a crop region that is empty or reaches outside the image.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(crop) = self.crop {
+            if crop.width == 0
+                || crop.height == 0
+                || crop.x.saturating_add(crop.width) > self.width
+                || crop.y.saturating_add(crop.height) > self.height
+            {
+                return Err(Error::InvalidSettings {
+                    message: "the crop region is empty or reaches outside the image",
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 1500,
+            height: 1500,
+            samples: 16,
+            max_depth: 3,
+            mode: RenderMode::Whitted,
+            aovs: false,
+            crop: None,
+        }
+    }
+}
+
+/// Renders the image, or its crop region, in memory, `settings.samples` samples for every pixel.
+/// The accumulator holds the image and the AOVs,
+/// `render_progressive` adds previews, checkpoints and time limits.
+/// Fails on scenes `Scene::validate` rejects, settings `RenderSettings::validate` does
+/// and images too large to hold.
+pub fn render(
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> Result<(Accumulator, RenderStats)> {
+    scene.validate()?;
+    settings.validate()?;
+
+    let (width, height) = settings.output_size();
+    let mut accumulator = Accumulator::new(width, height, settings.aovs)?;
+    let progressive = ProgressiveSettings {
+        max_samples: settings.samples,
+        time_limit: None,
+        checkpoint_interval: Duration::MAX,
+        preview_path: None,
+        checkpoint_path: None,
+        heatmap_path: None,
+    };
+
+    let stats = render_progressive(
+        &mut accumulator,
+        &progressive,
+        |x, y, rng| render_sample(scene, camera, settings, x, y, rng),
+        |_| {},
+    )?;
+
+    Ok((accumulator, stats))
+}
+
+/// One sample of the pixel (x, y), counted from the corner of the crop region when there
+/// is one, with what its camera ray hit for the AOVs.
+/// The sample position inside the pixel, the lens point and the time come from `rng`.
+pub fn render_sample(
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    x: u32,
+    y: u32,
+    rng: &mut Rng,
+) -> (Color, AovSample) {
+    trace_sample(scene, camera, settings, x, y, rng, None)
+}
+
+/// `render_sample` that also records every ray it traces, for the debugger.
+/// Returns the colour of the sample and the tree of rays behind it, see `debugger::to_text`.
+/// Fails on settings `RenderSettings::validate` rejects and pixels outside the image.
+pub fn debug_sample(
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    x: u32,
+    y: u32,
+    rng: &mut Rng,
+) -> Result<(Color, Vec<RayRecord>)> {
+    settings.validate()?;
+    let (width, height) = settings.output_size();
+    if x >= width || y >= height {
+        return Err(Error::InvalidSettings {
+            message: "the debugged pixel is outside the image",
+        });
+    }
+
+    let mut recorder = Recorder::default();
+    let (color, _) = trace_sample(scene, camera, settings, x, y, rng, Some(&mut recorder));
+    Ok((color, recorder.finish()))
+}
+
+fn trace_sample(
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    x: u32,
+    y: u32,
+    rng: &mut Rng,
+    recorder: Option<&mut Recorder>,
+) -> (Color, AovSample) {
+    let (x, y) = match settings.crop {
+        Some(crop) => (x + crop.x, y + crop.y),
+        None => (x, y),
+    };
+    let x = x as Float - (settings.width / 2) as Float;
+    let y = (settings.height / 2) as Float - y as Float;
+
+    // with a single sample keep shooting through the pixel center
+    let (dx, dy) = if settings.samples > 1 {
+        (rng.next_float() - 0.5, rng.next_float() - 0.5)
+    } else {
+        (0., 0.)
+    };
+
+    let viewport = canvas_to_viewport(x + dx, y + dy, settings);
+    let ray = camera.ray(viewport, rng);
+    let mut aov = AovSample::default();
+    let mut trace = Trace {
+        scene,
+        mode: settings.mode,
+        rng,
+        recorder,
+    };
+    let color = trace_ray(
+        &mut trace,
+        &ray,
+        RayKind::Camera,
+        settings.max_depth,
+        Some(&mut aov),
+    );
+    (color, aov)
+}
+
+/// What every ray traced for one sample shares.
+struct Trace<'a> {
+    scene: &'a Scene,
+    mode: RenderMode,
+    rng: &'a mut Rng,
+    /// Only there when the sample is being debugged.
+    recorder: Option<&'a mut Recorder>,
+}
+
+impl Trace<'_> {
+    fn record(&mut self, record: impl FnOnce(&mut Recorder)) {
+        if let Some(recorder) = self.recorder.as_deref_mut() {
+            record(recorder);
+        }
+    }
+}
+
+/// Image coordinates around the center, y up, to the projection plane.
+/// The viewport is `VIEWPORT_SIZE` wide and pixels are square.
+fn canvas_to_viewport(x: Float, y: Float, settings: &RenderSettings) -> Vector3 {
+    let width = settings.width as Float;
+    Vector3::new(
+        x * VIEWPORT_SIZE / width,
+        y * VIEWPORT_SIZE / width,
+        PROJECTION_PLANE_Z,
+    )
+}
+
+fn closest_intersection<'a>(
+    scene: &'a Scene,
+    ray: &Ray,
+    kind: RayKind,
+    t_min: Float,
+    t_max: Float,
+) -> Option<(usize, &'a Object, SurfaceHit)> {
+    let mut closest: Option<(usize, &Object, SurfaceHit)> = None;
+    record_ray(kind);
+
+    scene.bvh.traverse(ray, t_min, t_max, |index, t_max| {
+        let object = &scene.objects[index];
+        let hit = object.hit(ray, t_min, t_max)?;
+        closest = Some((index, object, hit));
+        Some(hit.t)
+    });
+
+    closest
+}
+
+/// Start of a ray leaving the surface at `position` towards `direction`.
+/// It is pushed along the normal past the rounding error of the hit, so the new ray
+/// can not find the surface it starts on again and needs no epsilon for its `t_min`.
+fn offset_ray_origin(
+    position: Vector3,
+    normal: Vector3,
+    error: Float,
+    direction: Vector3,
+) -> Vector3 {
+    let offset = if normal.dot(direction) < 0. {
+        normal * -error
+    } else {
+        normal * error
+    };
+    let mut origin = position + offset;
+
+    // the addition rounds as well, one more float away from the surface covers that
+    for axis in 0..3 {
+        if offset[axis] > 0. {
+            origin[axis] = origin[axis].next_up();
+        } else if offset[axis] < 0. {
+            origin[axis] = origin[axis].next_down();
+        }
+    }
+    origin
+}
+
+/// Follows `ray` and returns the light coming back along it, `aov` is filled in
+/// with what the ray hit for camera rays that want the AOVs.
+/// Camera rays start at the projection plane, the others at their origin.
+fn trace_ray(
+    trace: &mut Trace,
+    ray: &Ray,
+    kind: RayKind,
+    rec_depth: u32,
+    aov: Option<&mut AovSample>,
+) -> Color {
+    let scene = trace.scene;
+    let direction = ray.direction;
+    let t_min = if kind == RayKind::Camera { 1. } else { 0. };
+    trace.record(|recorder| recorder.begin_ray(ray, kind, t_min, Float::INFINITY));
+
+    let color = match closest_intersection(scene, ray, kind, t_min, Float::INFINITY) {
+        Some((index, object, hit)) => {
+            let position = ray.at(hit.t);
+            let normal = hit.normal;
+            let uv = hit.uv;
+            let local = object.local_position(ray, hit.t);
+            let material = &object.material;
+            let specular = material.specular.sample(uv, local).round() as i32;
+            let lightning_koef = compute_lightning(
+                trace, position, normal, hit.error, -direction, specular, ray.time,
+            );
+            let albedo = to_color(material.albedo.sample(uv, local));
+            let reflective = if rec_depth > 0 {
+                material.reflective.sample(uv, local).max(0.)
+            } else {
+                0.
+            };
+            trace.record(|recorder| {
+                recorder.record_hit(HitRecord {
+                    object: index,
+                    t: hit.t,
+                    position,
+                    normal,
+                    uv,
+                    albedo,
+                    specular,
+                    reflective,
+                })
+            });
+
+            let mut direct = albedo * lightning_koef;
+            let mut indirect = BACKGROUND_COLOR;
+
+            match trace.mode {
+                RenderMode::Whitted => {
+                    indirect = albedo * compute_ambient_lightning(trace);
+                }
+                RenderMode::PathTrace => {
+                    let environment =
+                        compute_environment_lightning(trace, position, normal, hit.error, ray.time);
+                    direct += albedo * environment;
+
+                    if rec_depth > 0 {
+                        let bounce_direction = sample_cosine_direction(trace.rng, normal);
+                        let bounce_ray = Ray::new(
+                            offset_ray_origin(position, normal, hit.error, bounce_direction),
+                            bounce_direction,
+                            ray.time,
+                        );
+                        // cosine density cancels out with the lambertian term
+                        let bounced =
+                            trace_ray(trace, &bounce_ray, RayKind::Diffuse, rec_depth - 1, None);
+                        indirect = albedo * bounced;
+                    }
+                }
+            }
+
+            let mut color = direct + indirect;
+            if reflective > 0. {
+                let reflect_ray = math::reflect(-direction, normal);
+                let reflected_ray = Ray::new(
+                    offset_ray_origin(position, normal, hit.error, reflect_ray),
+                    reflect_ray,
+                    ray.time,
+                );
+                let reflected_color = trace_ray(
+                    trace,
+                    &reflected_ray,
+                    RayKind::Reflection,
+                    rec_depth - 1,
+                    None,
+                );
+
+                color = color * (1. - reflective) + reflected_color * reflective;
+            }
+
+            if let Some(aov) = aov {
+                let direct = direct * (1. - reflective);
+                *aov = AovSample {
+                    // camera rays reach the projection plane at t = 1
+                    depth: hit.t * PROJECTION_PLANE_Z,
+                    normal,
+                    albedo,
+                    object_id: index as u32 + 1,
+                    direct,
+                    indirect: color - direct,
+                };
+            }
+
+            color
+        }
+        None => {
+            // the environment reached by diffuse bounces is already sampled directly
+            let color = if kind == RayKind::Diffuse {
+                BACKGROUND_COLOR
+            } else {
+                scene.environment.lookup(direction)
+            };
+
+            if let Some(aov) = aov {
+                *aov = AovSample {
+                    direct: color,
+                    ..AovSample::default()
+                };
+            }
+
+            color
+        }
+    };
+
+    trace.record(|recorder| recorder.end_ray(color));
+    color
+}
+
+/// Ambient light stands in for all indirect light in Whitted mode.
+fn compute_ambient_lightning(trace: &mut Trace) -> Float {
+    let mut i = 0.;
+    for (index, light) in trace.scene.lights.iter().enumerate() {
+        if light.light_type == LightType::Ambient {
+            trace.record(|recorder| {
+                recorder.record_light(LightSample::Ambient {
+                    light: index,
+                    intensity: light.intensity,
+                })
+            });
+            i += light.intensity;
+        }
+    }
+
+    i
+}
+
+/// Light from one importance sampled direction of the environment,
+/// already divided by its density and weighted for a lambertian surface.
+fn compute_environment_lightning(
+    trace: &mut Trace,
+    position: Vector3,
+    normal: Vector3,
+    error: Float,
+    time: Float,
+) -> Color {
+    let scene = trace.scene;
+    let (direction, pdf) = scene.environment.sample(trace.rng);
+    let normal_dot_direction = normal.dot(direction);
+    if normal_dot_direction <= 0. || pdf <= 0. {
+        return BACKGROUND_COLOR;
+    }
+
+    let shadow_ray = Ray::new(
+        offset_ray_origin(position, normal, error, direction),
+        direction,
+        time,
+    );
+    if let Some((occluder, ..)) =
+        closest_intersection(scene, &shadow_ray, RayKind::Shadow, 0., Float::INFINITY)
+    {
+        trace.record(|recorder| {
+            recorder.record_light(LightSample::Environment {
+                direction,
+                pdf,
+                occluder: Some(occluder),
+                radiance: BACKGROUND_COLOR,
+            })
+        });
+        return BACKGROUND_COLOR;
+    }
+
+    let radiance = scene.environment.lookup(direction);
+    trace.record(|recorder| {
+        recorder.record_light(LightSample::Environment {
+            direction,
+            pdf,
+            occluder: None,
+            radiance,
+        })
+    });
+
+    radiance * (normal_dot_direction / (PI * pdf))
+}
+
+fn compute_lightning(
+    trace: &mut Trace,
+    position: Vector3,
+    normal: Vector3,
+    error: Float,
+    vector: Vector3,
+    specular: i32,
+    time: Float,
+) -> Float {
+    let scene = trace.scene;
+    let mut i = 0.;
+    for (index, light) in scene.lights.iter().enumerate() {
+        let (light_direction, t_max) = match (light.light_type, light.direction) {
+            // indirect light, see `compute_ambient_lightning`
+            (LightType::Ambient, _) => continue,
+            (LightType::Point, Some(light_position)) => (light_position - position, 1.),
+            (LightType::Directional, Some(direction)) => (direction, Float::INFINITY),
+            // rejected by `Scene::validate`
+            (_, None) => continue,
+        };
+
+        let origin = offset_ray_origin(position, normal, error, light_direction);
+        // a point light stays at t = 1 from the moved origin
+        let shadow_direction = match (light.light_type, light.direction) {
+            (LightType::Point, Some(light_position)) => light_position - origin,
+            _ => light_direction,
+        };
+        let shadow_ray = Ray::new(origin, shadow_direction, time);
+        if let Some((occluder, ..)) =
+            closest_intersection(scene, &shadow_ray, RayKind::Shadow, 0., t_max)
+        {
+            trace.record(|recorder| {
+                recorder.record_light(LightSample::Direct {
+                    light: index,
+                    direction: light_direction,
+                    occluder: Some(occluder),
+                    diffuse: 0.,
+                    specular: 0.,
+                })
+            });
+            continue;
+        }
+
+        let diffuse_i = math::diffuse(normal, light_direction, light.intensity);
+        let specular_i = if specular != -1 {
+            math::specular(
+                normal,
+                light_direction,
+                vector,
+                specular as Float,
+                light.intensity,
+            )
+        } else {
+            0.
+        };
+
+        trace.record(|recorder| {
+            recorder.record_light(LightSample::Direct {
+                light: index,
+                direction: light_direction,
+                occluder: None,
+                diffuse: diffuse_i,
+                specular: specular_i,
+            })
+        });
+        i += diffuse_i + specular_i;
+    }
+
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo;
+
+    fn cropped(x: u32, y: u32, width: u32, height: u32) -> RenderSettings {
+        RenderSettings {
+            width: 40,
+            height: 30,
+            samples: 1,
+            crop: Some(CropRegion {
+                x,
+                y,
+                width,
+                height,
+            }),
+            ..RenderSettings::default()
+        }
+    }
+
+    fn is_invalid<T>(result: Result<T>) -> bool {
+        matches!(result, Err(Error::InvalidSettings { .. }))
+    }
+
+    #[test]
+    fn crop_regions_stay_inside_the_image() {
+        assert!(cropped(0, 0, 40, 30).validate().is_ok());
+        assert!(cropped(30, 20, 10, 10).validate().is_ok());
+
+        for settings in [
+            cropped(0, 0, 0, 10),
+            cropped(0, 0, 10, 0),
+            cropped(31, 0, 10, 10),
+            cropped(0, 21, 10, 10),
+            cropped(u32::MAX, 0, 10, 10),
+        ] {
+            assert!(is_invalid(settings.validate()), "{:?}", settings.crop);
+        }
+    }
+
+    #[test]
+    fn entry_points_validate_their_settings() {
+        let camera = demo::camera();
+        let scene = demo::scene(&camera);
+
+        assert!(is_invalid(render(&scene, &camera, &cropped(35, 0, 10, 10))));
+
+        // the debugged pixel counts from the corner of the crop region
+        let settings = cropped(30, 20, 10, 5);
+        let mut rng = Rng::new(0);
+        assert!(debug_sample(&scene, &camera, &settings, 9, 4, &mut rng).is_ok());
+        assert!(is_invalid(debug_sample(
+            &scene, &camera, &settings, 10, 0, &mut rng
+        )));
+        assert!(is_invalid(debug_sample(
+            &scene, &camera, &settings, 0, 5, &mut rng
+        )));
+    }
+}
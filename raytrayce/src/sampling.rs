@@ -0,0 +1,88 @@
+use crate::{consts::PI, Float, Vector3};
+
+/// Small xorshift64* generator, good enough for picking sample positions
+/// and reproducible between runs for the same seed.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 step so neighbouring seeds do not give correlated sequences
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        Self { state: z | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in [0, 1).
+    pub fn next_float(&mut self) -> Float {
+        (self.next_u64() >> 40) as Float / (1u64 << 24) as Float
+    }
+}
+
+/// Uniform point on the unit disk, concentric mapping.
+pub fn sample_unit_disk(rng: &mut Rng) -> (Float, Float) {
+    let a = 2. * rng.next_float() - 1.;
+    let b = 2. * rng.next_float() - 1.;
+
+    if a == 0. && b == 0. {
+        return (0., 0.);
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4. * (b / a))
+    } else {
+        (b, PI / 2. - PI / 4. * (a / b))
+    };
+
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Uniform point inside a regular polygon inscribed in the unit circle.
+pub fn sample_polygon(rng: &mut Rng, blades: u32, rotation: Float) -> (Float, Float) {
+    let blades = blades.max(3);
+    let wedge = (rng.next_float() * blades as Float) as u32 % blades;
+
+    let angle = 2. * PI / blades as Float;
+    let a0 = rotation + wedge as Float * angle;
+    let a1 = a0 + angle;
+
+    // uniform point in the triangle (center, v0, v1)
+    let mut s = rng.next_float();
+    let mut t = rng.next_float();
+    if s + t > 1. {
+        s = 1. - s;
+        t = 1. - t;
+    }
+
+    (s * a0.cos() + t * a1.cos(), s * a0.sin() + t * a1.sin())
+}
+
+/// Direction around `normal` with a density proportional to the cosine
+/// of the angle to it, for bouncing off diffuse surfaces.
+pub fn sample_cosine_direction(rng: &mut Rng, normal: Vector3) -> Vector3 {
+    let (x, y) = sample_unit_disk(rng);
+    let z = (1. - x * x - y * y).max(0.).sqrt();
+
+    // any two vectors perpendicular to the normal and to each other
+    let helper = if normal.x.abs() > 0.9 {
+        Vector3::new(0., 1., 0.)
+    } else {
+        Vector3::new(1., 0., 0.)
+    };
+    let tangent = helper.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+
+    tangent * x + bitangent * y + normal * z
+}
@@ -0,0 +1,195 @@
+use crate::{
+    bvh::Bvh,
+    camera::Camera,
+    environment::Environment,
+    error::{Error, Result},
+    material::Material,
+    shape::{rounding_error, Shape, SurfaceHit},
+    Aabb, Float, Ray, Transform, Vector3,
+};
+
+/// A shape placed in the world with the material it is made of.
+#[derive(Clone)]
+pub struct Object {
+    pub shape: Shape,
+    /// Places the shape in the world, without one it stays where it was defined.
+    pub transform: Option<Transform>,
+    /// Units travelled per unit of shutter time, the shape is where it was defined at time 0.
+    pub velocity: Vector3,
+    pub material: Material,
+}
+
+impl Object {
+    pub fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<SurfaceHit> {
+        let origin = ray.origin - self.velocity * ray.time;
+        let mut hit = match &self.transform {
+            Some(transform) => {
+                // the direction is left unnormalized so t means the same in both spaces
+                let local = Ray::new(
+                    transform.inverse_point(origin),
+                    transform.inverse_vector(ray.direction),
+                    ray.time,
+                );
+                let mut hit = self.shape.hit(&local, t_min, t_max)?;
+                hit.normal = transform.normal(hit.normal).normalize();
+                hit.error *= transform.stretch_bound();
+                hit
+            }
+            None => self
+                .shape
+                .hit(&Ray::new(origin, ray.direction, ray.time), t_min, t_max)?,
+        };
+
+        // the caller finds the point again from the world space ray
+        hit.error += rounding_error(ray.origin.max_abs() + (ray.direction * hit.t).max_abs());
+        Some(hit)
+    }
+
+    /// Where the world position `ray.at(t)` is on the shape as it was defined, before the
+    /// transform places it and the velocity moves it. Solid textures are looked up there,
+    /// so they stay on the object wherever it goes.
+    pub fn local_position(&self, ray: &Ray, t: Float) -> Vector3 {
+        let position = ray.at(t) - self.velocity * ray.time;
+        match &self.transform {
+            Some(transform) => transform.inverse_point(position),
+            None => position,
+        }
+    }
+
+    /// Everywhere the object is while the shutter is open.
+    pub fn bounds(&self, shutter_open: Float, shutter_close: Float) -> Aabb {
+        let bounds = match &self.transform {
+            Some(transform) => self.shape.bounds().transform(transform),
+            None => self.shape.bounds(),
+        };
+
+        bounds
+            .translate(self.velocity * shutter_open)
+            .union(&bounds.translate(self.velocity * shutter_close))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightType {
+    /// Reaches every point evenly, stands in for indirect light in Whitted mode.
+    Ambient,
+    /// Sits at `direction`, which is a position for this type.
+    Point,
+    /// Comes from infinitely far away along `direction`.
+    Directional,
+}
+
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub intensity: Float,
+    pub light_type: LightType,
+    /// Position of point lights, direction towards directional lights, `None` for ambient ones.
+    pub direction: Option<Vector3>,
+}
+
+/// Everything a render needs besides the camera.
+pub struct Scene {
+    pub objects: Vec<Object>,
+    pub lights: Vec<Light>,
+    /// What rays see when they leave the scene.
+    pub environment: Environment,
+    /// Over the object bounds, objects must not move or change shape after `Scene::new`.
+    pub(crate) bvh: Bvh,
+}
+
+impl Scene {
+    /// Builds the acceleration structure over `objects` as they are while
+    /// the shutter of `camera` is open. Fails on lights `validate` rejects.
+    pub fn new(
+        objects: Vec<Object>,
+        lights: Vec<Light>,
+        environment: Environment,
+        camera: &Camera,
+    ) -> Result<Self> {
+        let bounds: Vec<Aabb> = objects
+            .iter()
+            .map(|object| object.bounds(camera.shutter_open, camera.shutter_close))
+            .collect();
+
+        let scene = Self {
+            bvh: Bvh::build(&bounds),
+            objects,
+            lights,
+            environment,
+        };
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Point lights need a position and directional lights a direction,
+    /// intensities must be finite and not negative.
+    /// Texture and environment images need at least one pixel.
+    pub fn validate(&self) -> Result<()> {
+        if self.environment.has_empty_image()
+            || self
+                .objects
+                .iter()
+                .any(|object| object.material.has_empty_image())
+        {
+            return Err(Error::EmptyImage { path: None });
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let invalid = |message| Err(Error::InvalidLight { index, message });
+
+            if !light.intensity.is_finite() || light.intensity < 0. {
+                return invalid("intensity must be finite and not negative");
+            }
+            match (light.light_type, light.direction) {
+                (LightType::Point, None) => return invalid("point light without a position"),
+                (LightType::Directional, None) => {
+                    return invalid("directional light without a direction")
+                }
+                (LightType::Directional, Some(direction)) if direction == Vector3::ZERO => {
+                    return invalid("directional light with a zero direction")
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Channel, texture::Texture};
+    use image::Rgb;
+
+    #[test]
+    fn solid_textures_move_with_their_object() {
+        let object = Object {
+            shape: Shape::Sphere {
+                center: Vector3::ZERO,
+                radius: 1.,
+            },
+            transform: Some(
+                Transform::scaling(Vector3::splat(2.))
+                    .then(&Transform::translation(Vector3::new(3., 0., 0.))),
+            ),
+            velocity: Vector3::new(0., 1., 0.),
+            material: Material {
+                albedo: Texture::Solid(Rgb([255, 255, 255])),
+                specular: Channel::Constant(-1.),
+                reflective: Channel::Constant(0.),
+            },
+        };
+
+        // the top of the sphere, wherever the shutter catches it
+        for time in [0., 0.5, 1.] {
+            let ray = Ray::new(Vector3::new(3., 10., 0.), Vector3::new(0., -1., 0.), time);
+            let hit = object.hit(&ray, 0., Float::INFINITY).unwrap();
+            let local = object.local_position(&ray, hit.t);
+            assert!(
+                (local - Vector3::new(0., 1., 0.)).length() < 1e-4,
+                "{local:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! Plain text scenes, one camera, light, environment or object per line.
+//! Every line starts with a keyword followed by properties, `#` starts a comment:
+//!
+//! ```text
+//! camera position 0 0 0 yaw 0 pitch 0 aperture 0.1 focus 4 shutter 0 1 blades 6
+//! environment color 0.1 0.1 0.2          # or: environment map sky.hdr
+//! light ambient 0.2
+//! light point 0.6 2 1 0                  # intensity, then the position
+//! light directional 0.2 1 4 4            # intensity, then the direction towards the light
+//! sphere center 0 -1 3 radius 1 color 255 0 0 specular 500 reflective 0.2
+//! box min -0.25 -0.25 -0.25 max 0.25 0.25 0.25 rotate_y 45 translate -1.3 -0.75 2.4
+//! mesh part.obj scale 0.1 0.1 0.1 marble 230 230 255 0 0 160 4 6
+//! icosahedron translate 0 0 5 velocity 0 0.25 0
+//! ```
+//!
+//! Spheres need a positive radius, boxes a `min` corner below `max` on every axis, and the
+//! shutter may not close before it opens.
+//!
+//! Objects take, after their shape:
+//! - an albedo: `color R G B`, `checker R G B R G B SCALE`, `stripes R G B R G B SCALE`,
+//!   `noise R G B SCALE`, `marble R G B R G B SCALE TURBULENCE` or
+//!   `image PATH repeat|clamp|mirror`, white without one, with non zero scales,
+//! - `specular EXPONENT`, -1 (the default) for matte surfaces, and `reflective AMOUNT`,
+//! - `scale X Y Z` with non zero factors, `rotate_x DEGREES`, `rotate_y DEGREES`,
+//!   `rotate_z DEGREES` and `translate X Y Z`, applied in the order they are written,
+//! - `velocity X Y Z`.
+//!
+//! Paths are relative to the scene file, OBJ meshes named more than once are loaded once
+//! and shared. Without a `camera` line the camera is a pinhole at the origin looking down +z.
+
+use image::Rgb;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::SplitWhitespace,
+    sync::Arc,
+};
+
+use crate::{
+    camera::{Bokeh, Camera},
+    environment::Environment,
+    error::Error,
+    material::{Channel, Material},
+    mesh::Mesh,
+    scene::{Light, LightType, Object, Scene},
+    shape::Shape,
+    texture::{Texture, WrapMode},
+    Color, Float, Transform, Vector3,
+};
+
+/// Reads the scene at `path` along with the camera it is seen through.
+pub fn load(path: &Path) -> Result<(Scene, Camera), Error> {
+    let source = fs::read_to_string(path).map_err(Error::io(path))?;
+
+    let mut loader = Loader {
+        directory: path.parent().unwrap_or(Path::new("")),
+        meshes: HashMap::new(),
+    };
+    let mut camera = pinhole();
+    let mut objects = vec![];
+    let mut lights = vec![];
+    let mut light_lines = vec![];
+    let mut environment = Environment::Color(Color::BLACK);
+
+    for (line_number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = Words(line.split_whitespace());
+        let Some(keyword) = words.0.next() else {
+            continue;
+        };
+
+        let result = match keyword {
+            "camera" => parse_camera(&mut words).map(|parsed| camera = parsed),
+            "light" => parse_light(&mut words).map(|light| {
+                lights.push(light);
+                light_lines.push(line_number + 1);
+            }),
+            "environment" => loader
+                .parse_environment(&mut words)
+                .map(|parsed| environment = parsed),
+            "sphere" | "box" | "mesh" | "icosahedron" => loader
+                .parse_object(keyword, &mut words)
+                .map(|object| objects.push(object)),
+            _ => Err(format!("unknown keyword `{keyword}`").into()),
+        };
+        result.map_err(|error| match error {
+            LineError::Syntax(message) => Error::BadScene {
+                path: path.to_path_buf(),
+                line: line_number + 1,
+                message,
+            },
+            LineError::Load(error) => error,
+        })?;
+    }
+
+    // point a light the scene rejects at its line
+    let scene = Scene::new(objects, lights, environment, &camera).map_err(|error| match error {
+        Error::InvalidLight { index, message } => Error::BadScene {
+            path: path.to_path_buf(),
+            line: light_lines[index],
+            message: message.to_string(),
+        },
+        error => error,
+    })?;
+    Ok((scene, camera))
+}
+
+/// What went wrong on a line: bad syntax, which still needs the file and line number,
+/// or a file it names that did not load.
+enum LineError {
+    Syntax(String),
+    Load(Error),
+}
+
+impl From<String> for LineError {
+    fn from(message: String) -> Self {
+        LineError::Syntax(message)
+    }
+}
+
+impl From<Error> for LineError {
+    fn from(error: Error) -> Self {
+        LineError::Load(error)
+    }
+}
+
+type LineResult<T> = Result<T, LineError>;
+
+/// The words of one line, parsed on demand.
+struct Words<'a>(SplitWhitespace<'a>);
+
+impl<'a> Words<'a> {
+    fn word(&mut self, what: &str) -> Result<&'a str, String> {
+        self.0.next().ok_or_else(|| format!("missing {what}"))
+    }
+
+    fn float(&mut self, what: &str) -> Result<Float, String> {
+        let word = self.word(what)?;
+        word.parse()
+            .map_err(|_| format!("expected a number for {what}, found `{word}`"))
+    }
+
+    fn vector(&mut self, what: &str) -> Result<Vector3, String> {
+        Ok(Vector3::new(
+            self.float(what)?,
+            self.float(what)?,
+            self.float(what)?,
+        ))
+    }
+
+    /// Size of a pattern, which is divided by.
+    fn pattern_scale(&mut self, what: &str) -> Result<Float, String> {
+        let scale = self.float(what)?;
+        if scale == 0. || !scale.is_finite() {
+            return Err(format!("{what} must be finite and non zero, found {scale}"));
+        }
+        Ok(scale)
+    }
+
+    fn rgb(&mut self, what: &str) -> Result<Rgb<u8>, String> {
+        let mut channel = || {
+            let word = self.word(what)?;
+            word.parse().map_err(|_| {
+                format!("expected a colour channel in 0..=255 for {what}, found `{word}`")
+            })
+        };
+        Ok(Rgb([channel()?, channel()?, channel()?]))
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        match self.0.next() {
+            Some(word) => Err(format!("unexpected `{word}`")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// At the origin looking down +z, with the shutter closed.
+fn pinhole() -> Camera {
+    Camera {
+        position: Vector3::ZERO,
+        rotation: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+        aperture: 0.,
+        focus_distance: 1.,
+        bokeh: Bokeh::Circle,
+        shutter_open: 0.,
+        shutter_close: 0.,
+    }
+}
+
+fn parse_camera(words: &mut Words) -> LineResult<Camera> {
+    let mut camera = pinhole();
+    let (mut yaw, mut pitch): (Float, Float) = (0., 0.);
+
+    while let Some(key) = words.0.next() {
+        match key {
+            "position" => camera.position = words.vector("camera position")?,
+            "yaw" => yaw = words.float("camera yaw")?,
+            "pitch" => pitch = words.float("camera pitch")?,
+            "aperture" => camera.aperture = words.float("aperture")?,
+            "focus" => camera.focus_distance = words.float("focus distance")?,
+            "shutter" => {
+                camera.shutter_open = words.float("shutter open time")?;
+                camera.shutter_close = words.float("shutter close time")?;
+            }
+            "blades" => {
+                let word = words.word("blade count")?;
+                let blades = word
+                    .parse()
+                    .ok()
+                    .filter(|&blades| blades >= 3)
+                    .ok_or_else(|| format!("expected at least 3 blades, found `{word}`"))?;
+                camera.bokeh = Bokeh::Polygon {
+                    blades,
+                    rotation: 0.,
+                };
+            }
+            _ => return Err(format!("unknown camera property `{key}`").into()),
+        }
+    }
+
+    if camera.focus_distance <= 0. {
+        return Err("focus distance must be positive".to_string().into());
+    }
+    if camera.shutter_close < camera.shutter_open {
+        return Err("the shutter closes before it opens".to_string().into());
+    }
+
+    // positive yaw turns the view from +z towards +x, positive pitch looks up
+    let rotation =
+        Transform::rotation_x(-pitch.to_radians()).then(&Transform::rotation_y(yaw.to_radians()));
+    let axes = [
+        Vector3::new(1., 0., 0.),
+        Vector3::new(0., 1., 0.),
+        Vector3::new(0., 0., 1.),
+    ]
+    .map(|axis| rotation.vector(axis));
+    camera.rotation = [
+        [axes[0].x, axes[1].x, axes[2].x],
+        [axes[0].y, axes[1].y, axes[2].y],
+        [axes[0].z, axes[1].z, axes[2].z],
+    ];
+
+    Ok(camera)
+}
+
+fn parse_light(words: &mut Words) -> LineResult<Light> {
+    let light_type = match words.word("light type")? {
+        "ambient" => LightType::Ambient,
+        "point" => LightType::Point,
+        "directional" => LightType::Directional,
+        other => return Err(format!("unknown light type `{other}`").into()),
+    };
+    let intensity = words.float("light intensity")?;
+    let direction = match light_type {
+        LightType::Ambient => None,
+        LightType::Point => Some(words.vector("light position")?),
+        LightType::Directional => Some(words.vector("light direction")?),
+    };
+    words.end()?;
+
+    Ok(Light {
+        intensity,
+        light_type,
+        direction,
+    })
+}
+
+/// Resolves paths against the scene file and keeps the meshes loaded so far.
+struct Loader<'a> {
+    directory: &'a Path,
+    meshes: HashMap<PathBuf, Arc<Mesh>>,
+}
+
+impl Loader<'_> {
+    fn parse_environment(&self, words: &mut Words) -> LineResult<Environment> {
+        let environment = match words.word("environment type")? {
+            "color" => Environment::Color(Color::from(<[Float; 3]>::from(
+                words.vector("environment color")?,
+            ))),
+            "map" => {
+                let path = self.directory.join(words.word("environment map path")?);
+                Environment::load_equirectangular(&path)?
+            }
+            other => return Err(format!("unknown environment type `{other}`").into()),
+        };
+        words.end()?;
+
+        Ok(environment)
+    }
+
+    fn parse_object(&mut self, keyword: &str, words: &mut Words) -> LineResult<Object> {
+        let shape = match keyword {
+            "sphere" => {
+                expect_key(words, "center")?;
+                let center = words.vector("sphere center")?;
+                expect_key(words, "radius")?;
+                let radius = words.float("sphere radius")?;
+                if radius <= 0. || !radius.is_finite() {
+                    return Err(format!(
+                        "sphere radius must be positive and finite, found {radius}"
+                    )
+                    .into());
+                }
+                Shape::Sphere { center, radius }
+            }
+            "box" => {
+                expect_key(words, "min")?;
+                let min = words.vector("box corner")?;
+                expect_key(words, "max")?;
+                let max = words.vector("box corner")?;
+                if min.x > max.x || min.y > max.y || min.z > max.z {
+                    return Err(format!("box min {min:?} is past max {max:?}").into());
+                }
+                Shape::Cuboid { min, max }
+            }
+            "mesh" => Shape::Mesh(self.mesh(words.word("mesh path")?)?),
+            _ => Shape::Mesh(Arc::new(Mesh::icosahedron())),
+        };
+
+        let mut object = Object {
+            shape,
+            transform: None,
+            velocity: Vector3::ZERO,
+            material: Material {
+                albedo: Texture::Solid(Rgb([255, 255, 255])),
+                specular: Channel::Constant(-1.),
+                reflective: Channel::Constant(0.),
+            },
+        };
+
+        while let Some(key) = words.0.next() {
+            let step = match key {
+                "scale" => {
+                    let factors = words.vector("scale factors")?;
+                    // the inverse divides by them
+                    if (0..3).any(|axis| factors[axis] == 0. || !factors[axis].is_finite()) {
+                        return Err(format!(
+                            "scale factors must be finite and non zero, found {} {} {}",
+                            factors.x, factors.y, factors.z
+                        )
+                        .into());
+                    }
+                    Some(Transform::scaling(factors))
+                }
+                "rotate_x" => Some(Transform::rotation_x(words.float("angle")?.to_radians())),
+                "rotate_y" => Some(Transform::rotation_y(words.float("angle")?.to_radians())),
+                "rotate_z" => Some(Transform::rotation_z(words.float("angle")?.to_radians())),
+                "translate" => Some(Transform::translation(words.vector("offset")?)),
+                _ => None,
+            };
+            if let Some(step) = step {
+                object.transform = Some(match &object.transform {
+                    Some(transform) => transform.then(&step),
+                    None => step,
+                });
+                continue;
+            }
+
+            match key {
+                "velocity" => object.velocity = words.vector("velocity")?,
+                "specular" => {
+                    object.material.specular = Channel::Constant(words.float("specular exponent")?)
+                }
+                "reflective" => {
+                    object.material.reflective = Channel::Constant(words.float("reflectivity")?)
+                }
+                _ => object.material.albedo = self.parse_texture(key, words)?,
+            }
+        }
+
+        Ok(object)
+    }
+
+    fn parse_texture(&self, key: &str, words: &mut Words) -> LineResult<Texture> {
+        Ok(match key {
+            "color" => Texture::Solid(words.rgb("colour")?),
+            "checker" => Texture::Checker {
+                even: words.rgb("checker colour")?,
+                odd: words.rgb("checker colour")?,
+                scale: words.pattern_scale("checker scale")?,
+            },
+            "stripes" => Texture::Stripes {
+                even: words.rgb("stripe colour")?,
+                odd: words.rgb("stripe colour")?,
+                scale: words.pattern_scale("stripe scale")?,
+            },
+            "noise" => Texture::Noise {
+                color: words.rgb("noise colour")?,
+                scale: words.pattern_scale("noise scale")?,
+            },
+            "marble" => Texture::Marble {
+                color: words.rgb("marble colour")?,
+                vein: words.rgb("vein colour")?,
+                scale: words.pattern_scale("marble scale")?,
+                turbulence: words.float("marble turbulence")?,
+            },
+            "image" => {
+                let path = self.directory.join(words.word("image path")?);
+                let wrap = match words.word("wrap mode")? {
+                    "repeat" => WrapMode::Repeat,
+                    "clamp" => WrapMode::Clamp,
+                    "mirror" => WrapMode::Mirror,
+                    other => return Err(format!("unknown wrap mode `{other}`").into()),
+                };
+                Texture::load(&path, wrap)?
+            }
+            _ => return Err(format!("unknown object property `{key}`").into()),
+        })
+    }
+
+    fn mesh(&mut self, name: &str) -> LineResult<Arc<Mesh>> {
+        let path = self.directory.join(name);
+        if let Some(mesh) = self.meshes.get(&path) {
+            return Ok(mesh.clone());
+        }
+
+        let mesh = Arc::new(Mesh::load_obj(&path)?);
+        self.meshes.insert(path, mesh.clone());
+        Ok(mesh)
+    }
+}
+
+fn expect_key(words: &mut Words, key: &str) -> Result<(), String> {
+    match words.0.next() {
+        Some(word) if word == key => Ok(()),
+        Some(word) => Err(format!("expected `{key}`, found `{word}`")),
+        None => Err(format!("missing `{key}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads `source` from a file of its own, `name` telling the tests apart.
+    fn load_source(name: &str, source: &str) -> Result<(Scene, Camera), Error> {
+        let path = std::env::temp_dir().join(format!("raytrayce-{name}.scene"));
+        fs::write(&path, source).unwrap();
+        let result = load(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn assert_bad_line(result: Result<(Scene, Camera), Error>, expected: usize) {
+        match result {
+            Err(Error::BadScene { line, .. }) => assert_eq!(line, expected),
+            Err(error) => panic!("expected a bad scene, got {error}"),
+            Ok(_) => panic!("expected a bad scene, the scene loaded"),
+        }
+    }
+
+    #[test]
+    fn patterns_need_a_scale() {
+        let good = "sphere center 0 0 3 radius 1 checker 255 255 255 0 0 0 0.5\n";
+        assert!(load_source("pattern-scale", good).is_ok());
+
+        for pattern in [
+            "checker 255 255 255 0 0 0 0",
+            "stripes 255 255 255 0 0 0 0",
+            "noise 255 255 255 0",
+            "marble 255 255 255 0 0 0 inf 2",
+        ] {
+            let source = format!("light ambient 0.2\nsphere center 0 0 3 radius 1 {pattern}\n");
+            assert_bad_line(load_source("pattern-scale", &source), 2);
+        }
+    }
+
+    #[test]
+    fn spheres_need_a_positive_radius() {
+        for radius in ["0", "-1", "inf", "NaN"] {
+            let source = format!("sphere center 0 0 3 radius {radius}\n");
+            assert_bad_line(load_source("sphere-radius", &source), 1);
+        }
+    }
+
+    #[test]
+    fn box_corners_must_be_in_order() {
+        let good = "box min -1 -1 -1 max 1 1 1\nbox min 0 0 0 max 0 1 1\n";
+        assert!(load_source("box-corners", good).is_ok());
+
+        for max in ["-2 1 1", "1 -2 1", "1 1 -2"] {
+            let source = format!("light ambient 0.2\nbox min -1 -1 -1 max {max}\n");
+            assert_bad_line(load_source("box-corners", &source), 2);
+        }
+    }
+
+    #[test]
+    fn shutters_close_after_they_open() {
+        let (_, camera) = load_source("shutter", "camera shutter 0.5 0.5\n").unwrap();
+        assert_eq!((camera.shutter_open, camera.shutter_close), (0.5, 0.5));
+
+        let source = "# motion blur\ncamera shutter 1 0\n";
+        assert_bad_line(load_source("shutter", source), 2);
+    }
+}
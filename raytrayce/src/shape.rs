@@ -0,0 +1,425 @@
+use std::sync::Arc;
+
+use crate::{consts::PI, mesh::Mesh, stats::record_intersection_test, Aabb, Float, Ray, Vector3};
+
+/// Point where a ray crosses the surface of a shape.
+/// The normal always points out of the shape and has unit length.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceHit {
+    pub t: Float,
+    pub normal: Vector3,
+    pub uv: (Float, Float),
+    /// How far `ray.at(t)` may be from the true surface point
+    /// because of rounding, see `rounding_error`.
+    pub error: Float,
+}
+
+/// Bound on the rounding error of a point computed from coordinates up to `magnitude`.
+/// The error of a hit grows with the size of everything that went into it, a point
+/// on a huge sphere far from the origin is much less exact than the same point on a small one.
+pub fn rounding_error(magnitude: Float) -> Float {
+    4. * Float::EPSILON * magnitude
+}
+
+/// Part of a ray that lies inside of a closed shape.
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    pub enter: SurfaceHit,
+    pub exit: SurfaceHit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Closed shapes, in the object space of their owner.
+#[derive(Clone)]
+pub enum Shape {
+    Sphere {
+        center: Vector3,
+        radius: Float,
+    },
+    /// Axis aligned box between two opposite corners.
+    Cuboid {
+        min: Vector3,
+        max: Vector3,
+    },
+    Csg {
+        operation: CsgOperation,
+        left: Box<Shape>,
+        right: Box<Shape>,
+    },
+    /// Shared so many objects can place copies of one mesh.
+    /// Inside CSG it is taken as closed, see `Mesh::spans`.
+    Mesh(Arc<Mesh>),
+}
+
+impl Shape {
+    /// Closest surface crossing with `t_min < t < t_max`.
+    pub fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<SurfaceHit> {
+        if let Shape::Mesh(mesh) = self {
+            return mesh.hit(ray, t_min, t_max);
+        }
+
+        if let Shape::Csg { .. } = self {
+            return self
+                .spans(ray)
+                .iter()
+                .find_map(|span| {
+                    if span.enter.t > t_min {
+                        Some(span.enter)
+                    } else if span.exit.t > t_min {
+                        Some(span.exit)
+                    } else {
+                        None
+                    }
+                })
+                .filter(|hit| hit.t < t_max);
+        }
+
+        // primitives only build the surface data for the crossing that is used
+        let (t_enter, t_exit) = self.interval(ray)?;
+        let t = if t_enter > t_min { t_enter } else { t_exit };
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        Some(self.surface(ray, t))
+    }
+
+    /// Every interval of the ray inside the shape, ordered by t.
+    /// Intervals behind the origin are reported too, so CSG can tell
+    /// whether the origin itself is inside.
+    pub fn spans(&self, ray: &Ray) -> Vec<Span> {
+        match self {
+            Shape::Csg {
+                operation,
+                left,
+                right,
+            } => combine(*operation, &left.spans(ray), &right.spans(ray)),
+            Shape::Mesh(mesh) => mesh.spans(ray),
+            _ => match self.interval(ray) {
+                Some((t_enter, t_exit)) => vec![Span {
+                    enter: self.surface(ray, t_enter),
+                    exit: self.surface(ray, t_exit),
+                }],
+                None => vec![],
+            },
+        }
+    }
+
+    /// Entry and exit t of a primitive.
+    fn interval(&self, ray: &Ray) -> Option<(Float, Float)> {
+        record_intersection_test();
+
+        match self {
+            Shape::Sphere { center, radius } => sphere_interval(ray, *center, *radius),
+            Shape::Cuboid { min, max } => cuboid_interval(ray, *min, *max),
+            Shape::Csg { .. } | Shape::Mesh(_) => None,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            Shape::Sphere { center, radius } => Aabb {
+                min: *center - Vector3::splat(*radius),
+                max: *center + Vector3::splat(*radius),
+            },
+            Shape::Cuboid { min, max } => Aabb {
+                min: *min,
+                max: *max,
+            },
+            Shape::Csg {
+                operation,
+                left,
+                right,
+            } => match operation {
+                CsgOperation::Union => left.bounds().union(&right.bounds()),
+                CsgOperation::Intersection => left.bounds().intersection(&right.bounds()),
+                CsgOperation::Difference => left.bounds(),
+            },
+            Shape::Mesh(mesh) => mesh.bounds(),
+        }
+    }
+
+    fn surface(&self, ray: &Ray, t: Float) -> SurfaceHit {
+        let position = ray.at(t);
+
+        let (normal, uv, magnitude) = match self {
+            Shape::Sphere { center, radius } => {
+                let normal = (position - *center).normalize();
+                (normal, sphere_uv(normal), center.max_abs() + radius)
+            }
+            Shape::Cuboid { min, max } => {
+                let (normal, uv) = cuboid_surface(position, *min, *max);
+                (normal, uv, min.max_abs().max(max.max_abs()))
+            }
+            Shape::Csg { .. } | Shape::Mesh(_) => (Vector3::ZERO, (0., 0.), 0.),
+        };
+
+        SurfaceHit {
+            t,
+            normal,
+            uv,
+            error: rounding_error(magnitude + ray.origin.max_abs() + (ray.direction * t).max_abs()),
+        }
+    }
+}
+
+/// Spherical (u, v) coordinates of a point given its unit normal.
+fn sphere_uv(normal: Vector3) -> (Float, Float) {
+    let u = 0.5 + normal.z.atan2(normal.x) / (2. * PI);
+    let v = 0.5 + normal.y.clamp(-1., 1.).asin() / PI;
+    (u, v)
+}
+
+fn sphere_interval(ray: &Ray, center: Vector3, radius: Float) -> Option<(Float, Float)> {
+    let f = ray.origin - center;
+    let direction = ray.direction;
+
+    // a t^2 + 2 b t + c = 0
+    let a = direction.length_squared();
+    let b = f.dot(direction);
+    let c = f.length_squared() - radius * radius;
+
+    // b^2 - a c loses every digit when the sphere is small next to its distance,
+    // the distance of the line to the center does not
+    let closest = f - direction * (b / a);
+    let discriminant = a * (radius * radius - closest.length_squared());
+    if discriminant < 0. {
+        return None;
+    }
+
+    // only the root that adds values of the same sign is taken directly,
+    // the other one follows from t1 * t2 = c / a
+    let q = -(b + b.signum() * discriminant.sqrt());
+    if q == 0. {
+        return Some((0., 0.));
+    }
+    let (t1, t2) = (q / a, c / q);
+    Some((t1.min(t2), t1.max(t2)))
+}
+
+/// Slab test.
+fn cuboid_interval(ray: &Ray, min: Vector3, max: Vector3) -> Option<(Float, Float)> {
+    let (origin, direction) = (ray.origin, ray.direction);
+
+    let mut t_enter = Float::NEG_INFINITY;
+    let mut t_exit = Float::INFINITY;
+
+    for axis in 0..3 {
+        if direction[axis] == 0. {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t0 = (min[axis] - origin[axis]) / direction[axis];
+        let t1 = (max[axis] - origin[axis]) / direction[axis];
+
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+    }
+
+    if t_enter > t_exit {
+        return None;
+    }
+
+    Some((t_enter, t_exit))
+}
+
+/// Normal of the face closest to `position` and a planar mapping of that face,
+/// stretched over the whole box extent.
+fn cuboid_surface(position: Vector3, min: Vector3, max: Vector3) -> (Vector3, (Float, Float)) {
+    let mut axis = 0;
+    let mut largest = Float::NEG_INFINITY;
+    for i in 0..3 {
+        let half = (max[i] - min[i]) / 2.;
+        let offset = (position[i] - (min[i] + half)) / half;
+        if offset.abs() > largest {
+            largest = offset.abs();
+            axis = i;
+        }
+    }
+
+    let mut normal = Vector3::ZERO;
+    normal[axis] = if position[axis] > (min[axis] + max[axis]) / 2. {
+        1.
+    } else {
+        -1.
+    };
+
+    let u_axis = (axis + 1) % 3;
+    let v_axis = (axis + 2) % 3;
+    let uv = (
+        (position[u_axis] - min[u_axis]) / (max[u_axis] - min[u_axis]),
+        (position[v_axis] - min[v_axis]) / (max[v_axis] - min[v_axis]),
+    );
+
+    (normal, uv)
+}
+
+/// Boolean combination of two ordered span lists.
+/// Walks over all boundary crossings and keeps the ones where
+/// the inside state of the result flips.
+fn combine(operation: CsgOperation, left: &[Span], right: &[Span]) -> Vec<Span> {
+    // (hit, is_left, is_enter)
+    let mut events: Vec<(SurfaceHit, bool, bool)> = vec![];
+    for span in left {
+        events.push((span.enter, true, true));
+        events.push((span.exit, true, false));
+    }
+    for span in right {
+        events.push((span.enter, false, true));
+        events.push((span.exit, false, false));
+    }
+    events.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+    let mut in_left = false;
+    let mut in_right = false;
+    let mut inside = false;
+    let mut enter: Option<SurfaceHit> = None;
+    let mut result = vec![];
+
+    for (mut hit, is_left, is_enter) in events {
+        if is_left {
+            in_left = is_enter;
+        } else {
+            in_right = is_enter;
+        }
+
+        let now_inside = operation.inside(in_left, in_right);
+        if now_inside == inside {
+            continue;
+        }
+        inside = now_inside;
+
+        // the subtracted shape is seen from its inside
+        if operation == CsgOperation::Difference && !is_left {
+            hit.normal = -hit.normal;
+        }
+
+        if inside {
+            enter = Some(hit);
+        } else if let Some(enter) = enter.take() {
+            result.push(Span { enter, exit: hit });
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit sphere at the origin with a bite of the unit sphere at (0, 0, 1) taken out of it.
+    fn bitten_sphere() -> Shape {
+        Shape::Csg {
+            operation: CsgOperation::Difference,
+            left: Box::new(Shape::Sphere {
+                center: Vector3::ZERO,
+                radius: 1.,
+            }),
+            right: Box::new(Shape::Sphere {
+                center: Vector3::new(0., 0., 1.),
+                radius: 1.,
+            }),
+        }
+    }
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-4, "{a:?} is not {b:?}");
+    }
+
+    #[test]
+    fn difference_normals_point_out_of_the_bite() {
+        let shape = bitten_sphere();
+
+        // down into the bite, whose floor is the subtracted sphere seen from inside
+        let ray = Ray::new(Vector3::new(0., 0., 5.), Vector3::new(0., 0., -1.), 0.);
+        let hit = shape.hit(&ray, 0., Float::INFINITY).unwrap();
+        assert!((hit.t - 5.).abs() < 1e-4);
+        assert_close(hit.normal, Vector3::new(0., 0., 1.));
+
+        // up through the rest of the sphere and out into the bite
+        let ray = Ray::new(Vector3::new(0., 0., -5.), Vector3::new(0., 0., 1.), 0.);
+        let spans = shape.spans(&ray);
+        assert_eq!(spans.len(), 1);
+        assert_close(spans[0].enter.normal, Vector3::new(0., 0., -1.));
+        assert!((spans[0].exit.t - 5.).abs() < 1e-4);
+        assert_close(spans[0].exit.normal, Vector3::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn difference_normals_face_against_entering_rays() {
+        let shape = Shape::Csg {
+            operation: CsgOperation::Difference,
+            left: Box::new(Shape::Cuboid {
+                min: Vector3::splat(-1.),
+                max: Vector3::splat(1.),
+            }),
+            right: Box::new(bitten_sphere()),
+        };
+
+        for i in 0..200 {
+            let angle = i as Float * 0.1;
+            let origin = Vector3::new(angle.cos() * 4., (i as Float * 0.37).sin() * 3., 4.5);
+            let target = Vector3::new(angle.sin() * 0.9, angle.cos() * 0.7, 0.2);
+            let ray = Ray::new(origin, (target - origin).normalize(), 0.);
+            let spans = shape.spans(&ray);
+            assert!(!spans.is_empty());
+            for span in spans {
+                assert!(span.enter.normal.dot(ray.direction) < 0.);
+                assert!(span.exit.normal.dot(ray.direction) > 0.);
+                assert!((span.enter.normal.length() - 1.).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_uv_wraps_around_the_equator() {
+        let uv = |x, y, z| sphere_uv(Vector3::new(x, y, z));
+        let close = |(u, v): (Float, Float), (eu, ev): (Float, Float)| {
+            assert!((u - eu).abs() < 1e-5 && (v - ev).abs() < 1e-5, "{u} {v}");
+        };
+        close(uv(1., 0., 0.), (0.5, 0.5));
+        close(uv(0., 0., 1.), (0.75, 0.5));
+        close(uv(0., 0., -1.), (0.25, 0.5));
+        close(uv(0., 1., 0.), (0.5, 1.));
+        close(uv(0., -1., 0.), (0.5, 0.));
+    }
+
+    #[test]
+    fn cuboid_uv_spans_every_face() {
+        let shape = Shape::Cuboid {
+            min: Vector3::new(0., 0., 0.),
+            max: Vector3::new(2., 4., 8.),
+        };
+        // onto the face at z = 0, u runs along x and v along y
+        let ray = Ray::new(Vector3::new(0.5, 3., -1.), Vector3::new(0., 0., 1.), 0.);
+        let hit = shape.hit(&ray, 0., Float::INFINITY).unwrap();
+        assert_close(hit.normal, Vector3::new(0., 0., -1.));
+        assert!((hit.uv.0 - 0.25).abs() < 1e-5 && (hit.uv.1 - 0.75).abs() < 1e-5);
+
+        // onto the face at x = 2, u runs along y and v along z
+        let ray = Ray::new(Vector3::new(5., 1., 6.), Vector3::new(-1., 0., 0.), 0.);
+        let hit = shape.hit(&ray, 0., Float::INFINITY).unwrap();
+        assert_close(hit.normal, Vector3::new(1., 0., 0.));
+        assert!((hit.uv.0 - 0.25).abs() < 1e-5 && (hit.uv.1 - 0.75).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,149 @@
+use image::{Rgb, RgbImage};
+use std::{cell::Cell, fmt, ops, path::Path, time::Duration};
+
+use crate::{
+    error::{Error, Result},
+    RayKind,
+};
+
+/// Work done by the rendering threads, counted per thread and collected after every sample.
+/// There is no refraction count, as no material lets light through: every ray is one of
+/// the `RayKind`s.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RayCounts {
+    pub camera_rays: u64,
+    pub reflection_rays: u64,
+    /// Diffuse bounces of path tracing.
+    pub diffuse_rays: u64,
+    pub shadow_rays: u64,
+    /// Ray against primitive tests, every part of a CSG shape and every mesh triangle counts.
+    pub intersection_tests: u64,
+    pub bvh_node_visits: u64,
+}
+
+impl RayCounts {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.reflection_rays + self.diffuse_rays + self.shadow_rays
+    }
+
+    /// Rays, intersection tests and node visits together, the cost shown by the heatmap.
+    pub fn cost(&self) -> u64 {
+        self.rays() + self.intersection_tests + self.bvh_node_visits
+    }
+}
+
+impl ops::AddAssign for RayCounts {
+    fn add_assign(&mut self, rhs: Self) {
+        self.camera_rays += rhs.camera_rays;
+        self.reflection_rays += rhs.reflection_rays;
+        self.diffuse_rays += rhs.diffuse_rays;
+        self.shadow_rays += rhs.shadow_rays;
+        self.intersection_tests += rhs.intersection_tests;
+        self.bvh_node_visits += rhs.bvh_node_visits;
+    }
+}
+
+thread_local! {
+    static COUNTS: Cell<RayCounts> = Cell::new(RayCounts::default());
+}
+
+fn update(change: impl FnOnce(&mut RayCounts)) {
+    COUNTS.with(|counts| {
+        let mut value = counts.get();
+        change(&mut value);
+        counts.set(value);
+    });
+}
+
+pub(crate) fn record_ray(kind: RayKind) {
+    update(|counts| match kind {
+        RayKind::Camera => counts.camera_rays += 1,
+        RayKind::Reflection => counts.reflection_rays += 1,
+        RayKind::Diffuse => counts.diffuse_rays += 1,
+        RayKind::Shadow => counts.shadow_rays += 1,
+    });
+}
+
+pub(crate) fn record_intersection_test() {
+    update(|counts| counts.intersection_tests += 1);
+}
+
+pub(crate) fn record_bvh_visits(visits: u64) {
+    update(|counts| counts.bvh_node_visits += visits);
+}
+
+/// Counts of the current thread since the last call.
+pub(crate) fn take_counts() -> RayCounts {
+    COUNTS.with(|counts| counts.take())
+}
+
+/// Summary of a `render_progressive` run.
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub counts: RayCounts,
+    /// Passes finished, not counting one the time limit cut short.
+    pub passes: u32,
+    /// Time spent tracing.
+    pub render_time: Duration,
+    /// Time spent writing previews and checkpoints.
+    pub output_time: Duration,
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.render_time.as_secs_f64().max(f64::EPSILON);
+        let counts = &self.counts;
+
+        writeln!(f, "Passes:             {}", self.passes)?;
+        writeln!(f, "Camera rays:        {}", counts.camera_rays)?;
+        writeln!(f, "Reflection rays:    {}", counts.reflection_rays)?;
+        writeln!(f, "Diffuse rays:       {}", counts.diffuse_rays)?;
+        writeln!(f, "Shadow rays:        {}", counts.shadow_rays)?;
+        writeln!(f, "Intersection tests: {}", counts.intersection_tests)?;
+        writeln!(f, "BVH node visits:    {}", counts.bvh_node_visits)?;
+        writeln!(
+            f,
+            "Render time:        {:.2}s ({:.2} Mrays/s)",
+            seconds,
+            counts.rays() as f64 / seconds / 1e6
+        )?;
+        write!(
+            f,
+            "Output time:        {:.2}s",
+            self.output_time.as_secs_f64()
+        )
+    }
+}
+
+/// Per pixel cost as colours from dark blue (cheapest pixel) to red (most expensive),
+/// on a logarithmic scale.
+pub(crate) fn save_heatmap(path: &Path, width: u32, height: u32, cost: &[u64]) -> Result<()> {
+    let min = (cost.iter().copied().min().unwrap_or(0).max(1) as f32).ln();
+    let max = (cost.iter().copied().max().unwrap_or(0).max(1) as f32).ln();
+    let range = (max - min).max(f32::EPSILON);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let value = (cost[(y * width + x) as usize].max(1) as f32).ln();
+        heat_color((value - min) / range)
+    })
+    .save(path)
+    .map_err(Error::image(path))
+}
+
+/// Blue, cyan, green, yellow, red for `t` going from 0 to 1.
+fn heat_color(t: f32) -> Rgb<u8> {
+    const STOPS: [[f32; 3]; 5] = [
+        [0., 0., 0.5],
+        [0., 0.8, 1.],
+        [0., 0.8, 0.],
+        [1., 0.9, 0.],
+        [1., 0., 0.],
+    ];
+
+    let position = t.clamp(0., 1.) * (STOPS.len() - 1) as f32;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let f = position - index as f32;
+    let [a, b] = [STOPS[index], STOPS[index + 1]];
+
+    Rgb([0, 1, 2].map(|i| ((a[i] * (1. - f) + b[i] * f) * 255.).round() as u8))
+}
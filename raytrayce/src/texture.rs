@@ -0,0 +1,328 @@
+use image::{Rgb, RgbImage};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    error::{Error, Result},
+    Float, Vector3,
+};
+
+/// Ken Perlin's reference permutation table.
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+/// How texel coordinates outside of the image are mapped back into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn apply(self, i: i32, size: i32) -> i32 {
+        match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        }
+    }
+}
+
+/// Colour source of a surface.
+/// Images are looked up by the surface (u, v) coordinates, procedural patterns are solid
+/// textures evaluated at the position on the object before it is placed and moved.
+/// Pattern scales must be finite and non zero.
+#[derive(Clone)]
+pub enum Texture {
+    Solid(Rgb<u8>),
+    Image {
+        image: Arc<RgbImage>,
+        wrap: WrapMode,
+    },
+    Checker {
+        even: Rgb<u8>,
+        odd: Rgb<u8>,
+        scale: Float,
+    },
+    Stripes {
+        even: Rgb<u8>,
+        odd: Rgb<u8>,
+        scale: Float,
+    },
+    Noise {
+        color: Rgb<u8>,
+        scale: Float,
+    },
+    Marble {
+        color: Rgb<u8>,
+        vein: Rgb<u8>,
+        scale: Float,
+        turbulence: Float,
+    },
+}
+
+impl Texture {
+    /// Fails for an image that does not load or has no pixels.
+    pub fn load(path: &Path, wrap: WrapMode) -> Result<Self> {
+        let image = image::open(path).map_err(Error::image(path))?.to_rgb8();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::EmptyImage {
+                path: Some(path.to_path_buf()),
+            });
+        }
+
+        Ok(Texture::Image {
+            image: Arc::new(image),
+            wrap,
+        })
+    }
+
+    /// An image without pixels, which can not be sampled.
+    pub fn is_empty_image(&self) -> bool {
+        matches!(self, Texture::Image { image, .. } if image.width() == 0 || image.height() == 0)
+    }
+
+    pub fn sample(&self, uv: (Float, Float), position: Vector3) -> Rgb<u8> {
+        match self {
+            Texture::Solid(color) => *color,
+            Texture::Image { image, wrap } => sample_bilinear(image, *wrap, uv),
+            Texture::Checker { even, odd, scale } => {
+                let cell = (position.x / scale).floor()
+                    + (position.y / scale).floor()
+                    + (position.z / scale).floor();
+                if (cell as i64).rem_euclid(2) == 0 {
+                    *even
+                } else {
+                    *odd
+                }
+            }
+            Texture::Stripes { even, odd, scale } => {
+                if ((position.x / scale).floor() as i64).rem_euclid(2) == 0 {
+                    *even
+                } else {
+                    *odd
+                }
+            }
+            Texture::Noise { color, scale } => {
+                let p = position * *scale;
+                scale_color(*color, 0.5 * (1. + perlin(p)))
+            }
+            Texture::Marble {
+                color,
+                vein,
+                scale,
+                turbulence: amount,
+            } => {
+                let p = position * *scale;
+                let t = 0.5 * (1. + (p.z + amount * turbulence(p, 7)).sin());
+                mix_color(*vein, *color, t)
+            }
+        }
+    }
+}
+
+fn scale_color(color: Rgb<u8>, k: Float) -> Rgb<u8> {
+    Rgb(color.0.map(|c| (Float::from(c) * k).round() as u8))
+}
+
+fn mix_color(a: Rgb<u8>, b: Rgb<u8>, t: Float) -> Rgb<u8> {
+    let mut res = [0u8; 3];
+    for (index, c) in res.iter_mut().enumerate() {
+        *c = (Float::from(a.0[index]) * (1. - t) + Float::from(b.0[index]) * t).round() as u8;
+    }
+    Rgb(res)
+}
+
+/// Bilinear lookup, v grows upwards while image rows grow downwards.
+fn sample_bilinear(image: &RgbImage, wrap: WrapMode, uv: (Float, Float)) -> Rgb<u8> {
+    let width = image.width() as i32;
+    let height = image.height() as i32;
+
+    let x = uv.0 * width as Float - 0.5;
+    let y = (1. - uv.1) * height as Float - 0.5;
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+
+    let texel = |dx: i32, dy: i32| {
+        let tx = wrap.apply(x0 as i32 + dx, width);
+        let ty = wrap.apply(y0 as i32 + dy, height);
+        *image.get_pixel(tx as u32, ty as u32)
+    };
+
+    let top = mix_color(texel(0, 0), texel(1, 0), fx);
+    let bottom = mix_color(texel(0, 1), texel(1, 1), fx);
+    mix_color(top, bottom, fy)
+}
+
+fn fade(t: Float) -> Float {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    a + t * (b - a)
+}
+
+fn grad(hash: u8, x: Float, y: Float, z: Float) -> Float {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Improved Perlin noise, returns values in [-1, 1].
+pub fn perlin(p: Vector3) -> Float {
+    let hash = |i: i32| PERMUTATION[(i & 255) as usize];
+
+    let xi = p.x.floor() as i32;
+    let yi = p.y.floor() as i32;
+    let zi = p.z.floor() as i32;
+
+    let x = p.x - p.x.floor();
+    let y = p.y - p.y.floor();
+    let z = p.z - p.z.floor();
+
+    let u = fade(x);
+    let v = fade(y);
+    let w = fade(z);
+
+    let a = hash(xi) as i32 + yi;
+    let aa = hash(a) as i32 + zi;
+    let ab = hash(a + 1) as i32 + zi;
+    let b = hash(xi + 1) as i32 + yi;
+    let ba = hash(b) as i32 + zi;
+    let bb = hash(b + 1) as i32 + zi;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(hash(aa), x, y, z), grad(hash(ba), x - 1., y, z)),
+            lerp(
+                u,
+                grad(hash(ab), x, y - 1., z),
+                grad(hash(bb), x - 1., y - 1., z),
+            ),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                grad(hash(aa + 1), x, y, z - 1.),
+                grad(hash(ba + 1), x - 1., y, z - 1.),
+            ),
+            lerp(
+                u,
+                grad(hash(ab + 1), x, y - 1., z - 1.),
+                grad(hash(bb + 1), x - 1., y - 1., z - 1.),
+            ),
+        ),
+    )
+}
+
+/// Sum of octaves of absolute noise.
+pub fn turbulence(p: Vector3, octaves: u32) -> Float {
+    let mut sum = 0.;
+    let mut frequency = 1.;
+    let mut weight = 1.;
+
+    for _ in 0..octaves {
+        sum += weight * perlin(p * frequency).abs();
+        frequency *= 2.;
+        weight *= 0.5;
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two by two image: black and red on the top row, green and blue below.
+    fn image(wrap: WrapMode) -> Texture {
+        let mut image = RgbImage::new(2, 2);
+        image.put_pixel(0, 0, Rgb([0, 0, 0]));
+        image.put_pixel(1, 0, Rgb([255, 0, 0]));
+        image.put_pixel(0, 1, Rgb([0, 255, 0]));
+        image.put_pixel(1, 1, Rgb([0, 0, 255]));
+        Texture::Image {
+            image: Arc::new(image),
+            wrap,
+        }
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let wrapped = |wrap: WrapMode| (-3..7).map(|i| wrap.apply(i, 3)).collect::<Vec<_>>();
+        assert_eq!(wrapped(WrapMode::Repeat), [0, 1, 2, 0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(wrapped(WrapMode::Clamp), [0, 0, 0, 0, 1, 2, 2, 2, 2, 2]);
+        assert_eq!(wrapped(WrapMode::Mirror), [2, 1, 0, 0, 1, 2, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn texel_centres_give_their_texel() {
+        let texture = image(WrapMode::Clamp);
+        let at = |u, v| texture.sample((u, v), Vector3::ZERO);
+        // v grows upwards, the top row is at v = 1
+        assert_eq!(at(0.25, 0.75), Rgb([0, 0, 0]));
+        assert_eq!(at(0.75, 0.75), Rgb([255, 0, 0]));
+        assert_eq!(at(0.25, 0.25), Rgb([0, 255, 0]));
+        assert_eq!(at(0.75, 0.25), Rgb([0, 0, 255]));
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_the_four_texels_around() {
+        let at = |wrap, u, v| image(wrap).sample((u, v), Vector3::ZERO);
+        // halfway between the two texels of the top row
+        assert_eq!(at(WrapMode::Clamp, 0.5, 0.75), Rgb([128, 0, 0]));
+        // the middle of the image averages all four
+        assert_eq!(at(WrapMode::Clamp, 0.5, 0.5), Rgb([64, 64, 64]));
+        // past the right edge clamping keeps the last column, repeating comes back around
+        assert_eq!(at(WrapMode::Clamp, 1., 0.75), Rgb([255, 0, 0]));
+        assert_eq!(at(WrapMode::Repeat, 1., 0.75), Rgb([128, 0, 0]));
+        assert_eq!(at(WrapMode::Mirror, 1., 0.75), Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let (even, odd) = (Rgb([255, 255, 255]), Rgb([0, 0, 0]));
+        let texture = Texture::Checker {
+            even,
+            odd,
+            scale: 0.5,
+        };
+        let at = |x, y, z| texture.sample((0., 0.), Vector3::new(x, y, z));
+        assert_eq!(at(0.25, 0.25, 0.25), even);
+        assert_eq!(at(0.75, 0.25, 0.25), odd);
+        assert_eq!(at(0.75, 0.75, 0.25), even);
+        assert_eq!(at(-0.25, 0.25, 0.25), odd);
+    }
+}